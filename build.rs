@@ -2,9 +2,9 @@ extern crate cc;
 extern crate glob;
 
 #[cfg(not(feature = "orangepi"))]
-const TARGET: &'static str = "wiringPi";
+const TARGET: &str = "wiringPi";
 #[cfg(feature = "orangepi")]
-const TARGET: &'static str = "WiringOP";
+const TARGET: &str = "WiringOP";
 
 fn main() {
     if cfg!(feature = "development") {
@@ -33,5 +33,5 @@ fn main() {
     if target.starts_with("aarch64") {
         cc.flag("-mabi=lp64").flag("-O2");
     }
-    cc.static_flag(true).compile("wiringpi");
+    cc.compile("wiringpi");
 }
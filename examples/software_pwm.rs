@@ -9,7 +9,7 @@ fn main() {
     let pi = wiringpi::setup_gpio();
 
     // Use pins 23 and 25 as software PWM output
-    // (note that the only hardware PWM pin is 18)
+    // (note that hardware PWM is only available on pins 12, 13, 18 and 19)
     let mut alice = pi.soft_pwm_pin(23);
     let bob = pi.soft_pwm_pin(25);
 
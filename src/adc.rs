@@ -0,0 +1,1034 @@
+//! Analog to digital converters, which make their channels available to
+//! `InputPin::analog_read` through extension nodes.
+
+use std::error::Error;
+use std::fmt;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use extension::{self, ExtensionError, Node, Registration};
+use i2c::{I2cDevice, I2cError};
+use interrupt;
+use libc;
+use pin::{Edge, InputPin, Pin, Pull, RequiresRoot, Value};
+use spi::{SpiBus, SpiError};
+
+///The ways reading an analog to digital converter can fail.
+#[derive(Debug)]
+pub enum AdcError {
+    ///The chip doesn't have the channel.
+    InvalidChannel,
+    ///The chip couldn't be registered as an extension node.
+    Extension(ExtensionError),
+    ///The SPI transfer failed.
+    Spi(SpiError),
+    ///The I2C transfer failed.
+    I2c(I2cError),
+    ///The conversion didn't finish in time.
+    Timeout,
+    ///An interrupt handler couldn't be registered for the alert pin.
+    InterruptUnavailable,
+}
+
+impl fmt::Display for AdcError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            AdcError::InvalidChannel => f.write_str("the ADC doesn't have that channel"),
+            AdcError::Extension(ref error) => write!(f, "ADC error: {}", error),
+            AdcError::Spi(ref error) => write!(f, "ADC error: {}", error),
+            AdcError::I2c(ref error) => write!(f, "ADC error: {}", error),
+            AdcError::Timeout => f.write_str("the ADC conversion didn't finish in time"),
+            AdcError::InterruptUnavailable => f.write_str("the alert pin of the ADC couldn't be set up"),
+        }
+    }
+}
+
+impl Error for AdcError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match *self {
+            AdcError::InvalidChannel | AdcError::Timeout | AdcError::InterruptUnavailable => None,
+            AdcError::Extension(ref error) => Some(error),
+            AdcError::Spi(ref error) => Some(error),
+            AdcError::I2c(ref error) => Some(error),
+        }
+    }
+}
+
+impl From<ExtensionError> for AdcError {
+    fn from(error: ExtensionError) -> AdcError {
+        AdcError::Extension(error)
+    }
+}
+
+impl From<SpiError> for AdcError {
+    fn from(error: SpiError) -> AdcError {
+        AdcError::Spi(error)
+    }
+}
+
+impl From<I2cError> for AdcError {
+    fn from(error: I2cError) -> AdcError {
+        AdcError::I2c(error)
+    }
+}
+
+///The chips in the MCP3004/3008/3204/3208 family.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Mcp3xxxChip {
+    ///4 channels with 10 bits.
+    Mcp3004,
+    ///8 channels with 10 bits.
+    Mcp3008,
+    ///4 channels with 12 bits.
+    Mcp3204,
+    ///8 channels with 12 bits.
+    Mcp3208,
+}
+
+impl Mcp3xxxChip {
+    ///The number of single-ended channels.
+    pub fn channels(self) -> u8 {
+        match self {
+            Mcp3xxxChip::Mcp3004 | Mcp3xxxChip::Mcp3204 => 4,
+            Mcp3xxxChip::Mcp3008 | Mcp3xxxChip::Mcp3208 => 8,
+        }
+    }
+
+    ///The resolution in bits.
+    pub fn bits(self) -> u8 {
+        match self {
+            Mcp3xxxChip::Mcp3004 | Mcp3xxxChip::Mcp3008 => 10,
+            Mcp3xxxChip::Mcp3204 | Mcp3xxxChip::Mcp3208 => 12,
+        }
+    }
+
+    ///The highest conversion result.
+    pub fn max_value(self) -> u16 {
+        (1 << self.bits()) - 1
+    }
+
+    ///The command for a conversion of `input`, followed by the bytes that
+    ///clock out the result.
+    fn command(self, input: AdcInput) -> [u8; 3] {
+        let (single, channel) = match input {
+            AdcInput::Single(channel) => (1, channel),
+            AdcInput::Differential(positive) => (0, positive),
+        };
+
+        if self.bits() == 10 {
+            [0x01, single << 7 | channel << 4, 0x00]
+        } else {
+            [0x04 | single << 1 | channel >> 2, channel << 6, 0x00]
+        }
+    }
+
+    ///The conversion result in the received bytes.
+    fn result(self, data: [u8; 3]) -> u16 {
+        ((data[1] as u16) << 8 | data[2] as u16) & self.max_value()
+    }
+}
+
+///An input of an analog to digital converter.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AdcInput {
+    ///The voltage of a channel, measured from ground.
+    Single(u8),
+    ///The voltage of a channel, measured from its neighbor in the pairs 0-1,
+    ///2-3, and so on. `Differential(0)` measures channel 0 from channel 1 and
+    ///`Differential(1)` measures channel 1 from channel 0. Negative
+    ///differences read 0.
+    Differential(u8),
+}
+
+struct Mcp3xxxNode {
+    chip: Mcp3xxxChip,
+    bus: Mutex<SpiBus>,
+}
+
+impl Mcp3xxxNode {
+    fn lock(&self) -> MutexGuard<'_, SpiBus> {
+        self.bus.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn read(&self, input: AdcInput) -> Result<u16, AdcError> {
+        let channel = match input {
+            AdcInput::Single(channel) | AdcInput::Differential(channel) => channel,
+        };
+
+        if channel >= self.chip.channels() {
+            return Err(AdcError::InvalidChannel);
+        }
+
+        let mut data = self.chip.command(input);
+        self.lock().transfer(&mut data)?;
+
+        Ok(self.chip.result(data))
+    }
+}
+
+impl Node for Mcp3xxxNode {
+    ///The first pins are the single-ended channels, followed by the
+    ///differential inputs. Reads 0 if the transfer fails.
+    fn analog_read(&self, pin: u16) -> i32 {
+        let channels = self.chip.channels() as u16;
+        let input = if pin < channels {
+            AdcInput::Single(pin as u8)
+        } else {
+            AdcInput::Differential((pin - channels) as u8)
+        };
+
+        self.read(input).map_or(0, |value| value as i32)
+    }
+}
+
+///An MCP3004, MCP3008, MCP3204 or MCP3208 SPI analog to digital converter.
+///
+///The inputs are registered as an extension node, so they can be read with
+///`InputPin::analog_read`. The single-ended channels are the pins from
+///`pin_base`, followed by the differential inputs. For an MCP3008 at pin base
+///100, channel 3 is pin 103 and `AdcInput::Differential(3)` is pin 111.
+///
+///The maximum clock speed depends on the supply voltage, from 1.35 MHz at
+///2.7 V to 3.6 MHz at 5 V for the MCP3008, and from 1 MHz to 2 MHz for the
+///MCP3208.
+///
+///In development mode, the chip can be simulated with
+///`adc::simulation::Mcp3xxxSlave`.
+///
+///```
+///use std::sync::{Arc, Mutex};
+///use wiringpi::adc::{AdcInput, Mcp3xxx, Mcp3xxxChip};
+///use wiringpi::adc::simulation::Mcp3xxxSlave;
+///use wiringpi::spi::{self, SpiBus, SpiChannel, SpiMode};
+///
+///let chip = Arc::new(Mutex::new(Mcp3xxxSlave::new(Mcp3xxxChip::Mcp3008)));
+///chip.lock().unwrap().set(2, 700);
+///chip.lock().unwrap().set(3, 200);
+///spi::simulation::attach(SpiChannel::Ce0, chip.clone());
+///
+///let pi = wiringpi::setup();
+///let bus = SpiBus::new(SpiChannel::Ce0, 1_000_000, SpiMode::Mode0).unwrap();
+///let adc = Mcp3xxx::new(Mcp3xxxChip::Mcp3008, bus, 100).unwrap();
+///
+///assert_eq!(pi.input_pin(102).analog_read(), 700);
+///assert_eq!(adc.read(AdcInput::Differential(2)).unwrap(), 500);
+///assert_eq!(pi.input_pin(110).analog_read(), 500);
+///assert_eq!(adc.pin(AdcInput::Differential(2)), Some(110));
+///```
+pub struct Mcp3xxx {
+    node: Arc<Mcp3xxxNode>,
+    registration: Registration,
+}
+
+impl Mcp3xxx {
+    ///Sets up `chip` on `bus` and registers its inputs from `pin_base`.
+    pub fn new(chip: Mcp3xxxChip, bus: SpiBus, pin_base: u16) -> Result<Mcp3xxx, AdcError> {
+        let node = Arc::new(Mcp3xxxNode {
+            chip,
+            bus: Mutex::new(bus),
+        });
+
+        let registration = extension::register(pin_base, chip.channels() as u16 * 2, node.clone())?;
+
+        Ok(Mcp3xxx {
+            node,
+            registration,
+        })
+    }
+
+    pub fn chip(&self) -> Mcp3xxxChip {
+        self.node.chip
+    }
+
+    ///The number of the first pin.
+    pub fn pin_base(&self) -> u16 {
+        self.registration.pin_base()
+    }
+
+    ///The pin number that reads `input` with `analog_read`, or `None` if
+    ///the chip doesn't have the channel.
+    pub fn pin(&self, input: AdcInput) -> Option<u16> {
+        let channels = self.node.chip.channels();
+        let (channel, offset) = match input {
+            AdcInput::Single(channel) => (channel, 0),
+            AdcInput::Differential(channel) => (channel, channels),
+        };
+
+        if channel >= channels {
+            return None;
+        }
+
+        //The registered pins end within the u16 range
+        Some(self.pin_base() + (offset + channel) as u16)
+    }
+
+    ///Converts `input` and returns the result.
+    pub fn read(&self, input: AdcInput) -> Result<u16, AdcError> {
+        self.node.read(input)
+    }
+
+    ///Converts `input` and returns the result in volts, relative to the
+    ///`reference` voltage on VREF.
+    pub fn read_voltage(&self, input: AdcInput, reference: f64) -> Result<f64, AdcError> {
+        self.read(input).map(|value| self.to_voltage(value, reference))
+    }
+
+    ///Converts a result to volts, relative to the `reference` voltage on
+    ///VREF.
+    pub fn to_voltage(&self, value: u16, reference: f64) -> f64 {
+        value as f64 * reference / (self.node.chip.max_value() as f64 + 1.0)
+    }
+}
+
+///The address of an ADS1x15 with ADDR connected to ground. It's 0x49, 0x4A
+///or 0x4B with ADDR connected to VDD, SDA or SCL.
+pub const ADS1X15_ADDRESS: u16 = 0x48;
+
+///The chips in the ADS1015/ADS1115 family.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Ads1x15Chip {
+    ///12 bits, up to 3300 samples per second.
+    Ads1015,
+    ///16 bits, up to 860 samples per second.
+    Ads1115,
+}
+
+impl Ads1x15Chip {
+    ///The supported data rates in samples per second.
+    pub fn data_rates(self) -> [u16; 8] {
+        match self {
+            Ads1x15Chip::Ads1015 => [128, 250, 490, 920, 1600, 2400, 3300, 3300],
+            Ads1x15Chip::Ads1115 => [8, 16, 32, 64, 128, 250, 475, 860],
+        }
+    }
+
+    ///The highest conversion result. The lowest is `-max_value() - 1`.
+    pub fn max_value(self) -> i16 {
+        match self {
+            Ads1x15Chip::Ads1015 => 2047,
+            Ads1x15Chip::Ads1115 => i16::MAX,
+        }
+    }
+
+    ///The number of bits the result is shifted left in the registers.
+    fn shift(self) -> u8 {
+        match self {
+            Ads1x15Chip::Ads1015 => 4,
+            Ads1x15Chip::Ads1115 => 0,
+        }
+    }
+}
+
+///The input voltage range of the programmable gain amplifier.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Gain {
+    ///±6.144 V, which is limited to the supply voltage in practice.
+    Fsr6_144 = 0,
+    ///±4.096 V, which is limited to the supply voltage in practice.
+    Fsr4_096,
+    ///±2.048 V, which is the default.
+    Fsr2_048,
+    ///±1.024 V.
+    Fsr1_024,
+    ///±0.512 V.
+    Fsr0_512,
+    ///±0.256 V.
+    Fsr0_256,
+}
+
+impl Gain {
+    ///The full scale voltage, which reads as the highest result.
+    pub fn full_scale(self) -> f64 {
+        match self {
+            Gain::Fsr6_144 => 6.144,
+            Gain::Fsr4_096 => 4.096,
+            Gain::Fsr2_048 => 2.048,
+            Gain::Fsr1_024 => 1.024,
+            Gain::Fsr0_512 => 0.512,
+            Gain::Fsr0_256 => 0.256,
+        }
+    }
+
+    fn from_config(config: u16) -> Gain {
+        match (config >> 9) & 0x07 {
+            0 => Gain::Fsr6_144,
+            1 => Gain::Fsr4_096,
+            2 => Gain::Fsr2_048,
+            3 => Gain::Fsr1_024,
+            4 => Gain::Fsr0_512,
+            _ => Gain::Fsr0_256,
+        }
+    }
+}
+
+///An input of an ADS1x15.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AdsInput {
+    ///The voltage of AIN0 to AIN3, measured from ground.
+    Single(u8),
+    ///AIN0 measured from AIN1.
+    Diff01,
+    ///AIN0 measured from AIN3.
+    Diff03,
+    ///AIN1 measured from AIN3.
+    Diff13,
+    ///AIN2 measured from AIN3.
+    Diff23,
+}
+
+impl AdsInput {
+    ///The MUX field of the config register, or `None` for a channel that
+    ///doesn't exist.
+    fn mux(self) -> Option<u16> {
+        match self {
+            AdsInput::Single(channel) if channel < 4 => Some(4 + channel as u16),
+            AdsInput::Single(_) => None,
+            AdsInput::Diff01 => Some(0),
+            AdsInput::Diff03 => Some(1),
+            AdsInput::Diff13 => Some(2),
+            AdsInput::Diff23 => Some(3),
+        }
+    }
+
+    ///The inputs in the order of the extension node pins.
+    fn from_pin(pin: u16) -> AdsInput {
+        match pin {
+            0..=3 => AdsInput::Single(pin as u8),
+            4 => AdsInput::Diff01,
+            5 => AdsInput::Diff03,
+            6 => AdsInput::Diff13,
+            _ => AdsInput::Diff23,
+        }
+    }
+}
+
+///How the conversions are started.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AdsMode {
+    ///Each read starts a conversion and waits for it, and the chip sleeps in
+    ///between. This is the default.
+    SingleShot,
+    ///The chip converts the selected input continuously, and reads return
+    ///the latest result.
+    Continuous,
+}
+
+///How the comparator compares the results to the thresholds.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ComparatorMode {
+    ///Asserts ALERT when a result is above the high threshold, and releases
+    ///it when a result is below the low threshold.
+    Traditional,
+    ///Asserts ALERT when a result is outside the thresholds.
+    Window,
+}
+
+///How many consecutive results have to exceed the thresholds before ALERT
+///is asserted.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ComparatorQueue {
+    One = 0,
+    Two,
+    Four,
+}
+
+///The comparator settings of an ADS1x15.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Comparator {
+    pub mode: ComparatorMode,
+    ///The low threshold, in the same units as the results.
+    pub low: i16,
+    ///The high threshold, in the same units as the results.
+    pub high: i16,
+    ///The level of ALERT when it's asserted. The default is `Low`.
+    pub active_level: Value,
+    ///Keeps ALERT asserted until the result is read, instead of releasing it
+    ///when the results are back within the thresholds.
+    pub latching: bool,
+    pub queue: ComparatorQueue,
+}
+
+const ADS_CONVERSION: u8 = 0x00;
+const ADS_CONFIG: u8 = 0x01;
+const ADS_LOW_THRESHOLD: u8 = 0x02;
+const ADS_HIGH_THRESHOLD: u8 = 0x03;
+
+const CONFIG_OS: u16 = 0x8000;
+const CONFIG_MUX: u16 = 0x7000;
+const CONFIG_PGA: u16 = 0x0E00;
+const CONFIG_MODE: u16 = 0x0100;
+const CONFIG_DR: u16 = 0x00E0;
+const CONFIG_COMP_MODE: u16 = 0x0010;
+const CONFIG_COMP_POL: u16 = 0x0008;
+const CONFIG_COMP_LAT: u16 = 0x0004;
+const CONFIG_COMP_QUE: u16 = 0x0003;
+
+///AIN0, ±2.048 V, single-shot, the default data rate and the comparator
+///disabled.
+const CONFIG_DEFAULT: u16 = 0x4583;
+
+struct AdsState {
+    device: I2cDevice,
+    ///The config register, without the OS bit.
+    config: u16,
+}
+
+struct AdsNode {
+    chip: Ads1x15Chip,
+    state: Mutex<AdsState>,
+}
+
+impl AdsNode {
+    fn lock(&self) -> MutexGuard<'_, AdsState> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn read_register(state: &AdsState, register: u8) -> Result<u16, I2cError> {
+        let mut buffer = [0; 2];
+        state.device.read_reg_block(register, &mut buffer)?;
+        Ok((buffer[0] as u16) << 8 | buffer[1] as u16)
+    }
+
+    fn write_register(state: &AdsState, register: u8, value: u16) -> Result<(), I2cError> {
+        state.device.write_reg_block(register, &[(value >> 8) as u8, value as u8])
+    }
+
+    fn set_config(state: &mut AdsState, config: u16) -> Result<(), I2cError> {
+        AdsNode::write_register(state, ADS_CONFIG, config)?;
+        state.config = config;
+        Ok(())
+    }
+
+    ///The time one conversion takes at the current data rate.
+    fn conversion_time(&self, state: &AdsState) -> Duration {
+        let rate = self.chip.data_rates()[((state.config & CONFIG_DR) >> 5) as usize];
+        Duration::from_micros(1_000_000 / rate as u64)
+    }
+
+    fn result(&self, raw: u16) -> i16 {
+        (raw as i16) >> self.chip.shift()
+    }
+
+    fn read_result(&self, state: &AdsState) -> Result<i16, AdcError> {
+        Ok(self.result(AdsNode::read_register(state, ADS_CONVERSION)?))
+    }
+
+    fn read(&self, input: AdsInput) -> Result<i16, AdcError> {
+        let mux = input.mux().ok_or(AdcError::InvalidChannel)? << 12;
+        let mut state = self.lock();
+        let config = (state.config & !CONFIG_MUX) | mux;
+        let conversion_time = self.conversion_time(&state);
+
+        if state.config & CONFIG_MODE == 0 {
+            //Continuous mode has to finish a conversion of the new input
+            if config != state.config {
+                AdsNode::set_config(&mut state, config)?;
+                thread::sleep(conversion_time + conversion_time / 10);
+            }
+
+            return self.read_result(&state);
+        }
+
+        AdsNode::write_register(&state, ADS_CONFIG, config | CONFIG_OS)?;
+        state.config = config;
+
+        let deadline = Instant::now() + conversion_time * 4 + Duration::from_millis(10);
+        thread::sleep(conversion_time);
+
+        while AdsNode::read_register(&state, ADS_CONFIG)? & CONFIG_OS == 0 {
+            if Instant::now() > deadline {
+                return Err(AdcError::Timeout);
+            }
+
+            thread::sleep(conversion_time / 10 + Duration::from_micros(50));
+        }
+
+        self.read_result(&state)
+    }
+}
+
+impl Node for AdsNode {
+    ///Pins 0 to 3 are AIN0 to AIN3, and pins 4 to 7 are the differential
+    ///inputs in the order of `AdsInput`. Negative results and failed reads
+    ///read 0.
+    fn analog_read(&self, pin: u16) -> i32 {
+        self.read(AdsInput::from_pin(pin)).map_or(0, |value| value.max(0) as i32)
+    }
+}
+
+///An ADS1015 or ADS1115 I2C analog to digital converter.
+///
+///The inputs are registered as an extension node, so they can be read with
+///`InputPin::analog_read`. AIN0 to AIN3 are the pins from `pin_base`,
+///followed by `Diff01`, `Diff03`, `Diff13` and `Diff23`. Results are signed,
+///but `analog_read` reads negative results as 0.
+///
+///The ALERT/RDY pin can be connected to a header pin with `with_alert`. The
+///latest result is read and sent to `alerts()` every time it's asserted,
+///which happens either when the comparator is triggered, or after each
+///conversion with `set_conversion_ready`.
+///
+///The pin is watched for the edge that asserts it, starting with the first
+///`set_comparator` or `set_conversion_ready` call. wiringPi can't change the
+///edge of a pin after that, so changing the active level later fails with
+///`AdcError::InterruptUnavailable`.
+///
+///In development mode, the chip can be simulated with
+///`adc::simulation::Ads1x15Slave`.
+///
+///```
+///use std::sync::{Arc, Mutex};
+///use wiringpi::adc::{Ads1x15, Ads1x15Chip, AdsInput, Gain, ADS1X15_ADDRESS};
+///use wiringpi::adc::simulation::Ads1x15Slave;
+///use wiringpi::i2c::{self, I2cDevice};
+///
+///let chip = Arc::new(Mutex::new(Ads1x15Slave::new(Ads1x15Chip::Ads1115)));
+///chip.lock().unwrap().set_voltage(1, 1.5);
+///chip.lock().unwrap().set_voltage(3, 0.5);
+///i2c::simulation::attach(1, ADS1X15_ADDRESS, chip.clone());
+///
+///let pi = wiringpi::setup();
+///let mut adc = Ads1x15::new(Ads1x15Chip::Ads1115, I2cDevice::new(ADS1X15_ADDRESS).unwrap(), 120).unwrap();
+///adc.set_gain(Gain::Fsr4_096).unwrap();
+///
+///assert_eq!(pi.input_pin(121).analog_read(), 12000);
+///assert!((adc.read_voltage(AdsInput::Diff13).unwrap() - 1.0).abs() < 0.001);
+///```
+pub struct Ads1x15 {
+    node: Arc<AdsNode>,
+    registration: Registration,
+    alert: Option<Alert>,
+}
+
+///The interrupt handling of the ALERT/RDY pin.
+struct Alert {
+    pin: libc::c_int,
+    alerts_tx: Sender<i16>,
+    alerts_rx: Receiver<i16>,
+    ///The active level and the handler for its edge, once it's known.
+    registration: Option<(Value, interrupt::Registration)>,
+}
+
+impl Ads1x15 {
+    ///Sets up `chip` at `device` and registers its inputs from `pin_base`.
+    ///The chip is reset to single-shot mode with the default gain and data
+    ///rate, and the comparator disabled.
+    pub fn new(chip: Ads1x15Chip, device: I2cDevice, pin_base: u16) -> Result<Ads1x15, AdcError> {
+        let mut state = AdsState {
+            device,
+            config: CONFIG_DEFAULT,
+        };
+
+        AdsNode::set_config(&mut state, CONFIG_DEFAULT)?;
+
+        let node = Arc::new(AdsNode {
+            chip,
+            state: Mutex::new(state),
+        });
+
+        let registration = extension::register(pin_base, 8, node.clone())?;
+
+        Ok(Ads1x15 {
+            node,
+            registration,
+            alert: None,
+        })
+    }
+
+    ///Like `new`, but with the ALERT/RDY pin of the chip connected to
+    ///`alert`, which gets its pull-up enabled since the pin is open drain.
+    pub fn with_alert<P>(chip: Ads1x15Chip, device: I2cDevice, pin_base: u16, alert: InputPin<P>) -> Result<Ads1x15, AdcError>
+        where P: Pin + RequiresRoot
+    {
+        let mut adc = Ads1x15::new(chip, device, pin_base)?;
+        let (alerts_tx, alerts_rx) = mpsc::channel();
+
+        alert.pull_up_dn_control(Pull::Up);
+
+        adc.alert = Some(Alert {
+            pin: alert.number(),
+            alerts_tx,
+            alerts_rx,
+            registration: None,
+        });
+
+        Ok(adc)
+    }
+
+    pub fn chip(&self) -> Ads1x15Chip {
+        self.node.chip
+    }
+
+    ///The number of the first pin.
+    pub fn pin_base(&self) -> u16 {
+        self.registration.pin_base()
+    }
+
+    pub fn gain(&self) -> Gain {
+        Gain::from_config(self.node.lock().config)
+    }
+
+    pub fn set_gain(&mut self, gain: Gain) -> Result<(), AdcError> {
+        self.update_config(CONFIG_PGA, (gain as u16) << 9)
+    }
+
+    ///The data rate in samples per second.
+    pub fn data_rate(&self) -> u16 {
+        self.node.chip.data_rates()[((self.node.lock().config & CONFIG_DR) >> 5) as usize]
+    }
+
+    ///Sets the data rate to the supported rate that is closest to `rate`,
+    ///in samples per second, and returns it.
+    pub fn set_data_rate(&mut self, rate: u16) -> Result<u16, AdcError> {
+        let rates = self.node.chip.data_rates();
+        let index = (0..rates.len())
+            .min_by_key(|&index| (rates[index] as i32 - rate as i32).abs())
+            .unwrap_or(0);
+
+        self.update_config(CONFIG_DR, (index as u16) << 5)?;
+        Ok(rates[index])
+    }
+
+    pub fn mode(&self) -> AdsMode {
+        if self.node.lock().config & CONFIG_MODE == 0 {
+            AdsMode::Continuous
+        } else {
+            AdsMode::SingleShot
+        }
+    }
+
+    ///Switches between single-shot and continuous conversions. Continuous
+    ///conversions start right away, with the input that was read last.
+    pub fn set_mode(&mut self, mode: AdsMode) -> Result<(), AdcError> {
+        let bits = match mode {
+            AdsMode::SingleShot => CONFIG_MODE,
+            AdsMode::Continuous => 0,
+        };
+
+        self.update_config(CONFIG_MODE, bits)
+    }
+
+    ///Sets up or disables the comparator.
+    pub fn set_comparator(&mut self, comparator: Option<Comparator>) -> Result<(), AdcError> {
+        let comparator = match comparator {
+            Some(comparator) => comparator,
+            None => return self.update_config(CONFIG_COMP_QUE, CONFIG_COMP_QUE),
+        };
+
+        let shift = self.node.chip.shift();
+        let mut bits = comparator.queue as u16;
+
+        if comparator.mode == ComparatorMode::Window {
+            bits |= CONFIG_COMP_MODE;
+        }
+
+        if comparator.active_level == Value::High {
+            bits |= CONFIG_COMP_POL;
+        }
+
+        if comparator.latching {
+            bits |= CONFIG_COMP_LAT;
+        }
+
+        self.watch_alert(comparator.active_level)?;
+
+        {
+            let state = self.node.lock();
+            AdsNode::write_register(&state, ADS_LOW_THRESHOLD, (comparator.low << shift) as u16)?;
+            AdsNode::write_register(&state, ADS_HIGH_THRESHOLD, (comparator.high << shift) as u16)?;
+        }
+
+        self.update_config(CONFIG_COMP_MODE | CONFIG_COMP_POL | CONFIG_COMP_LAT | CONFIG_COMP_QUE, bits)
+    }
+
+    ///Makes ALERT/RDY signal the end of each conversion instead of comparing
+    ///the results. It's pulsed in continuous mode, and asserted until the
+    ///next conversion starts in single-shot mode. The comparator settings
+    ///are replaced.
+    pub fn set_conversion_ready(&mut self, active_level: Value) -> Result<(), AdcError> {
+        self.watch_alert(active_level)?;
+
+        {
+            let state = self.node.lock();
+            AdsNode::write_register(&state, ADS_LOW_THRESHOLD, 0x0000)?;
+            AdsNode::write_register(&state, ADS_HIGH_THRESHOLD, 0x8000)?;
+        }
+
+        let polarity = if active_level == Value::High { CONFIG_COMP_POL } else { 0 };
+        self.update_config(CONFIG_COMP_MODE | CONFIG_COMP_POL | CONFIG_COMP_LAT | CONFIG_COMP_QUE, polarity)
+    }
+
+    ///Converts `input` and returns the result. In continuous mode, this
+    ///waits for a new conversion if the input has changed, and returns the
+    ///latest result otherwise.
+    pub fn read(&self, input: AdsInput) -> Result<i16, AdcError> {
+        self.node.read(input)
+    }
+
+    ///Returns the latest result, without starting a conversion.
+    pub fn read_latest(&self) -> Result<i16, AdcError> {
+        self.node.read_result(&self.node.lock())
+    }
+
+    ///Converts `input` and returns the result in volts.
+    pub fn read_voltage(&self, input: AdsInput) -> Result<f64, AdcError> {
+        self.read(input).map(|value| self.to_voltage(value))
+    }
+
+    ///Converts a result to volts, with the current gain.
+    pub fn to_voltage(&self, value: i16) -> f64 {
+        value as f64 * self.gain().full_scale() / (self.node.chip.max_value() as f64 + 1.0)
+    }
+
+    ///Converts volts to a result with the current gain, for example for the
+    ///comparator thresholds.
+    pub fn from_voltage(&self, voltage: f64) -> i16 {
+        let max = self.node.chip.max_value() as f64;
+        let value = (voltage * (max + 1.0) / self.gain().full_scale()).round();
+
+        value.max(-max - 1.0).min(max) as i16
+    }
+
+    ///The receiver for the results that are read when ALERT/RDY is
+    ///asserted, if it's connected.
+    pub fn alerts(&self) -> Option<&Receiver<i16>> {
+        self.alert.as_ref().map(|alert| &alert.alerts_rx)
+    }
+
+    ///Watches the ALERT/RDY pin for the edge that asserts `active_level`,
+    ///if it's connected. The previous handler is kept if the edge can't be
+    ///changed.
+    fn watch_alert(&mut self, active_level: Value) -> Result<(), AdcError> {
+        let alert = match self.alert {
+            Some(ref mut alert) => alert,
+            None => return Ok(()),
+        };
+
+        match alert.registration {
+            Some((level, _)) if level == active_level => return Ok(()),
+            _ => {},
+        }
+
+        let previous = alert.registration.take().map(|(level, _)| level);
+        let (pin, node, alerts_tx) = (alert.pin, &self.node, &alert.alerts_tx);
+        let handler = |level| {
+            let node = node.clone();
+            let alerts_tx = alerts_tx.clone();
+            let edge = if level == Value::High { Edge::Rising } else { Edge::Falling };
+
+            interrupt::Registration::new(pin, edge, move || {
+                let state = node.lock();
+
+                if let Ok(value) = node.read_result(&state) {
+                    let _ = alerts_tx.send(value);
+                }
+            }).map(|registration| (level, registration))
+        };
+
+        match handler(active_level) {
+            Some(registration) => {
+                alert.registration = Some(registration);
+                Ok(())
+            },
+            None => {
+                alert.registration = previous.and_then(handler);
+                Err(AdcError::InterruptUnavailable)
+            },
+        }
+    }
+
+    fn update_config(&mut self, mask: u16, bits: u16) -> Result<(), AdcError> {
+        let mut state = self.node.lock();
+        let config = (state.config & !mask) | bits;
+        Ok(AdsNode::set_config(&mut state, config)?)
+    }
+}
+
+///Simulated analog to digital converters for development mode.
+#[cfg(feature = "development")]
+pub mod simulation {
+    use i2c::simulation::I2cSlave;
+    use spi::simulation::SpiSlave;
+
+    use super::{Ads1x15Chip, Gain, Mcp3xxxChip};
+
+    ///A simulated MCP3004, MCP3008, MCP3204 or MCP3208, where the channels
+    ///have fixed values.
+    pub struct Mcp3xxxSlave {
+        chip: Mcp3xxxChip,
+        values: [u16; 8],
+    }
+
+    impl Mcp3xxxSlave {
+        pub fn new(chip: Mcp3xxxChip) -> Mcp3xxxSlave {
+            Mcp3xxxSlave {
+                chip,
+                values: [0; 8],
+            }
+        }
+
+        pub fn get(&self, channel: u8) -> u16 {
+            self.values[channel as usize]
+        }
+
+        ///Sets the conversion result for `channel`. It's limited to the
+        ///resolution of the chip.
+        pub fn set(&mut self, channel: u8, value: u16) {
+            self.values[channel as usize] = value.min(self.chip.max_value());
+        }
+    }
+
+    impl SpiSlave for Mcp3xxxSlave {
+        fn transfer(&mut self, data: &mut [u8]) {
+            if data.len() < 3 {
+                return;
+            }
+
+            let (single, channel) = if self.chip.bits() == 10 {
+                (data[1] & 0x80 != 0, (data[1] >> 4) & 0x07)
+            } else {
+                (data[0] & 0x02 != 0, (data[0] & 0x01) << 2 | data[1] >> 6)
+            };
+
+            let channel = channel % self.chip.channels();
+            let value = if single {
+                self.get(channel)
+            } else {
+                self.get(channel).saturating_sub(self.get(channel ^ 1))
+            };
+
+            data[0] = 0;
+            data[1] = (value >> 8) as u8;
+            data[2] = value as u8;
+        }
+    }
+
+    ///A simulated ADS1015 or ADS1115, where the inputs have fixed voltages.
+    ///
+    ///Conversions finish as soon as they are started, and the comparator
+    ///isn't simulated.
+    pub struct Ads1x15Slave {
+        chip: Ads1x15Chip,
+        voltages: [f64; 4],
+        registers: [u16; 4],
+        pointer: u8,
+    }
+
+    impl Ads1x15Slave {
+        pub fn new(chip: Ads1x15Chip) -> Ads1x15Slave {
+            Ads1x15Slave {
+                chip,
+                voltages: [0.0; 4],
+                registers: [0x0000, 0x8583, 0x8000, 0x7FFF],
+                pointer: 0,
+            }
+        }
+
+        pub fn voltage(&self, channel: u8) -> f64 {
+            self.voltages[channel as usize]
+        }
+
+        ///Sets the voltage of AIN0 to AIN3, which is used for the next
+        ///conversion.
+        pub fn set_voltage(&mut self, channel: u8, voltage: f64) {
+            self.voltages[channel as usize] = voltage;
+        }
+
+        ///The config register, as it was last written.
+        pub fn config(&self) -> u16 {
+            self.registers[1]
+        }
+
+        fn convert(&mut self) {
+            let config = self.registers[1];
+            let [ain0, ain1, ain2, ain3] = self.voltages;
+            let voltage = match (config >> 12) & 0x07 {
+                0 => ain0 - ain1,
+                1 => ain0 - ain3,
+                2 => ain1 - ain3,
+                3 => ain2 - ain3,
+                mux => self.voltages[mux as usize - 4],
+            };
+
+            let max = self.chip.max_value() as f64;
+            let value = (voltage * (max + 1.0) / Gain::from_config(config).full_scale()).round();
+            let value = value.max(-max - 1.0).min(max) as i16;
+
+            self.registers[0] = (value << if self.chip == Ads1x15Chip::Ads1015 { 4 } else { 0 }) as u16;
+        }
+    }
+
+    impl I2cSlave for Ads1x15Slave {
+        fn write(&mut self, data: &[u8]) {
+            if let Some((&pointer, value)) = data.split_first() {
+                self.pointer = pointer & 0x03;
+
+                if value.len() >= 2 && self.pointer != 0 {
+                    let value = (value[0] as u16) << 8 | value[1] as u16;
+                    self.registers[self.pointer as usize] = value;
+
+                    if self.pointer == 1 {
+                        //OS reads as 1 when the conversion is done
+                        self.registers[1] |= 0x8000;
+
+                        if value & 0x8000 != 0 || value & 0x0100 == 0 {
+                            self.convert();
+                        }
+                    }
+                }
+            }
+        }
+
+        fn read(&mut self, buffer: &mut [u8]) {
+            let value = self.registers[self.pointer as usize];
+
+            for (index, byte) in buffer.iter_mut().enumerate() {
+                *byte = if index % 2 == 0 { (value >> 8) as u8 } else { value as u8 };
+            }
+        }
+    }
+}
+
+#[cfg(all(test, feature = "development"))]
+mod tests {
+    use i2c::{self, I2cDevice};
+    use pin::{Gpio, InputPin, Value};
+    use spi::{SpiBus, SpiChannel, SpiMode};
+    use super::{AdcError, AdcInput, Ads1x15, Ads1x15Chip, Mcp3xxx, Mcp3xxxChip, CONFIG_COMP_POL, ADS1X15_ADDRESS};
+    use super::simulation::Ads1x15Slave;
+
+    #[test]
+    fn mcp3xxx_pins() {
+        let bus = SpiBus::new(SpiChannel::Ce1, 1_000_000, SpiMode::Mode0).unwrap();
+        let adc = Mcp3xxx::new(Mcp3xxxChip::Mcp3004, bus, u16::MAX - 7).unwrap();
+
+        assert_eq!(adc.pin(AdcInput::Single(0)), Some(u16::MAX - 7));
+        assert_eq!(adc.pin(AdcInput::Single(3)), Some(u16::MAX - 4));
+        assert_eq!(adc.pin(AdcInput::Differential(0)), Some(u16::MAX - 3));
+        assert_eq!(adc.pin(AdcInput::Differential(3)), Some(u16::MAX));
+
+        assert_eq!(adc.pin(AdcInput::Single(4)), None);
+        assert_eq!(adc.pin(AdcInput::Differential(4)), None);
+        assert_eq!(adc.pin(AdcInput::Differential(255)), None);
+        assert!(matches!(adc.read(AdcInput::Single(4)), Err(AdcError::InvalidChannel)));
+    }
+
+    #[test]
+    fn alert_edge_follows_first_active_level() {
+        i2c::simulation::attach(8, ADS1X15_ADDRESS, Ads1x15Slave::new(Ads1x15Chip::Ads1115));
+        let device = I2cDevice::with_bus(8, ADS1X15_ADDRESS).unwrap();
+        let mut adc = Ads1x15::with_alert(Ads1x15Chip::Ads1115, device, 1100, InputPin::<Gpio>::new(26)).unwrap();
+
+        assert!(adc.alert.as_ref().unwrap().registration.is_none());
+
+        adc.set_conversion_ready(Value::High).unwrap();
+        adc.set_conversion_ready(Value::High).unwrap();
+        assert_eq!(adc.alert.as_ref().unwrap().registration.as_ref().map(|&(level, _)| level), Some(Value::High));
+
+        assert!(matches!(adc.set_conversion_ready(Value::Low), Err(AdcError::InterruptUnavailable)));
+        assert_eq!(adc.alert.as_ref().unwrap().registration.as_ref().map(|&(level, _)| level), Some(Value::High));
+        assert_ne!(adc.node.lock().config & CONFIG_COMP_POL, 0);
+    }
+}
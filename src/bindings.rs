@@ -88,19 +88,29 @@ macro_rules! binding_functions {
     };
 }
 
+///The time since the development mode setup, to let `millis` and `micros`
+///behave like a real clock.
+#[cfg(feature = "development")]
+fn elapsed() -> ::std::time::Duration {
+    static EPOCH: ::std::sync::OnceLock<::std::time::Instant> = ::std::sync::OnceLock::new();
+    EPOCH.get_or_init(::std::time::Instant::now).elapsed()
+}
+
 binding_functions! {
-    pub fn wiringPiSetup() -> ::libc::c_int; 0;
-    pub fn wiringPiSetupSys() -> ::libc::c_int; 0;
-    pub fn wiringPiSetupGpio() -> ::libc::c_int; 0;
-    pub fn wiringPiSetupPhys() -> ::libc::c_int; 0;
+    pub fn wiringPiSetup() -> ::libc::c_int; { elapsed(); 0 };
+    pub fn wiringPiSetupSys() -> ::libc::c_int; { elapsed(); 0 };
+    pub fn wiringPiSetupGpio() -> ::libc::c_int; { elapsed(); 0 };
+    pub fn wiringPiSetupPhys() -> ::libc::c_int; { elapsed(); 0 };
     pub fn pinModeAlt(pin: ::libc::c_int, mode: ::libc::c_int) -> (); ();
-    pub fn pinMode(pin: ::libc::c_int, mode: ::libc::c_int) -> (); ();
-    pub fn pullUpDnControl(pin: ::libc::c_int, pud: ::libc::c_int) -> (); ();
-    pub fn digitalRead(pin: ::libc::c_int) -> ::libc::c_int; 0;
-    pub fn digitalWrite(pin: ::libc::c_int, value: ::libc::c_int) -> (); ();
-    pub fn pwmWrite(pin: ::libc::c_int, value: ::libc::c_int) -> (); ();
-    pub fn analogRead(pin: ::libc::c_int) -> ::libc::c_int; 0;
-    pub fn analogWrite(pin: ::libc::c_int, value: ::libc::c_int) -> (); ();
+    pub fn pinMode(pin: ::libc::c_int, mode: ::libc::c_int) -> (); ::extension::simulation::pin_mode(pin, mode);
+    pub fn pullUpDnControl(pin: ::libc::c_int, pud: ::libc::c_int) -> (); ::extension::simulation::pull_up_dn_control(pin, pud);
+    pub fn digitalRead(pin: ::libc::c_int) -> ::libc::c_int; ::extension::simulation::digital_read(pin);
+    pub fn digitalWrite(pin: ::libc::c_int, value: ::libc::c_int) -> (); ::extension::simulation::digital_write(pin, value);
+    pub fn pwmWrite(pin: ::libc::c_int, value: ::libc::c_int) -> (); ::extension::simulation::pwm_write(pin, value);
+    pub fn analogRead(pin: ::libc::c_int) -> ::libc::c_int; ::extension::simulation::analog_read(pin);
+    pub fn analogWrite(pin: ::libc::c_int, value: ::libc::c_int) -> (); ::extension::simulation::analog_write(pin, value);
+    pub fn wiringPiFindNode(pin: ::libc::c_int) -> *mut wiringPiNodeStruct; ::extension::simulation::find_node(pin);
+    pub fn wiringPiNewNode(pinBase: ::libc::c_int, numPins: ::libc::c_int) -> *mut wiringPiNodeStruct; ::extension::simulation::new_node(pinBase, numPins);
     pub fn wiringPiSetupPiFace() -> ::libc::c_int; 0;
     pub fn wiringPiSetupPiFaceForGpioProg() -> ::libc::c_int; 0;
     pub fn piBoardRev() -> ::libc::c_int; 0;
@@ -118,6 +128,22 @@ binding_functions! {
     pub fn pwmSetRange(range: ::libc::c_uint) -> (); ();
     pub fn pwmSetClock(divisor: ::libc::c_int) -> (); ();
     pub fn gpioClockSet(pin: ::libc::c_int, freq: ::libc::c_int) -> (); ();
+    pub fn wiringPiI2CRead(fd: ::libc::c_int) -> ::libc::c_int; ::i2c::simulation::smbus_read(fd, None, 1);
+    pub fn wiringPiI2CReadReg8(fd: ::libc::c_int, reg: ::libc::c_int) -> ::libc::c_int; ::i2c::simulation::smbus_read(fd, Some(reg), 1);
+    pub fn wiringPiI2CReadReg16(fd: ::libc::c_int, reg: ::libc::c_int) -> ::libc::c_int; ::i2c::simulation::smbus_read(fd, Some(reg), 2);
+    pub fn wiringPiI2CWrite(fd: ::libc::c_int, data: ::libc::c_int) -> ::libc::c_int; ::i2c::simulation::smbus_write(fd, &[data as u8]);
+    pub fn wiringPiI2CWriteReg8(fd: ::libc::c_int, reg: ::libc::c_int, data: ::libc::c_int) -> ::libc::c_int; ::i2c::simulation::smbus_write(fd, &[reg as u8, data as u8]);
+    pub fn wiringPiI2CWriteReg16(fd: ::libc::c_int, reg: ::libc::c_int, data: ::libc::c_int) -> ::libc::c_int; ::i2c::simulation::smbus_write(fd, &[reg as u8, data as u8, (data >> 8) as u8]);
+    pub fn wiringPiSPIGetFd(channel: ::libc::c_int) -> ::libc::c_int; -1;
+    pub fn wiringPiSPIDataRW(channel: ::libc::c_int, data: *mut ::libc::c_uchar, len: ::libc::c_int) -> ::libc::c_int; ::spi::simulation::data_rw(channel, data, len);
+    pub fn wiringPiSPISetupMode(channel: ::libc::c_int, speed: ::libc::c_int, mode: ::libc::c_int) -> ::libc::c_int; 0;
+    pub fn serialOpen(device: *const ::libc::c_char, baud: ::libc::c_int) -> ::libc::c_int; ::serial::native::open(device, baud);
+    pub fn serialClose(fd: ::libc::c_int) -> (); ::serial::native::close(fd);
+    pub fn serialFlush(fd: ::libc::c_int) -> (); ::serial::native::flush(fd);
+    pub fn serialPutchar(fd: ::libc::c_int, c: ::libc::c_uchar) -> (); ::serial::native::putchar(fd, c);
+    pub fn serialPuts(fd: ::libc::c_int, s: *const ::libc::c_char) -> (); ::serial::native::puts(fd, s);
+    pub fn serialDataAvail(fd: ::libc::c_int) -> ::libc::c_int; ::serial::native::data_avail(fd);
+    pub fn serialGetchar(fd: ::libc::c_int) -> ::libc::c_int; ::serial::native::getchar(fd);
     pub fn waitForInterrupt(pin: ::libc::c_int, mS: ::libc::c_int)
      -> ::libc::c_int; 0;
     pub fn wiringPiISR(pin: ::libc::c_int, mode: ::libc::c_int,
@@ -132,12 +158,15 @@ binding_functions! {
     pub fn piLock(key: ::libc::c_int) -> (); ();
     pub fn piUnlock(key: ::libc::c_int) -> (); ();
     pub fn piHiPri(pri: ::libc::c_int) -> ::libc::c_int; 0;
-    pub fn delay(howLong: ::libc::c_uint) -> (); ();
-    pub fn delayMicroseconds(howLong: ::libc::c_uint) -> (); ();
-    pub fn millis() -> ::libc::c_uint; 0;
-    pub fn micros() -> ::libc::c_uint; 0;
+    pub fn delay(howLong: ::libc::c_uint) -> (); ::std::thread::sleep(::std::time::Duration::from_millis(howLong as u64));
+    pub fn delayMicroseconds(howLong: ::libc::c_uint) -> (); ::std::thread::sleep(::std::time::Duration::from_micros(howLong as u64));
+    pub fn millis() -> ::libc::c_uint; elapsed().as_millis() as ::libc::c_uint;
+    pub fn micros() -> ::libc::c_uint; elapsed().as_micros() as ::libc::c_uint;
     pub fn softPwmCreate(pin: ::libc::c_int, value: ::libc::c_int,
                          range: ::libc::c_int) -> ::libc::c_int; 0;
     pub fn softPwmWrite(pin: ::libc::c_int, value: ::libc::c_int) -> (); ();
     pub fn softPwmStop(pin: ::libc::c_int) -> (); ();
+    pub fn softToneCreate(pin: ::libc::c_int) -> ::libc::c_int; 0;
+    pub fn softToneStop(pin: ::libc::c_int) -> (); ();
+    pub fn softToneWrite(pin: ::libc::c_int, freq: ::libc::c_int) -> (); ();
 }
@@ -0,0 +1,488 @@
+//! Push button gesture recognition.
+
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, Sender};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+use bindings;
+use interrupt::Registration;
+use libc;
+use pin::{Edge, InputPin, Pin, Value};
+
+///The gestures and state changes reported by a `Button`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ButtonEvent {
+    ///The button was pressed down.
+    Pressed,
+    ///The button was released.
+    Released,
+    ///The button was pressed and released once, before the long press
+    ///threshold.
+    Click,
+    ///The button was clicked twice within the double click window.
+    DoubleClick,
+    ///The button has been held down for the long press threshold.
+    LongPress,
+    ///The button is still held down after a long press. Repeated with the
+    ///configured repeat interval.
+    Repeat,
+}
+
+///Timing thresholds and polarity for a `Button`.
+#[derive(Debug, Clone, Copy)]
+pub struct ButtonConfig {
+    ///The level read on the pin when the button is pressed. Use `Low` for
+    ///buttons connecting the pin to ground with a pull-up resistor, and
+    ///`High` for buttons connecting it to 3.3v with a pull-down resistor.
+    pub active_level: Value,
+
+    ///How long the pin has to be stable before a change is accepted.
+    pub debounce: Duration,
+
+    ///The longest time between releasing the button and pressing it again
+    ///for the two presses to count as a `DoubleClick`. Clicks are reported
+    ///immediately, without waiting for a second click, if this is `None`.
+    pub double_click: Option<Duration>,
+
+    ///How long the button has to be held down to trigger a `LongPress`.
+    pub long_press: Duration,
+
+    ///The interval between `Repeat` events while the button is held down
+    ///after a long press. No `Repeat` events are sent if this is `None`.
+    pub repeat: Option<Duration>,
+}
+
+impl Default for ButtonConfig {
+    fn default() -> ButtonConfig {
+        ButtonConfig {
+            active_level: Value::Low,
+            debounce: Duration::from_millis(20),
+            double_click: Some(Duration::from_millis(300)),
+            long_press: Duration::from_millis(800),
+            repeat: None,
+        }
+    }
+}
+
+enum Signal {
+    Edge,
+    Stop,
+}
+
+///A debounced push button on an `InputPin`.
+///
+///Every change on the pin is picked up by an interrupt handler and passed on
+///to a worker thread, which debounces the signal and turns it into
+///`ButtonEvent`s. The events are read from the receiver returned by
+///`events()`.
+///
+///```no_run
+///use wiringpi::button::{Button, ButtonConfig, ButtonEvent};
+///use wiringpi::pin::Pull;
+///
+///let pi = wiringpi::setup_gpio();
+///let pin = pi.input_pin(17);
+///pin.pull_up_dn_control(Pull::Up);
+///
+///let button = Button::new(pin, ButtonConfig::default()).unwrap();
+///
+///for event in button.events() {
+///    if event == ButtonEvent::DoubleClick {
+///        println!("double click!");
+///    }
+///}
+///```
+pub struct Button<P> {
+    pin: InputPin<P>,
+    active_level: Value,
+    events: Receiver<ButtonEvent>,
+    _registration: Registration,
+    _worker: Worker,
+}
+
+impl<P: Pin> Button<P> {
+    ///Starts recognizing button presses on `pin`.
+    ///
+    ///Returns `None` if an interrupt handler could not be registered for the
+    ///pin.
+    pub fn new(pin: InputPin<P>, config: ButtonConfig) -> Option<Button<P>> {
+        let number = pin.number();
+        let (signal_tx, signal_rx) = mpsc::channel();
+        let (event_tx, event_rx) = mpsc::channel();
+
+        let isr_tx = signal_tx.clone();
+        let registration = Registration::new(number, Edge::Both, move || {
+            let _ = isr_tx.send(Signal::Edge);
+        })?;
+
+        let handle = thread::spawn(move || {
+            let read_pressed = || is_active(number, config.active_level);
+            Recognizer::new(config, read_pressed(), event_tx).run(signal_rx, read_pressed)
+        });
+
+        Some(Button {
+            pin,
+            active_level: config.active_level,
+            events: event_rx,
+            _registration: registration,
+            _worker: Worker {
+                signals: signal_tx,
+                handle: Some(handle),
+            },
+        })
+    }
+
+    #[inline]
+    pub fn number(&self) -> libc::c_int {
+        self.pin.number()
+    }
+
+    ///The receiver for the recognized events.
+    pub fn events(&self) -> &Receiver<ButtonEvent> {
+        &self.events
+    }
+
+    ///Checks if the button is held down right now, without any debouncing.
+    pub fn is_pressed(&self) -> bool {
+        self.pin.digital_read() == self.active_level
+    }
+
+    ///Stops the event recognition and returns the underlying pin.
+    pub fn into_inner(self) -> InputPin<P> {
+        let Button { pin, .. } = self;
+        pin
+    }
+}
+
+struct Worker {
+    signals: Sender<Signal>,
+    handle: Option<JoinHandle<()>>,
+}
+
+impl Drop for Worker {
+    fn drop(&mut self) {
+        let _ = self.signals.send(Signal::Stop);
+
+        if let Some(handle) = self.handle.take() {
+            let _ = handle.join();
+        }
+    }
+}
+
+///The debouncing and gesture state machine of a `Button`, which is fed with
+///edges and timestamps.
+struct Recognizer {
+    config: ButtonConfig,
+    events: Sender<ButtonEvent>,
+    pressed: bool,
+    settle_at: Option<Instant>,
+    long_press_at: Option<Instant>,
+    repeat_at: Option<Instant>,
+    click_expires_at: Option<Instant>,
+    second_press: bool,
+}
+
+impl Recognizer {
+    fn new(config: ButtonConfig, pressed: bool, events: Sender<ButtonEvent>) -> Recognizer {
+        Recognizer {
+            config,
+            events,
+            pressed,
+            settle_at: None,
+            long_press_at: None,
+            repeat_at: None,
+            click_expires_at: None,
+            second_press: false,
+        }
+    }
+
+    fn run<F: Fn() -> bool>(mut self, signals: Receiver<Signal>, read_pressed: F) {
+        loop {
+            let deadline = [self.settle_at, self.long_press_at, self.repeat_at, self.click_expires_at]
+                .iter()
+                .filter_map(|&deadline| deadline)
+                .min();
+
+            let signal = match deadline {
+                Some(deadline) => {
+                    let now = Instant::now();
+                    let timeout = if deadline > now { deadline - now } else { Duration::from_millis(0) };
+
+                    match signals.recv_timeout(timeout) {
+                        Ok(signal) => Some(signal),
+                        Err(RecvTimeoutError::Timeout) => None,
+                        Err(RecvTimeoutError::Disconnected) => return,
+                    }
+                },
+                None => match signals.recv() {
+                    Ok(signal) => Some(signal),
+                    Err(_) => return,
+                },
+            };
+
+            match signal {
+                Some(Signal::Edge) => self.edge(Instant::now()),
+                Some(Signal::Stop) => return,
+                None => {},
+            }
+
+            if !self.handle_deadlines(Instant::now(), &read_pressed) {
+                return;
+            }
+        }
+    }
+
+    ///Restarts the debounce time after a change on the pin.
+    fn edge(&mut self, now: Instant) {
+        self.settle_at = Some(now + self.config.debounce);
+    }
+
+    ///Handles every deadline that has passed, where `read_pressed` checks
+    ///the pin once it has settled. Returns `false` if the receiving end is
+    ///gone.
+    fn handle_deadlines<F: Fn() -> bool>(&mut self, now: Instant, read_pressed: F) -> bool {
+        if passed(self.settle_at, now) {
+            self.settle_at = None;
+            let pressed = read_pressed();
+
+            if pressed != self.pressed {
+                self.pressed = pressed;
+
+                let sent = if pressed {
+                    self.press(now)
+                } else {
+                    self.release(now)
+                };
+
+                if !sent {
+                    return false;
+                }
+            }
+        }
+
+        if passed(self.long_press_at, now) {
+            self.long_press_at = None;
+            self.repeat_at = self.config.repeat.map(|repeat| now + repeat);
+
+            if self.second_press {
+                self.second_press = false;
+
+                if !self.send(ButtonEvent::Click) {
+                    return false;
+                }
+            }
+
+            if !self.send(ButtonEvent::LongPress) {
+                return false;
+            }
+        }
+
+        if let (Some(repeat_at), Some(repeat)) = (self.repeat_at, self.config.repeat) {
+            if repeat_at <= now {
+                self.repeat_at = Some(repeat_at + repeat);
+
+                if !self.send(ButtonEvent::Repeat) {
+                    return false;
+                }
+            }
+        }
+
+        if passed(self.click_expires_at, now) {
+            self.click_expires_at = None;
+
+            if !self.send(ButtonEvent::Click) {
+                return false;
+            }
+        }
+
+        true
+    }
+
+    fn press(&mut self, now: Instant) -> bool {
+        self.long_press_at = Some(now + self.config.long_press);
+
+        if self.click_expires_at.take().is_some() {
+            self.second_press = true;
+        }
+
+        self.send(ButtonEvent::Pressed)
+    }
+
+    fn release(&mut self, now: Instant) -> bool {
+        let long_press = self.long_press_at.take().is_none();
+        let second_press = self.second_press;
+        self.repeat_at = None;
+        self.second_press = false;
+
+        if !self.send(ButtonEvent::Released) {
+            return false;
+        }
+
+        if long_press {
+            true
+        } else if second_press {
+            self.send(ButtonEvent::DoubleClick)
+        } else if let Some(double_click) = self.config.double_click {
+            self.click_expires_at = Some(now + double_click);
+            true
+        } else {
+            self.send(ButtonEvent::Click)
+        }
+    }
+
+    fn send(&self, event: ButtonEvent) -> bool {
+        self.events.send(event).is_ok()
+    }
+}
+
+fn passed(deadline: Option<Instant>, now: Instant) -> bool {
+    match deadline {
+        Some(deadline) => deadline <= now,
+        None => false,
+    }
+}
+
+fn is_active(pin: libc::c_int, active_level: Value) -> bool {
+    let value = unsafe {
+        bindings::digitalRead(pin)
+    };
+
+    let level = if value == 0 { Value::Low } else { Value::High };
+    level == active_level
+}
+
+
+#[cfg(test)]
+mod tests {
+    use std::sync::mpsc::{self, Receiver};
+    use std::time::{Duration, Instant};
+
+    use super::{ButtonConfig, ButtonEvent, Recognizer};
+
+    struct Harness {
+        recognizer: Recognizer,
+        events: Receiver<ButtonEvent>,
+        start: Instant,
+        pressed: bool,
+    }
+
+    impl Harness {
+        fn new(config: ButtonConfig) -> Harness {
+            let (events_tx, events) = mpsc::channel();
+
+            Harness {
+                recognizer: Recognizer::new(config, false, events_tx),
+                events,
+                start: Instant::now(),
+                pressed: false,
+            }
+        }
+
+        fn at(&self, millis: u64) -> Instant {
+            self.start + Duration::from_millis(millis)
+        }
+
+        ///Changes the level of the pin at `millis`.
+        fn set(&mut self, millis: u64, pressed: bool) {
+            let now = self.at(millis);
+            self.pressed = pressed;
+            self.recognizer.edge(now);
+        }
+
+        ///Handles the deadlines at `millis` and returns the new events.
+        fn tick(&mut self, millis: u64) -> Vec<ButtonEvent> {
+            let now = self.at(millis);
+            let pressed = self.pressed;
+            assert!(self.recognizer.handle_deadlines(now, || pressed));
+            self.events.try_iter().collect()
+        }
+    }
+
+    #[test]
+    fn debounced_click() {
+        let mut button = Harness::new(ButtonConfig::default());
+
+        button.set(0, true);
+        button.set(5, false);
+        button.set(10, true);
+        assert_eq!(button.tick(29), vec![]);
+        assert_eq!(button.tick(30), vec![ButtonEvent::Pressed]);
+
+        button.set(100, false);
+        assert_eq!(button.tick(120), vec![ButtonEvent::Released]);
+        assert_eq!(button.tick(419), vec![]);
+        assert_eq!(button.tick(420), vec![ButtonEvent::Click]);
+    }
+
+    #[test]
+    fn bounce_without_change() {
+        let mut button = Harness::new(ButtonConfig::default());
+
+        button.set(0, true);
+        button.set(10, false);
+        assert_eq!(button.tick(30), vec![]);
+        assert_eq!(button.tick(2000), vec![]);
+    }
+
+    #[test]
+    fn click_without_double_click() {
+        let mut button = Harness::new(ButtonConfig {
+            double_click: None,
+            ..ButtonConfig::default()
+        });
+
+        button.set(0, true);
+        assert_eq!(button.tick(20), vec![ButtonEvent::Pressed]);
+        button.set(100, false);
+        assert_eq!(button.tick(120), vec![ButtonEvent::Released, ButtonEvent::Click]);
+    }
+
+    #[test]
+    fn double_click() {
+        let mut button = Harness::new(ButtonConfig::default());
+
+        button.set(0, true);
+        assert_eq!(button.tick(20), vec![ButtonEvent::Pressed]);
+        button.set(100, false);
+        assert_eq!(button.tick(120), vec![ButtonEvent::Released]);
+        button.set(300, true);
+        assert_eq!(button.tick(320), vec![ButtonEvent::Pressed]);
+        button.set(400, false);
+        assert_eq!(button.tick(420), vec![ButtonEvent::Released, ButtonEvent::DoubleClick]);
+        assert_eq!(button.tick(2000), vec![]);
+    }
+
+    #[test]
+    fn second_press_held_down() {
+        let mut button = Harness::new(ButtonConfig::default());
+
+        button.set(0, true);
+        assert_eq!(button.tick(20), vec![ButtonEvent::Pressed]);
+        button.set(100, false);
+        assert_eq!(button.tick(120), vec![ButtonEvent::Released]);
+        button.set(300, true);
+        assert_eq!(button.tick(320), vec![ButtonEvent::Pressed]);
+        assert_eq!(button.tick(1120), vec![ButtonEvent::Click, ButtonEvent::LongPress]);
+        button.set(1500, false);
+        assert_eq!(button.tick(1520), vec![ButtonEvent::Released]);
+    }
+
+    #[test]
+    fn long_press_with_repeat() {
+        let mut button = Harness::new(ButtonConfig {
+            repeat: Some(Duration::from_millis(100)),
+            ..ButtonConfig::default()
+        });
+
+        button.set(0, true);
+        assert_eq!(button.tick(20), vec![ButtonEvent::Pressed]);
+        assert_eq!(button.tick(819), vec![]);
+        assert_eq!(button.tick(820), vec![ButtonEvent::LongPress]);
+        assert_eq!(button.tick(920), vec![ButtonEvent::Repeat]);
+        assert_eq!(button.tick(1020), vec![ButtonEvent::Repeat]);
+
+        button.set(1050, false);
+        assert_eq!(button.tick(1070), vec![ButtonEvent::Released]);
+        assert_eq!(button.tick(2000), vec![]);
+    }
+}
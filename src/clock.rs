@@ -0,0 +1,292 @@
+//! Direct access to the general purpose clock registers, for the settings
+//! that wiringPi doesn't expose.
+//!
+//! The registers are only touched on the SoCs whose clock manager layout is
+//! known, which are the BCM2835, BCM2836, BCM2837 and BCM2711.
+
+use pin::{ClockChannel, ClockError, ClockSource, Mash};
+
+///The offset of the clock manager from the peripheral base address.
+#[cfg(not(feature = "development"))]
+const CLOCK_MANAGER_OFFSET: u64 = 0x101000;
+
+const PASSWORD: u32 = 0x5A << 24;
+const ENABLE: u32 = 1 << 4;
+#[cfg(not(feature = "development"))]
+const BUSY: u32 = 1 << 7;
+
+///The settings for one clock generator.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Divisor {
+    pub integer: u32,
+    pub fraction: u32,
+}
+
+impl Divisor {
+    ///The divisor that gets closest to `frequency` from a source at
+    ///`source_frequency`, both in Hz.
+    pub fn for_frequency(source_frequency: f64, mash: Mash, frequency: u32) -> Result<Divisor, ClockError> {
+        if frequency == 0 {
+            return Err(ClockError::FrequencyOutOfRange);
+        }
+
+        let ratio = source_frequency / frequency as f64;
+
+        let (integer, fraction) = match mash {
+            Mash::Integer => (ratio.round() as u64, 0),
+            _ => {
+                let scaled = (ratio * 4096.0).round() as u64;
+                (scaled / 4096, (scaled % 4096) as u32)
+            },
+        };
+
+        let min_integer = match mash {
+            Mash::Integer => 1,
+            Mash::Stage1 => 2,
+            Mash::Stage2 => 3,
+            Mash::Stage3 => 5,
+        };
+
+        if integer < min_integer || integer > 4095 {
+            return Err(ClockError::FrequencyOutOfRange);
+        }
+
+        Ok(Divisor {
+            integer: integer as u32,
+            fraction,
+        })
+    }
+
+    ///The average output frequency in Hz, from a source at
+    ///`source_frequency`.
+    pub fn frequency(&self, source_frequency: f64) -> f64 {
+        source_frequency / (self.integer as f64 + self.fraction as f64 / 4096.0)
+    }
+}
+
+///The SoCs with a known clock manager.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(any(feature = "development", feature = "orangepi"), allow(dead_code))]
+enum Soc {
+    Bcm2835,
+    ///Also the BCM2837, which has the same peripheral addresses.
+    Bcm2836,
+    Bcm2711,
+}
+
+///The frequency of `source` in Hz, which depends on the SoC.
+pub fn source_frequency(source: ClockSource) -> Result<f64, ClockError> {
+    let bcm2711 = soc()? == Soc::Bcm2711;
+
+    let frequency = match source {
+        ClockSource::Oscillator if bcm2711 => 54_000_000.0,
+        ClockSource::Oscillator => 19_200_000.0,
+        ClockSource::PllC => 1_000_000_000.0,
+        ClockSource::PllD if bcm2711 => 750_000_000.0,
+        ClockSource::PllD => 500_000_000.0,
+        ClockSource::HdmiAux => 216_000_000.0,
+    };
+
+    Ok(frequency)
+}
+
+///Stops the clock generator and restarts it with new settings.
+pub fn start(channel: ClockChannel, source: ClockSource, mash: Mash, divisor: Divisor) -> Result<(), ClockError> {
+    let control = PASSWORD | (mash as u32) << 9 | source as u32;
+
+    stop(channel)?;
+    write(channel, Register::Divisor, PASSWORD | divisor.integer << 12 | divisor.fraction)?;
+    write(channel, Register::Control, control)?;
+    write(channel, Register::Control, control | ENABLE)
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Register {
+    Control,
+    Divisor,
+}
+
+impl Register {
+    ///The index of the register in the clock manager, counted in 32 bit
+    ///words.
+    #[cfg(not(feature = "development"))]
+    fn index(self, channel: ClockChannel) -> usize {
+        let control = 0x70 / 4 + channel as usize * 2;
+
+        match self {
+            Register::Control => control,
+            Register::Divisor => control + 1,
+        }
+    }
+}
+
+#[cfg(not(feature = "development"))]
+mod registers {
+    use std::sync::OnceLock;
+
+    use libc;
+
+    use pin::ClockError;
+    use super::Soc;
+
+    ///The clock manager registers, mapped from `/dev/mem`.
+    pub fn clock_manager() -> Result<*mut u32, ClockError> {
+        static CLOCK_MANAGER: OnceLock<Option<usize>> = OnceLock::new();
+
+        let base = match super::soc()? {
+            Soc::Bcm2835 => 0x2000_0000,
+            Soc::Bcm2836 => 0x3F00_0000,
+            Soc::Bcm2711 => 0xFE00_0000,
+        };
+
+        CLOCK_MANAGER.get_or_init(|| unsafe {
+            let fd = libc::open(b"/dev/mem\0".as_ptr() as *const libc::c_char, libc::O_RDWR | libc::O_SYNC);
+
+            if fd < 0 {
+                return None;
+            }
+
+            let address = libc::mmap(
+                ::std::ptr::null_mut(),
+                4096,
+                libc::PROT_READ | libc::PROT_WRITE,
+                libc::MAP_SHARED,
+                fd,
+                (base + super::CLOCK_MANAGER_OFFSET) as libc::off_t,
+            );
+
+            libc::close(fd);
+
+            if address == libc::MAP_FAILED {
+                None
+            } else {
+                Some(address as usize)
+            }
+        }).map(|address| address as *mut u32).ok_or(ClockError::RegisterAccess)
+    }
+}
+
+///Identifies the SoC from the device tree. Other SoCs, like the BCM2712 in
+///the Pi 5, have a different clock manager and are not supported.
+#[cfg(all(not(feature = "development"), not(feature = "orangepi")))]
+fn soc() -> Result<Soc, ClockError> {
+    use std::fs;
+    use std::sync::OnceLock;
+
+    static SOC: OnceLock<Option<Soc>> = OnceLock::new();
+
+    SOC.get_or_init(|| {
+        let compatible = fs::read("/proc/device-tree/compatible").unwrap_or_default();
+
+        compatible.split(|&byte| byte == 0).filter_map(|name| match name {
+            b"brcm,bcm2835" => Some(Soc::Bcm2835),
+            b"brcm,bcm2836" | b"brcm,bcm2837" => Some(Soc::Bcm2836),
+            b"brcm,bcm2711" => Some(Soc::Bcm2711),
+            _ => None,
+        }).next()
+    }).ok_or(ClockError::UnsupportedSoc)
+}
+
+#[cfg(all(not(feature = "development"), feature = "orangepi"))]
+fn soc() -> Result<Soc, ClockError> {
+    Err(ClockError::UnsupportedSoc)
+}
+
+#[cfg(feature = "development")]
+fn soc() -> Result<Soc, ClockError> {
+    Ok(Soc::Bcm2835)
+}
+
+///Stops the clock generator and waits for it to finish its current cycle.
+#[cfg(not(feature = "development"))]
+pub fn stop(channel: ClockChannel) -> Result<(), ClockError> {
+    let registers = registers::clock_manager()?;
+
+    unsafe {
+        let control = registers.add(Register::Control.index(channel));
+        let value = ::std::ptr::read_volatile(control);
+        ::std::ptr::write_volatile(control, PASSWORD | (value & !ENABLE & 0x00FF_FFFF));
+
+        for _ in 0..1000 {
+            if ::std::ptr::read_volatile(control) & BUSY == 0 {
+                break;
+            }
+
+            ::std::thread::sleep(::std::time::Duration::from_micros(10));
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(not(feature = "development"))]
+fn write(channel: ClockChannel, register: Register, value: u32) -> Result<(), ClockError> {
+    let registers = registers::clock_manager()?;
+
+    unsafe {
+        ::std::ptr::write_volatile(registers.add(register.index(channel)), value);
+    }
+
+    Ok(())
+}
+
+#[cfg(feature = "development")]
+pub fn stop(channel: ClockChannel) -> Result<(), ClockError> {
+    println!("[wiringpi] `{:?}` stopped", channel);
+    Ok(())
+}
+
+#[cfg(feature = "development")]
+fn write(channel: ClockChannel, register: Register, value: u32) -> Result<(), ClockError> {
+    println!("[wiringpi] `{:?}` {:?} register set to {:#010x}", channel, register, value);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Divisor;
+    use pin::{ClockError, Mash};
+
+    const OSCILLATOR: f64 = 19_200_000.0;
+
+    #[test]
+    fn integer_divisor() {
+        let divisor = Divisor::for_frequency(OSCILLATOR, Mash::Integer, 100_000).unwrap();
+        assert_eq!(divisor, Divisor { integer: 192, fraction: 0 });
+        assert_eq!(divisor.frequency(OSCILLATOR), 100_000.0);
+
+        let divisor = Divisor::for_frequency(OSCILLATOR, Mash::Integer, 7_000_000).unwrap();
+        assert_eq!(divisor, Divisor { integer: 3, fraction: 0 });
+        assert_eq!(divisor.frequency(OSCILLATOR), 6_400_000.0);
+    }
+
+    #[test]
+    fn fractional_divisor() {
+        let divisor = Divisor::for_frequency(OSCILLATOR, Mash::Stage1, 7_000_000).unwrap();
+        assert_eq!(divisor, Divisor { integer: 2, fraction: 3043 });
+        assert!((divisor.frequency(OSCILLATOR) - 7_000_000.0).abs() < 500.0);
+
+        let divisor = Divisor::for_frequency(OSCILLATOR, Mash::Stage1, 8_000_000).unwrap();
+        assert_eq!(divisor, Divisor { integer: 2, fraction: 1638 });
+    }
+
+    #[test]
+    fn minimum_divisor() {
+        assert!(Divisor::for_frequency(OSCILLATOR, Mash::Integer, 19_200_000).is_ok());
+        assert!(Divisor::for_frequency(OSCILLATOR, Mash::Stage1, 9_600_000).is_ok());
+        assert_eq!(Divisor::for_frequency(OSCILLATOR, Mash::Stage1, 12_000_000), Err(ClockError::FrequencyOutOfRange));
+        assert!(Divisor::for_frequency(OSCILLATOR, Mash::Stage2, 6_400_000).is_ok());
+        assert_eq!(Divisor::for_frequency(OSCILLATOR, Mash::Stage2, 8_000_000), Err(ClockError::FrequencyOutOfRange));
+        assert!(Divisor::for_frequency(OSCILLATOR, Mash::Stage3, 3_840_000).is_ok());
+        assert_eq!(Divisor::for_frequency(OSCILLATOR, Mash::Stage3, 4_800_000), Err(ClockError::FrequencyOutOfRange));
+    }
+
+    #[test]
+    fn maximum_divisor() {
+        let divisor = Divisor::for_frequency(OSCILLATOR, Mash::Integer, 4689).unwrap();
+        assert_eq!(divisor.integer, 4095);
+
+        assert_eq!(Divisor::for_frequency(OSCILLATOR, Mash::Integer, 4688), Err(ClockError::FrequencyOutOfRange));
+        assert_eq!(Divisor::for_frequency(OSCILLATOR, Mash::Integer, 0), Err(ClockError::FrequencyOutOfRange));
+    }
+}
@@ -0,0 +1,236 @@
+//! Edge counting and frequency measurement.
+
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+use interrupt::Registration;
+use libc;
+use pin::{Edge, InputPin, Pin};
+use timing::{self, ExtendedMicros};
+
+#[derive(Default)]
+struct Counts {
+    clock: ExtendedMicros,
+    total: u64,
+    count: u64,
+    last_edge: Option<u64>,
+    window_start: Option<u64>,
+    window_edges: u64,
+    estimate: Option<f64>,
+}
+
+impl Counts {
+    ///Counts an edge at the raw `micros` timestamp `now`, and completes the
+    ///gate window once it's at least `gate` long.
+    fn edge(&mut self, now: u32, gate: u64) {
+        let now = self.clock.extend(now);
+
+        self.total += 1;
+        self.count += 1;
+        self.last_edge = Some(now);
+
+        match self.window_start {
+            Some(start) => {
+                self.window_edges += 1;
+                let elapsed = now - start;
+
+                if elapsed >= gate {
+                    self.estimate = Some(self.window_edges as f64 * 1_000_000.0 / elapsed as f64);
+                    self.window_start = Some(now);
+                    self.window_edges = 0;
+                }
+            },
+            None => {
+                self.window_start = Some(now);
+                self.window_edges = 0;
+            },
+        }
+    }
+
+    ///The frequency estimate at the raw `micros` timestamp `now`, which
+    ///drops to 0 once there hasn't been an edge for two gate times.
+    fn frequency(&mut self, now: u32, gate: u64) -> Option<f64> {
+        let now = self.clock.extend(now);
+
+        match self.last_edge {
+            Some(last_edge) if now - last_edge > 2 * gate => {
+                self.window_start = None;
+                self.window_edges = 0;
+                self.estimate = Some(0.0);
+            },
+            _ => {},
+        }
+
+        self.estimate
+    }
+}
+
+///Counts edges on an `InputPin` and estimates their frequency.
+///
+///The edges are counted by an interrupt handler. The frequency is estimated
+///over gate windows: the first edge opens a window and the first edge after
+///the gate time closes it. The frequency is the number of edges in the window
+///divided by the time between its first and last edge, which keeps the
+///estimate accurate even at low frequencies.
+///
+///Timestamps are based on the `micros()` clock, which wraps after 71 minutes.
+///The wraparound is handled as long as there is at least one edge or call to
+///the counter within that time.
+///
+///```no_run
+///use std::time::Duration;
+///use wiringpi::counter::FrequencyCounter;
+///use wiringpi::pin::Edge;
+///
+///let pi = wiringpi::setup_gpio();
+///let flow = FrequencyCounter::new(pi.input_pin(27), Edge::Rising, Duration::from_secs(1)).unwrap();
+///
+///loop {
+///    std::thread::sleep(Duration::from_secs(1));
+///    let pulses = flow.reset();
+///    println!("{} pulses, {:?} Hz", pulses, flow.frequency());
+///}
+///```
+pub struct FrequencyCounter<P> {
+    pin: InputPin<P>,
+    gate: u64,
+    counts: Arc<Mutex<Counts>>,
+    _registration: Registration,
+}
+
+impl<P: Pin> FrequencyCounter<P> {
+    ///Starts counting `edge`s on `pin`, estimating the frequency over
+    ///windows of at least `gate`.
+    ///
+    ///Returns `None` if an interrupt handler could not be registered for the
+    ///pin.
+    pub fn new(pin: InputPin<P>, edge: Edge, gate: Duration) -> Option<FrequencyCounter<P>> {
+        let gate = timing::duration_to_micros(gate).max(1) as u64;
+        let counts = Arc::new(Mutex::new(Counts::default()));
+        let isr_counts = counts.clone();
+
+        let registration = Registration::new(pin.number(), edge, move || {
+            isr_counts.lock().unwrap_or_else(|e| e.into_inner()).edge(timing::micros(), gate);
+        });
+
+        registration.map(|registration| FrequencyCounter {
+            pin,
+            gate,
+            counts,
+            _registration: registration,
+        })
+    }
+
+    #[inline]
+    pub fn number(&self) -> libc::c_int {
+        self.pin.number()
+    }
+
+    ///The number of edges since the counter was created.
+    pub fn total(&self) -> u64 {
+        self.with_counts(|counts| counts.total)
+    }
+
+    ///The number of edges since the counter was created or last reset.
+    pub fn count(&self) -> u64 {
+        self.with_counts(|counts| counts.count)
+    }
+
+    ///Resets the resettable count to 0 and returns its previous value.
+    pub fn reset(&self) -> u64 {
+        self.with_counts(|counts| {
+            let count = counts.count;
+            counts.count = 0;
+            count
+        })
+    }
+
+    ///The frequency in Hz, estimated over the latest completed gate window.
+    ///
+    ///It's `None` before the first window has been completed, and 0 if no
+    ///edge has been seen for two gate times.
+    pub fn frequency(&self) -> Option<f64> {
+        let gate = self.gate;
+        self.with_counts(|counts| counts.frequency(timing::micros(), gate))
+    }
+
+    ///Counts the edges during `gate` and returns their average frequency in
+    ///Hz. This blocks the current thread for the duration of the gate.
+    pub fn measure(&self, gate: Duration) -> f64 {
+        let (start_count, start) = self.with_counts(|counts| (counts.total, counts.clock.now()));
+        thread::sleep(gate);
+        let (end_count, end) = self.with_counts(|counts| (counts.total, counts.clock.now()));
+
+        if end > start {
+            (end_count - start_count) as f64 * 1_000_000.0 / (end - start) as f64
+        } else {
+            0.0
+        }
+    }
+
+    ///Stops counting and returns the underlying pin.
+    pub fn into_inner(self) -> InputPin<P> {
+        let FrequencyCounter { pin, .. } = self;
+        pin
+    }
+
+    fn with_counts<T, F: FnOnce(&mut Counts) -> T>(&self, f: F) -> T {
+        f(&mut self.counts.lock().unwrap_or_else(|e| e.into_inner()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Counts;
+
+    const GATE: u64 = 1_000_000;
+
+    fn edges(counts: &mut Counts, start: u32, interval: u32, edges: u32) -> u32 {
+        let mut now = start;
+
+        for _ in 0..edges {
+            counts.edge(now, GATE);
+            now = now.wrapping_add(interval);
+        }
+
+        now.wrapping_sub(interval)
+    }
+
+    #[test]
+    fn frequency_over_gate_window() {
+        let mut counts = Counts::default();
+        let last = edges(&mut counts, 0, 10_000, 101);
+
+        assert_eq!(counts.total, 101);
+        assert_eq!(counts.frequency(last, GATE), Some(100.0));
+    }
+
+    #[test]
+    fn no_estimate_before_first_window() {
+        let mut counts = Counts::default();
+        let last = edges(&mut counts, 0, 10_000, 50);
+
+        assert_eq!(counts.frequency(last, GATE), None);
+    }
+
+    #[test]
+    fn frequency_across_wraparound() {
+        let mut counts = Counts::default();
+        let last = edges(&mut counts, u32::MAX - 500_000, 4_000, 251);
+
+        assert!(last < u32::MAX - 500_000);
+        assert_eq!(counts.frequency(last, GATE), Some(250.0));
+    }
+
+    #[test]
+    fn idle_counter() {
+        let mut counts = Counts::default();
+        let last = edges(&mut counts, 1_000, 10_000, 101);
+
+        assert_eq!(counts.frequency(last + 2 * GATE as u32, GATE), Some(100.0));
+        assert_eq!(counts.frequency(last + 2 * GATE as u32 + 1, GATE), Some(0.0));
+        assert_eq!(counts.window_start, None);
+        assert_eq!(counts.count, 101);
+    }
+}
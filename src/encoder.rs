@@ -0,0 +1,336 @@
+//! Quadrature encoder decoding.
+
+use std::sync::{Arc, Mutex};
+
+use bindings;
+use interrupt::Registration;
+use libc;
+use pin::{Edge, InputPin, Pin};
+use timing::ExtendedMicros;
+
+///How many counts the encoder produces for each cycle of the A and B
+///signals.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Decoding {
+    ///Count once per cycle, on the rising edge of A when moving forward and
+    ///the falling edge of A when moving backward.
+    X1,
+    ///Count on both edges of A.
+    X2,
+    ///Count on both edges of both A and B.
+    X4,
+}
+
+///The direction of the latest movement.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Direction {
+    ///A leads B. The position increases.
+    Forward,
+    ///B leads A. The position decreases.
+    Backward,
+}
+
+///The position change for each transition, indexed by `[old][new]`, where
+///the states are `A << 1 | B`. `None` marks illegal transitions, where both
+///signals changed at once.
+const TRANSITIONS: [[Option<i8>; 4]; 4] = [
+    [Some(0), Some(-1), Some(1), None],
+    [Some(1), Some(0), None, Some(-1)],
+    [Some(-1), None, Some(0), Some(1)],
+    [None, Some(1), Some(-1), Some(0)],
+];
+
+struct State {
+    decoding: Decoding,
+    clock: ExtendedMicros,
+    ab: usize,
+    position: i64,
+    direction: Option<Direction>,
+    last_count: Option<u64>,
+    interval: Option<u64>,
+    illegal: u64,
+    index_count: u64,
+    reset_on_index: bool,
+}
+
+impl State {
+    fn update(&mut self, ab: usize) {
+        let old = self.ab;
+        self.ab = ab;
+
+        let delta = match TRANSITIONS[old][ab] {
+            Some(0) => return,
+            Some(delta) => delta as i64,
+            None => {
+                self.illegal += 1;
+                return;
+            },
+        };
+
+        let a_changed = (old ^ ab) & 0b10 != 0;
+        let counts = match self.decoding {
+            Decoding::X4 => true,
+            Decoding::X2 => a_changed,
+            Decoding::X1 => (old == 0b00 && ab == 0b10) || (old == 0b10 && ab == 0b00),
+        };
+
+        self.direction = Some(if delta > 0 { Direction::Forward } else { Direction::Backward });
+
+        if counts {
+            let now = self.clock.now();
+            self.interval = self.last_count.map(|last| now - last);
+            self.last_count = Some(now);
+            self.position += delta;
+        }
+    }
+}
+
+///Decodes the A and B signals of a quadrature encoder into a signed
+///position.
+///
+///Both signals are monitored with interrupt handlers, which track the
+///position, the direction of the latest movement and the velocity. Both
+///signals changing at the same time can't be decoded, so those transitions
+///are counted as illegal and ignored. A high number of illegal transitions
+///is a sign of bouncing contacts or of an encoder that is too fast to be
+///followed.
+///
+///```no_run
+///use wiringpi::encoder::{Decoding, QuadratureEncoder};
+///
+///let pi = wiringpi::setup_gpio();
+///let knob = QuadratureEncoder::new(pi.input_pin(5), pi.input_pin(6), Decoding::X4).unwrap();
+///
+///println!("position: {}, velocity: {} counts/s", knob.position(), knob.velocity());
+///```
+pub struct QuadratureEncoder<P> {
+    a: InputPin<P>,
+    b: InputPin<P>,
+    index: Option<InputPin<P>>,
+    state: Arc<Mutex<State>>,
+    _registrations: Vec<Registration>,
+}
+
+impl<P: Pin> QuadratureEncoder<P> {
+    ///Starts decoding the signals on the `a` and `b` pins.
+    ///
+    ///Returns `None` if interrupt handlers could not be registered for the
+    ///pins.
+    pub fn new(a: InputPin<P>, b: InputPin<P>, decoding: Decoding) -> Option<QuadratureEncoder<P>> {
+        QuadratureEncoder::start(a, b, None, decoding)
+    }
+
+    ///Starts decoding the signals on the `a` and `b` pins, and counts the
+    ///rising edges of the `index` pin.
+    ///
+    ///Returns `None` if interrupt handlers could not be registered for the
+    ///pins.
+    pub fn with_index(a: InputPin<P>, b: InputPin<P>, index: InputPin<P>, decoding: Decoding) -> Option<QuadratureEncoder<P>> {
+        QuadratureEncoder::start(a, b, Some(index), decoding)
+    }
+
+    fn start(a: InputPin<P>, b: InputPin<P>, index: Option<InputPin<P>>, decoding: Decoding) -> Option<QuadratureEncoder<P>> {
+        let (a_number, b_number) = (a.number(), b.number());
+        let read = move || read_state(a_number, b_number);
+
+        let state = Arc::new(Mutex::new(State {
+            decoding,
+            clock: ExtendedMicros::default(),
+            ab: read(),
+            position: 0,
+            direction: None,
+            last_count: None,
+            interval: None,
+            illegal: 0,
+            index_count: 0,
+            reset_on_index: false,
+        }));
+
+        let mut registrations = Vec::new();
+
+        for &number in &[a_number, b_number] {
+            let isr_state = state.clone();
+            registrations.push(Registration::new(number, Edge::Both, move || {
+                isr_state.lock().unwrap_or_else(|e| e.into_inner()).update(read());
+            })?);
+        }
+
+        if let Some(ref index) = index {
+            let isr_state = state.clone();
+            registrations.push(Registration::new(index.number(), Edge::Rising, move || {
+                let mut state = isr_state.lock().unwrap_or_else(|e| e.into_inner());
+                state.index_count += 1;
+
+                if state.reset_on_index {
+                    state.position = 0;
+                }
+            })?);
+        }
+
+        Some(QuadratureEncoder {
+            a,
+            b,
+            index,
+            state,
+            _registrations: registrations,
+        })
+    }
+
+    ///The numbers of the A and B pins.
+    #[inline]
+    pub fn numbers(&self) -> (libc::c_int, libc::c_int) {
+        (self.a.number(), self.b.number())
+    }
+
+    ///The current position in counts.
+    pub fn position(&self) -> i64 {
+        self.with_state(|state| state.position)
+    }
+
+    ///Changes the current position, for example after homing.
+    pub fn set_position(&self, position: i64) {
+        self.with_state(|state| state.position = position)
+    }
+
+    ///The direction of the latest movement, or `None` if the encoder hasn't
+    ///moved yet.
+    pub fn direction(&self) -> Option<Direction> {
+        self.with_state(|state| state.direction)
+    }
+
+    ///The velocity in counts per second, based on the time between the two
+    ///latest counts. It's negative when moving backward and decays towards 0
+    ///when the encoder stops.
+    pub fn velocity(&self) -> f64 {
+        self.with_state(|state| {
+            let (last_count, interval) = match (state.last_count, state.interval) {
+                (Some(last_count), Some(interval)) => (last_count, interval),
+                _ => return 0.0,
+            };
+
+            let interval = interval.max(state.clock.now() - last_count);
+            let speed = if interval > 0 {
+                1_000_000.0 / interval as f64
+            } else {
+                0.0
+            };
+
+            match state.direction {
+                Some(Direction::Backward) => -speed,
+                _ => speed,
+            }
+        })
+    }
+
+    ///The number of transitions where both signals changed at once.
+    pub fn illegal_transitions(&self) -> u64 {
+        self.with_state(|state| state.illegal)
+    }
+
+    ///The number of rising edges on the index pin.
+    pub fn index_count(&self) -> u64 {
+        self.with_state(|state| state.index_count)
+    }
+
+    ///Decides if the position should be reset to 0 on every rising edge of
+    ///the index pin. This is off by default.
+    pub fn set_reset_on_index(&self, reset: bool) {
+        self.with_state(|state| state.reset_on_index = reset)
+    }
+
+    ///Stops decoding and returns the A, B and index pins.
+    pub fn into_inner(self) -> (InputPin<P>, InputPin<P>, Option<InputPin<P>>) {
+        let QuadratureEncoder { a, b, index, .. } = self;
+        (a, b, index)
+    }
+
+    fn with_state<T, F: FnOnce(&mut State) -> T>(&self, f: F) -> T {
+        f(&mut self.state.lock().unwrap_or_else(|e| e.into_inner()))
+    }
+}
+
+fn read_state(a: libc::c_int, b: libc::c_int) -> usize {
+    let (a, b) = unsafe {
+        (bindings::digitalRead(a), bindings::digitalRead(b))
+    };
+
+    ((a != 0) as usize) << 1 | (b != 0) as usize
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Decoding, Direction, State, TRANSITIONS};
+    use timing::ExtendedMicros;
+
+    const FORWARD: [usize; 5] = [0b00, 0b10, 0b11, 0b01, 0b00];
+
+    fn state(decoding: Decoding) -> State {
+        State {
+            decoding,
+            clock: ExtendedMicros::default(),
+            ab: 0b00,
+            position: 0,
+            direction: None,
+            last_count: None,
+            interval: None,
+            illegal: 0,
+            index_count: 0,
+            reset_on_index: false,
+        }
+    }
+
+    fn run(decoding: Decoding, sequence: &[usize]) -> State {
+        let mut state = state(decoding);
+
+        for &ab in sequence {
+            state.update(ab);
+        }
+
+        state
+    }
+
+    #[test]
+    fn transitions_are_symmetric() {
+        for (old, row) in TRANSITIONS.iter().enumerate() {
+            for (new, delta) in row.iter().enumerate() {
+                assert_eq!(delta.map(|delta| -delta), TRANSITIONS[new][old]);
+            }
+        }
+    }
+
+    #[test]
+    fn forward_cycle() {
+        let backward: Vec<usize> = FORWARD.iter().rev().cloned().collect();
+
+        for &(decoding, counts) in &[(Decoding::X1, 1), (Decoding::X2, 2), (Decoding::X4, 4)] {
+            let state = run(decoding, &FORWARD);
+            assert_eq!(state.position, counts);
+            assert_eq!(state.direction, Some(Direction::Forward));
+
+            let state = run(decoding, &backward);
+            assert_eq!(state.position, -counts);
+            assert_eq!(state.direction, Some(Direction::Backward));
+        }
+    }
+
+    #[test]
+    fn x1_counts_on_a() {
+        assert_eq!(run(Decoding::X1, &[0b10]).position, 1);
+        assert_eq!(run(Decoding::X1, &[0b01, 0b00]).position, 0);
+        assert_eq!(run(Decoding::X1, &[0b10, 0b00]).position, 0);
+    }
+
+    #[test]
+    fn illegal_transitions() {
+        let state = run(Decoding::X4, &[0b11, 0b00, 0b10]);
+        assert_eq!(state.illegal, 2);
+        assert_eq!(state.position, 1);
+    }
+
+    #[test]
+    fn repeated_state() {
+        let state = run(Decoding::X4, &[0b00, 0b00]);
+        assert_eq!(state.position, 0);
+        assert_eq!(state.direction, None);
+    }
+}
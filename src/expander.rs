@@ -0,0 +1,777 @@
+//! I2C port expanders, which add pins through extension nodes.
+
+use std::error::Error;
+use std::fmt;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::sync::mpsc::{self, Receiver};
+
+use extension::{self, ExtensionError, Node, PinMode, Registration};
+use i2c::{I2cDevice, I2cError};
+use interrupt;
+use pin::{Edge, Gpio, InputPin, Pin, Pull, RequiresRoot, Value};
+
+///The ways setting up and using a port expander can fail.
+#[derive(Debug)]
+pub enum ExpanderError {
+    ///The pin number is not less than the number of pins on the chip.
+    InvalidPin,
+    ///The chip couldn't be registered as an extension node.
+    Extension(ExtensionError),
+    ///The chip didn't respond.
+    I2c(I2cError),
+    ///An interrupt handler couldn't be registered for the interrupt pin.
+    InterruptUnavailable,
+}
+
+impl fmt::Display for ExpanderError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            ExpanderError::InvalidPin => f.write_str("the port expander doesn't have that pin"),
+            ExpanderError::Extension(ref error) => write!(f, "port expander error: {}", error),
+            ExpanderError::I2c(ref error) => write!(f, "port expander error: {}", error),
+            ExpanderError::InterruptUnavailable => f.write_str("the interrupt pin of the port expander couldn't be set up"),
+        }
+    }
+}
+
+impl Error for ExpanderError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match *self {
+            ExpanderError::InvalidPin | ExpanderError::InterruptUnavailable => None,
+            ExpanderError::Extension(ref error) => Some(error),
+            ExpanderError::I2c(ref error) => Some(error),
+        }
+    }
+}
+
+impl From<ExtensionError> for ExpanderError {
+    fn from(error: ExtensionError) -> ExpanderError {
+        ExpanderError::Extension(error)
+    }
+}
+
+impl From<I2cError> for ExpanderError {
+    fn from(error: I2cError) -> ExpanderError {
+        ExpanderError::I2c(error)
+    }
+}
+
+///The chips in the MCP230xx family.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum McpChip {
+    ///8 pins, GP0 to GP7.
+    Mcp23008,
+    ///16 pins, where GPA0 to GPA7 are pins 0 to 7 and GPB0 to GPB7 are pins
+    ///8 to 15.
+    Mcp23017,
+}
+
+impl McpChip {
+    ///The number of pins on the chip.
+    pub fn pins(self) -> u16 {
+        match self {
+            McpChip::Mcp23008 => 8,
+            McpChip::Mcp23017 => 16,
+        }
+    }
+}
+
+///When a pin triggers the interrupt output.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InterruptMode {
+    Disabled,
+    ///Whenever the level of the pin changes.
+    Change,
+    ///While the level of the pin is different from the given value.
+    Compare(Value),
+}
+
+///How the interrupt output is driven.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InterruptOutput {
+    ///Driven low when active.
+    ActiveLow,
+    ///Driven high when active.
+    ActiveHigh,
+    ///Pulled low when active and left floating otherwise, so the outputs of
+    ///several chips can share a pin with a pull-up resistor.
+    OpenDrain,
+}
+
+///The registers of the MCP230xx chips, in the order of the MCP23008. The
+///MCP23017 has one of each per port, interleaved.
+#[derive(Debug, Clone, Copy)]
+enum McpRegister {
+    Iodir = 0x00,
+    Gpinten = 0x02,
+    Defval = 0x03,
+    Intcon = 0x04,
+    Iocon = 0x05,
+    Gppu = 0x06,
+    Intf = 0x07,
+    Intcap = 0x08,
+    Gpio = 0x09,
+    Olat = 0x0A,
+}
+
+const IOCON_MIRROR: u16 = 0x40;
+const IOCON_ODR: u16 = 0x04;
+const IOCON_INTPOL: u16 = 0x02;
+
+///The registers that are cached to avoid reading them back before each
+///change.
+struct McpState {
+    device: I2cDevice,
+    iodir: u16,
+    gppu: u16,
+    olat: u16,
+    gpinten: u16,
+    intcon: u16,
+    defval: u16,
+    iocon: u16,
+}
+
+struct McpNode {
+    chip: McpChip,
+    state: Mutex<McpState>,
+}
+
+impl McpNode {
+    fn lock(&self) -> MutexGuard<'_, McpState> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn read(&self, state: &McpState, register: McpRegister) -> Result<u16, I2cError> {
+        match self.chip {
+            McpChip::Mcp23008 => state.device.read_reg8(register as u8).map(|value| value as u16),
+            McpChip::Mcp23017 => state.device.read_reg16(register as u8 * 2),
+        }
+    }
+
+    fn write(&self, state: &McpState, register: McpRegister, value: u16) -> Result<(), I2cError> {
+        match self.chip {
+            McpChip::Mcp23008 => state.device.write_reg8(register as u8, value as u8),
+            McpChip::Mcp23017 => state.device.write_reg16(register as u8 * 2, value),
+        }
+    }
+
+    ///Sets or clears `pin` in a cached register and writes it to the chip.
+    fn update<F>(&self, register: McpRegister, pin: u16, set: bool, cached: F) -> Result<(), I2cError>
+        where F: Fn(&mut McpState) -> &mut u16
+    {
+        let mut state = self.lock();
+        let mut value = *cached(&mut state);
+
+        if set {
+            value |= 1 << pin;
+        } else {
+            value &= !(1 << pin);
+        }
+
+        self.write(&state, register, value)?;
+        *cached(&mut state) = value;
+
+        Ok(())
+    }
+}
+
+impl Node for McpNode {
+    fn pin_mode(&self, pin: u16, mode: PinMode) {
+        let input = match mode {
+            PinMode::Input => true,
+            PinMode::Output => false,
+            PinMode::PwmOutput => return,
+        };
+
+        let _ = self.update(McpRegister::Iodir, pin, input, |state| &mut state.iodir);
+    }
+
+    ///The chip only has pull-up resistors, so `Down` turns them off.
+    fn pull_up_dn_control(&self, pin: u16, pull: Pull) {
+        let up = match pull {
+            Pull::Up => true,
+            Pull::Down | Pull::Off => false,
+        };
+
+        let _ = self.update(McpRegister::Gppu, pin, up, |state| &mut state.gppu);
+    }
+
+    ///Reads `Low` if the chip doesn't respond.
+    fn digital_read(&self, pin: u16) -> Value {
+        let state = self.lock();
+
+        match self.read(&state, McpRegister::Gpio) {
+            Ok(value) if value & (1 << pin) != 0 => Value::High,
+            _ => Value::Low,
+        }
+    }
+
+    fn digital_write(&self, pin: u16, value: Value) {
+        let _ = self.update(McpRegister::Olat, pin, value == Value::High, |state| &mut state.olat);
+    }
+}
+
+///An MCP23008 or MCP23017 I2C port expander.
+///
+///The pins are registered as an extension node, so they can be used as
+///`InputPin`s and `OutputPin`s with the numbers from `pin_base`. Pull-ups are
+///turned on and off with `InputPin::pull_up_dn_control`. The chip is set up
+///with sequential register access and the MCP23017 with its registers in
+///interleaved order, which are the power-on defaults.
+///
+///In development mode, the chip can be simulated with
+///`i2c::simulation::RegisterSlave`, where the registers of the MCP23017 are
+///interleaved.
+///
+///```
+///use std::sync::{Arc, Mutex};
+///use wiringpi::expander::Mcp230xx;
+///use wiringpi::i2c::I2cDevice;
+///use wiringpi::i2c::simulation::{self, RegisterSlave};
+///use wiringpi::pin::Value;
+///
+///let chip = Arc::new(Mutex::new(RegisterSlave::new()));
+///chip.lock().unwrap().set(0x13, 0b0000_0100);
+///simulation::attach(1, 0x20, chip.clone());
+///
+///let pi = wiringpi::setup();
+///let _expander = Mcp230xx::mcp23017(I2cDevice::new(0x20).unwrap(), 100).unwrap();
+///
+///let button = pi.input_pin(110);
+///assert_eq!(button.digital_read(), Value::High);
+///
+///let led = pi.output_pin(100);
+///led.digital_write(Value::High);
+///assert_eq!(chip.lock().unwrap().get(0x14), 0b0000_0001);
+///```
+pub struct Mcp230xx {
+    node: Arc<McpNode>,
+    registration: Registration,
+}
+
+impl Mcp230xx {
+    ///Sets up `chip` at `device` and registers its pins from `pin_base`.
+    ///
+    ///The current pin directions, pull-ups and outputs are kept, so it can
+    ///take over a chip without glitches on the outputs.
+    pub fn new(chip: McpChip, device: I2cDevice, pin_base: u16) -> Result<Mcp230xx, ExpanderError> {
+        let node = McpNode {
+            chip,
+            state: Mutex::new(McpState {
+                device,
+                iodir: 0,
+                gppu: 0,
+                olat: 0,
+                gpinten: 0,
+                intcon: 0,
+                defval: 0,
+                iocon: 0,
+            }),
+        };
+
+        {
+            let mut state = node.lock();
+            let iocon = node.read(&state, McpRegister::Iocon)? & (IOCON_ODR | IOCON_INTPOL | IOCON_MIRROR);
+            node.write(&state, McpRegister::Iocon, iocon | iocon << 8)?;
+
+            state.iocon = iocon;
+            state.iodir = node.read(&state, McpRegister::Iodir)?;
+            state.gppu = node.read(&state, McpRegister::Gppu)?;
+            state.olat = node.read(&state, McpRegister::Olat)?;
+            state.gpinten = node.read(&state, McpRegister::Gpinten)?;
+            state.intcon = node.read(&state, McpRegister::Intcon)?;
+            state.defval = node.read(&state, McpRegister::Defval)?;
+        }
+
+        let node = Arc::new(node);
+        let registration = extension::register(pin_base, chip.pins(), node.clone())?;
+
+        Ok(Mcp230xx {
+            node,
+            registration,
+        })
+    }
+
+    pub fn mcp23008(device: I2cDevice, pin_base: u16) -> Result<Mcp230xx, ExpanderError> {
+        Mcp230xx::new(McpChip::Mcp23008, device, pin_base)
+    }
+
+    pub fn mcp23017(device: I2cDevice, pin_base: u16) -> Result<Mcp230xx, ExpanderError> {
+        Mcp230xx::new(McpChip::Mcp23017, device, pin_base)
+    }
+
+    pub fn chip(&self) -> McpChip {
+        self.node.chip
+    }
+
+    ///The number of the first pin.
+    pub fn pin_base(&self) -> u16 {
+        self.registration.pin_base()
+    }
+
+    ///Reads the levels of all pins, with pin 0 in the least significant bit.
+    pub fn read_all(&self) -> Result<u16, ExpanderError> {
+        let state = self.node.lock();
+        Ok(self.node.read(&state, McpRegister::Gpio)?)
+    }
+
+    ///Sets the levels of all output pins, with pin 0 in the least
+    ///significant bit.
+    pub fn write_all(&self, value: u16) -> Result<(), ExpanderError> {
+        let mut state = self.node.lock();
+        self.node.write(&state, McpRegister::Olat, value)?;
+        state.olat = value;
+        Ok(())
+    }
+
+    ///Sets when `pin` triggers the interrupt output.
+    pub fn set_interrupt(&self, pin: u16, mode: InterruptMode) -> Result<(), ExpanderError> {
+        self.check_pin(pin)?;
+
+        let node = &self.node;
+
+        match mode {
+            InterruptMode::Disabled => {},
+            InterruptMode::Change => node.update(McpRegister::Intcon, pin, false, |state| &mut state.intcon)?,
+            InterruptMode::Compare(value) => {
+                node.update(McpRegister::Defval, pin, value == Value::High, |state| &mut state.defval)?;
+                node.update(McpRegister::Intcon, pin, true, |state| &mut state.intcon)?;
+            },
+        }
+
+        node.update(McpRegister::Gpinten, pin, mode != InterruptMode::Disabled, |state| &mut state.gpinten)?;
+
+        Ok(())
+    }
+
+    ///Sets how the interrupt output is driven. The default is `ActiveLow`.
+    pub fn set_interrupt_output(&self, output: InterruptOutput) -> Result<(), ExpanderError> {
+        let bits = match output {
+            InterruptOutput::ActiveLow => 0,
+            InterruptOutput::ActiveHigh => IOCON_INTPOL,
+            InterruptOutput::OpenDrain => IOCON_ODR,
+        };
+
+        self.set_iocon(IOCON_ODR | IOCON_INTPOL, bits)
+    }
+
+    ///Connects the interrupt outputs of the two ports of an MCP23017, so
+    ///either of them is triggered by all pins. This does nothing on an
+    ///MCP23008, which has one interrupt output.
+    pub fn set_interrupt_mirror(&self, mirror: bool) -> Result<(), ExpanderError> {
+        if self.node.chip == McpChip::Mcp23008 {
+            return Ok(());
+        }
+
+        self.set_iocon(IOCON_MIRROR, if mirror { IOCON_MIRROR } else { 0 })
+    }
+
+    ///The pins that have triggered the interrupt, with pin 0 in the least
+    ///significant bit.
+    pub fn interrupt_flags(&self) -> Result<u16, ExpanderError> {
+        let state = self.node.lock();
+        Ok(self.node.read(&state, McpRegister::Intf)?)
+    }
+
+    ///The levels of the pins when the interrupt was triggered. Reading them
+    ///clears the interrupt.
+    pub fn interrupt_capture(&self) -> Result<u16, ExpanderError> {
+        let state = self.node.lock();
+        Ok(self.node.read(&state, McpRegister::Intcap)?)
+    }
+
+    fn check_pin(&self, pin: u16) -> Result<(), ExpanderError> {
+        if pin < self.node.chip.pins() {
+            Ok(())
+        } else {
+            Err(ExpanderError::InvalidPin)
+        }
+    }
+
+    ///IOCON is the same register for both ports of the MCP23017, so it gets
+    ///the same value in both bytes.
+    fn set_iocon(&self, mask: u16, bits: u16) -> Result<(), ExpanderError> {
+        let mut state = self.node.lock();
+        let iocon = (state.iocon & !mask) | bits;
+        self.node.write(&state, McpRegister::Iocon, iocon | iocon << 8)?;
+        state.iocon = iocon;
+        Ok(())
+    }
+}
+
+///The first address of the PCF8574. The last three bits are set with the
+///A0 to A2 pins.
+pub const PCF8574_ADDRESS: u16 = 0x20;
+
+///The first address of the PCF8574A. The last three bits are set with the
+///A0 to A2 pins.
+pub const PCF8574A_ADDRESS: u16 = 0x38;
+
+///A pin of a `Pcf8574` changing level.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PinChange {
+    ///The pin number, counted from the pin base.
+    pub pin: u16,
+    ///The new level of the pin.
+    pub value: Value,
+}
+
+struct PcfState {
+    device: I2cDevice,
+    ///The last byte that was written, since the outputs can't be read back.
+    latch: u8,
+    ///The levels from the latest read, which are kept up to date by the
+    ///interrupt handler if there is one.
+    levels: Option<u8>,
+}
+
+///The INT line of a `Pcf8574`, with its interrupt handler.
+struct PcfInterrupt<P> {
+    registration: interrupt::Registration,
+    events: Receiver<PinChange>,
+    pin: InputPin<P>,
+}
+
+struct PcfNode {
+    state: Mutex<PcfState>,
+}
+
+impl PcfNode {
+    fn lock(&self) -> MutexGuard<'_, PcfState> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    ///Writes a new latch byte. The levels are read again afterwards, when
+    ///they are cached, since the written pins change them.
+    fn write(&self, state: &mut PcfState, latch: u8) -> Result<(), I2cError> {
+        state.device.write(latch)?;
+        state.latch = latch;
+
+        if state.levels.is_some() {
+            state.levels = Some(state.device.read()?);
+        }
+
+        Ok(())
+    }
+
+    fn read(&self, state: &PcfState) -> Result<u8, I2cError> {
+        match state.levels {
+            Some(levels) => Ok(levels),
+            None => state.device.read(),
+        }
+    }
+
+    fn set_pin(&self, pin: u16, high: bool) {
+        let mut state = self.lock();
+        let latch = if high { state.latch | 1 << pin } else { state.latch & !(1 << pin) };
+        let _ = self.write(&mut state, latch);
+    }
+
+    ///Reads the inputs after the INT line was pulled low, which also
+    ///releases it, and returns the inputs that changed since the previous
+    ///read.
+    fn read_changes(&self) -> Vec<PinChange> {
+        let mut state = self.lock();
+        let previous = state.levels.unwrap_or(0);
+
+        let levels = match state.device.read() {
+            Ok(levels) => levels,
+            Err(_) => return Vec::new(),
+        };
+
+        state.levels = Some(levels);
+        let changed = (previous ^ levels) & state.latch;
+
+        (0..8).filter(|pin| changed & (1 << pin) != 0).map(|pin| PinChange {
+            pin,
+            value: if levels & (1 << pin) != 0 { Value::High } else { Value::Low },
+        }).collect()
+    }
+}
+
+impl Node for PcfNode {
+    ///Inputs are released to their weak pull-up. Outputs keep their level
+    ///until they are written.
+    fn pin_mode(&self, pin: u16, mode: PinMode) {
+        if mode == PinMode::Input {
+            self.set_pin(pin, true);
+        }
+    }
+
+    ///Reads `Low` if the chip doesn't respond.
+    fn digital_read(&self, pin: u16) -> Value {
+        match self.read(&self.lock()) {
+            Ok(levels) if levels & (1 << pin) != 0 => Value::High,
+            _ => Value::Low,
+        }
+    }
+
+    fn digital_write(&self, pin: u16, value: Value) {
+        self.set_pin(pin, value == Value::High);
+    }
+}
+
+///A PCF8574 or PCF8574A I2C port expander.
+///
+///The pins are registered as an extension node, so they can be used as
+///`InputPin`s and `OutputPin`s with the numbers from `pin_base`. They are
+///quasi-bidirectional: a pin written `Low` is driven low, while a pin written
+///`High` is only pulled up weakly and can be used as an input. Making a pin
+///an input writes it `High`. The pull-ups can't be turned off, so
+///`pull_up_dn_control` does nothing.
+///
+///The chip can't report what was written to it, so the written levels are
+///cached. It pulls its INT line low when an input changes, which can be
+///connected to a header pin with `with_interrupt`. The inputs are then read
+///by an interrupt handler, which reports the changes and keeps a cache of the
+///levels, so reading a pin doesn't have to wait for the bus. The INT pin is
+///kept by the expander, and `into_interrupt_pin` returns it.
+///
+///In development mode, the chip can be simulated with
+///`expander::simulation::Pcf8574Slave`.
+///
+///```
+///use std::sync::{Arc, Mutex};
+///use wiringpi::expander::{Pcf8574, PCF8574_ADDRESS};
+///use wiringpi::expander::simulation::Pcf8574Slave;
+///use wiringpi::i2c::{self, I2cDevice};
+///use wiringpi::pin::Value;
+///
+///let chip = Arc::new(Mutex::new(Pcf8574Slave::new()));
+///i2c::simulation::attach(1, PCF8574_ADDRESS, chip.clone());
+///
+///let pi = wiringpi::setup();
+///let expander = Pcf8574::new(I2cDevice::new(PCF8574_ADDRESS).unwrap(), 200).unwrap();
+///
+///let relay = pi.output_pin(200);
+///relay.digital_write(Value::Low);
+///assert_eq!(expander.latch(), 0b1111_1110);
+///assert_eq!(chip.lock().unwrap().latch(), 0b1111_1110);
+///
+///let switch = pi.input_pin(207);
+///assert_eq!(switch.digital_read(), Value::High);
+///chip.lock().unwrap().pull_low(7, true);
+///assert_eq!(switch.digital_read(), Value::Low);
+///```
+pub struct Pcf8574<P = Gpio> {
+    node: Arc<PcfNode>,
+    registration: Registration,
+    interrupt: Option<PcfInterrupt<P>>,
+}
+
+impl Pcf8574 {
+    ///Sets up the chip at `device` and registers its pins from `pin_base`.
+    ///All pins are written `High`, which is also the power-on state.
+    pub fn new(device: I2cDevice, pin_base: u16) -> Result<Pcf8574, ExpanderError> {
+        Pcf8574::setup(device, pin_base, false)
+    }
+}
+
+impl<P: Pin + RequiresRoot> Pcf8574<P> {
+    ///Like `new`, but with the INT line of the chip connected to
+    ///`interrupt`, which gets its pull-up enabled. The changes are reported
+    ///through `events()`.
+    pub fn with_interrupt(device: I2cDevice, pin_base: u16, interrupt: InputPin<P>) -> Result<Pcf8574<P>, ExpanderError> {
+        let mut expander = Pcf8574::setup(device, pin_base, true)?;
+        let (events_tx, events_rx) = mpsc::channel();
+        let node = expander.node.clone();
+
+        interrupt.pull_up_dn_control(Pull::Up);
+
+        let registration = interrupt::Registration::new(interrupt.number(), Edge::Falling, move || {
+            for change in node.read_changes() {
+                let _ = events_tx.send(change);
+            }
+        }).ok_or(ExpanderError::InterruptUnavailable)?;
+
+        expander.interrupt = Some(PcfInterrupt {
+            registration,
+            events: events_rx,
+            pin: interrupt,
+        });
+
+        Ok(expander)
+    }
+
+    ///Stops handling the INT line and returns its pin, if it's connected.
+    pub fn into_interrupt_pin(self) -> Option<InputPin<P>> {
+        self.interrupt.map(|PcfInterrupt { registration, pin, .. }| {
+            drop(registration);
+            pin
+        })
+    }
+}
+
+impl<P> Pcf8574<P> {
+    fn setup(device: I2cDevice, pin_base: u16, cache_levels: bool) -> Result<Pcf8574<P>, ExpanderError> {
+        let node = PcfNode {
+            state: Mutex::new(PcfState {
+                device,
+                latch: 0xFF,
+                levels: None,
+            }),
+        };
+
+        {
+            let mut state = node.lock();
+            state.device.write(0xFF)?;
+
+            if cache_levels {
+                state.levels = Some(state.device.read()?);
+            }
+        }
+
+        let node = Arc::new(node);
+        let registration = extension::register(pin_base, 8, node.clone())?;
+
+        Ok(Pcf8574 {
+            node,
+            registration,
+            interrupt: None,
+        })
+    }
+
+    ///The number of the first pin.
+    pub fn pin_base(&self) -> u16 {
+        self.registration.pin_base()
+    }
+
+    ///The last byte that was written to the chip, with pin 0 in the least
+    ///significant bit.
+    pub fn latch(&self) -> u8 {
+        self.node.lock().latch
+    }
+
+    ///Reads the levels of all pins, with pin 0 in the least significant bit.
+    ///They are taken from the cache when the INT line is connected.
+    pub fn read_all(&self) -> Result<u8, ExpanderError> {
+        let state = self.node.lock();
+        Ok(self.node.read(&state)?)
+    }
+
+    ///Writes all pins, with pin 0 in the least significant bit.
+    pub fn write_all(&self, latch: u8) -> Result<(), ExpanderError> {
+        let mut state = self.node.lock();
+        Ok(self.node.write(&mut state, latch)?)
+    }
+
+    ///The receiver for the input changes, if the INT line is connected.
+    pub fn events(&self) -> Option<&Receiver<PinChange>> {
+        self.interrupt.as_ref().map(|interrupt| &interrupt.events)
+    }
+}
+
+///Simulated port expanders for development mode.
+#[cfg(feature = "development")]
+pub mod simulation {
+    use i2c::simulation::I2cSlave;
+
+    ///A simulated PCF8574, where the inputs are pulled low with `pull_low`.
+    ///
+    ///Each pin reads low if it's written low or pulled low.
+    pub struct Pcf8574Slave {
+        latch: u8,
+        pulled_low: u8,
+    }
+
+    impl Pcf8574Slave {
+        pub fn new() -> Pcf8574Slave {
+            Pcf8574Slave {
+                latch: 0xFF,
+                pulled_low: 0,
+            }
+        }
+
+        ///The last byte that was written.
+        pub fn latch(&self) -> u8 {
+            self.latch
+        }
+
+        ///Simulates an external device pulling `pin` low, or releasing it.
+        pub fn pull_low(&mut self, pin: u8, low: bool) {
+            if low {
+                self.pulled_low |= 1 << pin;
+            } else {
+                self.pulled_low &= !(1 << pin);
+            }
+        }
+
+        ///The levels of the pins.
+        pub fn levels(&self) -> u8 {
+            self.latch & !self.pulled_low
+        }
+    }
+
+    impl Default for Pcf8574Slave {
+        fn default() -> Pcf8574Slave {
+            Pcf8574Slave::new()
+        }
+    }
+
+    impl I2cSlave for Pcf8574Slave {
+        fn write(&mut self, data: &[u8]) {
+            if let Some(&latch) = data.last() {
+                self.latch = latch;
+            }
+        }
+
+        fn read(&mut self, buffer: &mut [u8]) {
+            for byte in buffer {
+                *byte = self.levels();
+            }
+        }
+    }
+}
+
+#[cfg(all(test, feature = "development"))]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use i2c::{self, I2cDevice};
+    use interrupt;
+    use pin::{Gpio, InputPin, Value};
+    use super::{Pcf8574, PinChange, PCF8574_ADDRESS};
+    use super::simulation::Pcf8574Slave;
+
+    #[test]
+    fn interrupt_updates_cache_and_reports_changes() {
+        let chip = Arc::new(Mutex::new(Pcf8574Slave::new()));
+        i2c::simulation::attach(10, PCF8574_ADDRESS, chip.clone());
+
+        let device = I2cDevice::with_bus(10, PCF8574_ADDRESS).unwrap();
+        let expander = Pcf8574::with_interrupt(device, 1200, InputPin::<Gpio>::new(27)).unwrap();
+        let events = expander.events().unwrap();
+
+        //The cached levels only change when the INT line is asserted
+        chip.lock().unwrap().pull_low(3, true);
+        assert_eq!(expander.read_all().unwrap(), 0xFF);
+
+        interrupt::trigger(27);
+        assert_eq!(expander.read_all().unwrap(), 0b1111_0111);
+        assert_eq!(events.try_recv(), Ok(PinChange { pin: 3, value: Value::Low }));
+        assert!(events.try_recv().is_err());
+
+        //Writing a pin reads the levels again, and outputs aren't reported
+        expander.write_all(0b0111_1111).unwrap();
+        assert_eq!(expander.read_all().unwrap(), 0b0111_0111);
+
+        chip.lock().unwrap().pull_low(3, false);
+        interrupt::trigger(27);
+        assert_eq!(events.try_recv(), Ok(PinChange { pin: 3, value: Value::High }));
+        assert!(events.try_recv().is_err());
+
+        let pin = expander.into_interrupt_pin().unwrap();
+        assert_eq!(pin.number(), 27);
+    }
+
+    #[test]
+    fn no_interrupt_pin() {
+        i2c::simulation::attach(10, PCF8574_ADDRESS + 1, Pcf8574Slave::new());
+
+        let device = I2cDevice::with_bus(10, PCF8574_ADDRESS + 1).unwrap();
+        let expander = Pcf8574::new(device, 1210).unwrap();
+
+        assert!(expander.events().is_none());
+        assert!(expander.into_interrupt_pin().is_none());
+    }
+}
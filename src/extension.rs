@@ -0,0 +1,446 @@
+//! Extension nodes, which make the pins of expansion chips available
+//! through the normal pin functions.
+//!
+//! A node claims a range of pin numbers, starting at its pin base. The pin
+//! functions in wiringPi pass any pin in that range on to the node, so an
+//! `InputPin` or `OutputPin` with one of those numbers works like it does for
+//! the header pins.
+
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::fmt;
+use std::sync::{Arc, Mutex, MutexGuard};
+
+use bindings::{self, wiringPiNodeStruct};
+use libc;
+use pin::{Pull, Value};
+
+///The lowest pin base, since the numbers below it are reserved for the
+///pins on the board.
+pub const MIN_PIN_BASE: u16 = 64;
+
+///The modes a pin can be set to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PinMode {
+    Input,
+    Output,
+    PwmOutput,
+}
+
+impl PinMode {
+    fn from_raw(mode: libc::c_int) -> Option<PinMode> {
+        match mode {
+            0 => Some(PinMode::Input),
+            1 => Some(PinMode::Output),
+            2 => Some(PinMode::PwmOutput),
+            _ => None,
+        }
+    }
+}
+
+///The pin functions of an extension node. The pins are counted from 0 at
+///the pin base.
+///
+///Pin functions can't fail, so errors have to be handled by the node, for
+///example by reading `Low`. Functions that aren't implemented do nothing,
+///and the reading functions return 0.
+pub trait Node: Send + Sync {
+    fn pin_mode(&self, _pin: u16, _mode: PinMode) {}
+
+    fn pull_up_dn_control(&self, _pin: u16, _pull: Pull) {}
+
+    fn digital_read(&self, _pin: u16) -> Value {
+        Value::Low
+    }
+
+    fn digital_write(&self, _pin: u16, _value: Value) {}
+
+    fn pwm_write(&self, _pin: u16, _value: i32) {}
+
+    fn analog_read(&self, _pin: u16) -> i32 {
+        0
+    }
+
+    fn analog_write(&self, _pin: u16, _value: i32) {}
+}
+
+impl<N: Node + ?Sized> Node for Arc<N> {
+    fn pin_mode(&self, pin: u16, mode: PinMode) {
+        (**self).pin_mode(pin, mode);
+    }
+
+    fn pull_up_dn_control(&self, pin: u16, pull: Pull) {
+        (**self).pull_up_dn_control(pin, pull);
+    }
+
+    fn digital_read(&self, pin: u16) -> Value {
+        (**self).digital_read(pin)
+    }
+
+    fn digital_write(&self, pin: u16, value: Value) {
+        (**self).digital_write(pin, value);
+    }
+
+    fn pwm_write(&self, pin: u16, value: i32) {
+        (**self).pwm_write(pin, value);
+    }
+
+    fn analog_read(&self, pin: u16) -> i32 {
+        (**self).analog_read(pin)
+    }
+
+    fn analog_write(&self, pin: u16, value: i32) {
+        (**self).analog_write(pin, value);
+    }
+}
+
+///The ways registering an extension node can fail.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExtensionError {
+    ///The pin base is below `MIN_PIN_BASE`.
+    PinBaseTooLow,
+    ///The node has no pins, or so many that the numbers overflow.
+    InvalidPinCount,
+    ///Some of the pins are already used by another node.
+    PinsInUse,
+}
+
+impl fmt::Display for ExtensionError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            ExtensionError::PinBaseTooLow => write!(f, "the pin base has to be at least {}", MIN_PIN_BASE),
+            ExtensionError::InvalidPinCount => f.write_str("the number of pins is 0 or too high"),
+            ExtensionError::PinsInUse => f.write_str("the pins are already used by another extension node"),
+        }
+    }
+}
+
+impl Error for ExtensionError {}
+
+///A registered node and its pin range.
+struct Entry {
+    pin_base: libc::c_int,
+    pins: libc::c_int,
+    node: Arc<dyn Node>,
+}
+
+///The registered nodes, by the address of their wiringPi node. Nodes are
+///removed when they are unregistered, even though wiringPi keeps the
+///address.
+static NODES: Mutex<BTreeMap<usize, Entry>> = Mutex::new(BTreeMap::new());
+
+fn lock_nodes() -> MutexGuard<'static, BTreeMap<usize, Entry>> {
+    NODES.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+///Registers `node` for the `pins` pin numbers starting at `pin_base`. The
+///node is unregistered when the returned `Registration` is dropped.
+///
+///This doesn't require any of the setup functions to be called first.
+///
+///```
+///use std::sync::atomic::{AtomicBool, Ordering};
+///use wiringpi::extension::{self, Node};
+///use wiringpi::pin::Value;
+///
+///#[derive(Default)]
+///struct Latch(AtomicBool);
+///
+///impl Node for Latch {
+///    fn digital_read(&self, _pin: u16) -> Value {
+///        if self.0.load(Ordering::SeqCst) { Value::High } else { Value::Low }
+///    }
+///
+///    fn digital_write(&self, _pin: u16, value: Value) {
+///        self.0.store(value == Value::High, Ordering::SeqCst);
+///    }
+///}
+///
+///let pi = wiringpi::setup();
+///let _registration = extension::register(100, 1, Latch::default()).unwrap();
+///
+///pi.output_pin(100).digital_write(Value::High);
+///assert_eq!(pi.input_pin(100).digital_read(), Value::High);
+///```
+pub fn register<N: Node + 'static>(pin_base: u16, pins: u16, node: N) -> Result<Registration, ExtensionError> {
+    if pin_base < MIN_PIN_BASE {
+        return Err(ExtensionError::PinBaseTooLow);
+    }
+
+    if pins == 0 || pin_base as u32 + pins as u32 > u16::MAX as u32 + 1 {
+        return Err(ExtensionError::InvalidPinCount);
+    }
+
+    let first = pin_base as libc::c_int;
+    let mut nodes = lock_nodes();
+
+    //wiringPi exits the process if the pins overlap, so it's checked first
+    if (first..first + pins as libc::c_int).any(|pin| unsafe { !bindings::wiringPiFindNode(pin).is_null() }) {
+        return Err(ExtensionError::PinsInUse);
+    }
+
+    let raw = unsafe {
+        let raw = bindings::wiringPiNewNode(first, pins as libc::c_int);
+
+        (*raw).pinMode = Some(pin_mode);
+        (*raw).pullUpDnControl = Some(pull_up_dn_control);
+        (*raw).digitalRead = Some(digital_read);
+        (*raw).digitalWrite = Some(digital_write);
+        (*raw).pwmWrite = Some(pwm_write);
+        (*raw).analogRead = Some(analog_read);
+        (*raw).analogWrite = Some(analog_write);
+
+        raw
+    };
+
+    nodes.insert(raw as usize, Entry {
+        pin_base: first,
+        pins: pins as libc::c_int,
+        node: Arc::new(node),
+    });
+
+    Ok(Registration {
+        pin_base,
+        pins,
+        raw: raw as usize,
+    })
+}
+
+///A registered extension node.
+///
+///wiringPi can't remove nodes, so dropping the registration empties the pin
+///range of the node instead. That frees the pin numbers for other nodes, but
+///the wiringPi node stays allocated in its node list, which grows by one
+///node for every registration.
+///
+///wiringPi reads the pin range without any locking, so a pin function that
+///runs at the same time as the drop may still find the old node. Its call
+///does nothing, because the node has already been removed on the Rust side.
+pub struct Registration {
+    pin_base: u16,
+    pins: u16,
+    raw: usize,
+}
+
+impl Registration {
+    pub fn pin_base(&self) -> u16 {
+        self.pin_base
+    }
+
+    ///The number of pins, starting at the pin base.
+    pub fn pins(&self) -> u16 {
+        self.pins
+    }
+}
+
+impl Drop for Registration {
+    fn drop(&mut self) {
+        let mut nodes = lock_nodes();
+        let raw = self.raw as *mut wiringPiNodeStruct;
+
+        unsafe {
+            (*raw).pinMax = (*raw).pinBase - 1;
+        }
+
+        nodes.remove(&self.raw);
+    }
+}
+
+///Finds the Rust side of `node`, and converts `pin` to a number relative to
+///the pin base. Returns `None` if the node has been unregistered, or if
+///`pin` is outside of its range.
+fn find(node: *mut wiringPiNodeStruct, pin: libc::c_int) -> Option<(Arc<dyn Node>, u16)> {
+    let nodes = lock_nodes();
+    let entry = nodes.get(&(node as usize))?;
+    let offset = pin.checked_sub(entry.pin_base)?;
+
+    if offset < 0 || offset >= entry.pins {
+        return None;
+    }
+
+    Some((entry.node.clone(), offset as u16))
+}
+
+fn value_from_raw(value: libc::c_int) -> Value {
+    if value == 0 {
+        Value::Low
+    } else {
+        Value::High
+    }
+}
+
+unsafe extern "C" fn pin_mode(node: *mut wiringPiNodeStruct, pin: libc::c_int, mode: libc::c_int) {
+    if let (Some((node, pin)), Some(mode)) = (find(node, pin), PinMode::from_raw(mode)) {
+        node.pin_mode(pin, mode);
+    }
+}
+
+unsafe extern "C" fn pull_up_dn_control(node: *mut wiringPiNodeStruct, pin: libc::c_int, pud: libc::c_int) {
+    let pull = match pud {
+        1 => Pull::Down,
+        2 => Pull::Up,
+        _ => Pull::Off,
+    };
+
+    if let Some((node, pin)) = find(node, pin) {
+        node.pull_up_dn_control(pin, pull);
+    }
+}
+
+unsafe extern "C" fn digital_read(node: *mut wiringPiNodeStruct, pin: libc::c_int) -> libc::c_int {
+    find(node, pin).map_or(0, |(node, pin)| node.digital_read(pin) as libc::c_int)
+}
+
+unsafe extern "C" fn digital_write(node: *mut wiringPiNodeStruct, pin: libc::c_int, value: libc::c_int) {
+    if let Some((node, pin)) = find(node, pin) {
+        node.digital_write(pin, value_from_raw(value));
+    }
+}
+
+unsafe extern "C" fn pwm_write(node: *mut wiringPiNodeStruct, pin: libc::c_int, value: libc::c_int) {
+    if let Some((node, pin)) = find(node, pin) {
+        node.pwm_write(pin, value);
+    }
+}
+
+unsafe extern "C" fn analog_read(node: *mut wiringPiNodeStruct, pin: libc::c_int) -> libc::c_int {
+    find(node, pin).map_or(0, |(node, pin)| node.analog_read(pin))
+}
+
+unsafe extern "C" fn analog_write(node: *mut wiringPiNodeStruct, pin: libc::c_int, value: libc::c_int) {
+    if let Some((node, pin)) = find(node, pin) {
+        node.analog_write(pin, value);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use bindings::wiringPiNodeStruct;
+    use pin::Value;
+    use super::{digital_write, register, Node};
+
+    struct Counter(Arc<AtomicUsize>);
+
+    impl Node for Counter {
+        fn digital_write(&self, _pin: u16, _value: Value) {
+            self.0.fetch_add(1, Ordering::SeqCst);
+        }
+    }
+
+    #[test]
+    fn dropped_node_ignores_dispatch() {
+        let old_writes = Arc::new(AtomicUsize::new(0));
+        let registration = register(1000, 4, Counter(old_writes.clone())).unwrap();
+        let old_raw = registration.raw as *mut wiringPiNodeStruct;
+
+        unsafe {
+            digital_write(old_raw, 1001, 1);
+            digital_write(old_raw, 1004, 1);
+        }
+
+        assert_eq!(old_writes.load(Ordering::SeqCst), 1);
+        drop(registration);
+
+        let new_writes = Arc::new(AtomicUsize::new(0));
+        let _registration = register(1000, 4, Counter(new_writes.clone())).unwrap();
+
+        unsafe {
+            digital_write(old_raw, 1001, 1);
+        }
+
+        assert_eq!(old_writes.load(Ordering::SeqCst), 1);
+        assert_eq!(new_writes.load(Ordering::SeqCst), 0);
+    }
+}
+
+///The node list and pin function dispatch of wiringPi, for development mode.
+#[cfg(feature = "development")]
+pub(crate) mod simulation {
+    use std::sync::Mutex;
+
+    use bindings::wiringPiNodeStruct;
+    use libc;
+
+    ///The nodes, newest first, like in wiringPi.
+    static NODES: Mutex<Vec<usize>> = Mutex::new(Vec::new());
+
+    pub fn new_node(pin_base: libc::c_int, num_pins: libc::c_int) -> *mut wiringPiNodeStruct {
+        let node = Box::into_raw(Box::new(wiringPiNodeStruct {
+            pinBase: pin_base,
+            pinMax: pin_base + num_pins - 1,
+            ..Default::default()
+        }));
+
+        NODES.lock().unwrap_or_else(|e| e.into_inner()).insert(0, node as usize);
+        node
+    }
+
+    pub fn find_node(pin: libc::c_int) -> *mut wiringPiNodeStruct {
+        NODES.lock().unwrap_or_else(|e| e.into_inner())
+            .iter()
+            .map(|&node| node as *mut wiringPiNodeStruct)
+            .find(|&node| unsafe { pin >= (*node).pinBase && pin <= (*node).pinMax })
+            .unwrap_or(::std::ptr::null_mut())
+    }
+
+    pub unsafe fn pin_mode(pin: libc::c_int, mode: libc::c_int) {
+        let node = find_node(pin);
+
+        if let Some(function) = node.as_ref().and_then(|node| node.pinMode) {
+            function(node, pin, mode);
+        }
+    }
+
+    pub unsafe fn pull_up_dn_control(pin: libc::c_int, pud: libc::c_int) {
+        let node = find_node(pin);
+
+        if let Some(function) = node.as_ref().and_then(|node| node.pullUpDnControl) {
+            function(node, pin, pud);
+        }
+    }
+
+    pub unsafe fn digital_read(pin: libc::c_int) -> libc::c_int {
+        let node = find_node(pin);
+
+        match node.as_ref().and_then(|node| node.digitalRead) {
+            Some(function) => function(node, pin),
+            None => 0,
+        }
+    }
+
+    pub unsafe fn digital_write(pin: libc::c_int, value: libc::c_int) {
+        let node = find_node(pin);
+
+        if let Some(function) = node.as_ref().and_then(|node| node.digitalWrite) {
+            function(node, pin, value);
+        }
+    }
+
+    pub unsafe fn pwm_write(pin: libc::c_int, value: libc::c_int) {
+        let node = find_node(pin);
+
+        if let Some(function) = node.as_ref().and_then(|node| node.pwmWrite) {
+            function(node, pin, value);
+        }
+    }
+
+    pub unsafe fn analog_read(pin: libc::c_int) -> libc::c_int {
+        let node = find_node(pin);
+
+        match node.as_ref().and_then(|node| node.analogRead) {
+            Some(function) => function(node, pin),
+            None => 0,
+        }
+    }
+
+    pub unsafe fn analog_write(pin: libc::c_int, value: libc::c_int) {
+        let node = find_node(pin);
+
+        if let Some(function) = node.as_ref().and_then(|node| node.analogWrite) {
+            function(node, pin, value);
+        }
+    }
+}
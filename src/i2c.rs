@@ -0,0 +1,543 @@
+//! I2C devices.
+
+use std::error::Error;
+use std::fmt;
+use std::io;
+
+use bindings;
+use libc;
+
+///The bus that is exposed on the header of all current models.
+pub const DEFAULT_BUS: u8 = 1;
+
+///The most bytes that can be written or read in one block transfer, which
+///is the limit of the `I2C_RDWR` ioctl of i2c-dev.
+pub const MAX_BLOCK_LEN: usize = 8192;
+
+///The ways talking to an I2C device can fail.
+#[derive(Debug)]
+pub enum I2cError {
+    ///The address doesn't fit in 7 bits.
+    InvalidAddress,
+    ///A block transfer is longer than `MAX_BLOCK_LEN` bytes.
+    BlockTooLong,
+    ///The bus couldn't be opened or the transfer failed, for example
+    ///because no device answered.
+    Io(io::Error),
+}
+
+impl fmt::Display for I2cError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            I2cError::InvalidAddress => f.write_str("the I2C address has to be between 0x00 and 0x7F"),
+            I2cError::BlockTooLong => write!(f, "I2C block transfers can't be longer than {} bytes", MAX_BLOCK_LEN),
+            I2cError::Io(ref error) => write!(f, "I2C error: {}", error),
+        }
+    }
+}
+
+impl Error for I2cError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match *self {
+            I2cError::Io(ref error) => Some(error),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for I2cError {
+    fn from(error: io::Error) -> I2cError {
+        I2cError::Io(error)
+    }
+}
+
+///A device on an I2C bus.
+///
+///The single byte and register functions use the SMBus transfers in
+///wiringPi, where 16 bit registers are sent with the low byte first. The
+///block transfers send the bytes as they are.
+///
+///In development mode, the transfers go to devices that are attached with
+///`i2c::simulation::attach`.
+///
+///```no_run
+///use wiringpi::i2c::I2cDevice;
+///
+///let sensor = I2cDevice::new(0x48).unwrap();
+///let raw = sensor.read_reg16(0x00).unwrap();
+///println!("raw temperature: {}", raw.swap_bytes() >> 4);
+///```
+pub struct I2cDevice {
+    fd: libc::c_int,
+    bus: u8,
+    address: u16,
+}
+
+impl I2cDevice {
+    ///Opens the device with the 7 bit `address` on the default bus.
+    pub fn new(address: u16) -> Result<I2cDevice, I2cError> {
+        I2cDevice::with_bus(DEFAULT_BUS, address)
+    }
+
+    ///Opens the device with the 7 bit `address` on `/dev/i2c-<bus>`.
+    pub fn with_bus(bus: u8, address: u16) -> Result<I2cDevice, I2cError> {
+        if address > 0x7F {
+            return Err(I2cError::InvalidAddress);
+        }
+
+        let fd = backend::open(bus, address)?;
+
+        Ok(I2cDevice {
+            fd,
+            bus,
+            address,
+        })
+    }
+
+    pub fn bus(&self) -> u8 {
+        self.bus
+    }
+
+    pub fn address(&self) -> u16 {
+        self.address
+    }
+
+    ///Reads a single byte, without sending a register.
+    pub fn read(&self) -> Result<u8, I2cError> {
+        check(unsafe { bindings::wiringPiI2CRead(self.fd) }).map(|value| value as u8)
+    }
+
+    ///Writes a single byte, without sending a register.
+    pub fn write(&self, data: u8) -> Result<(), I2cError> {
+        check(unsafe { bindings::wiringPiI2CWrite(self.fd, data as libc::c_int) }).map(|_| ())
+    }
+
+    ///Reads an 8 bit value from the register `reg`.
+    pub fn read_reg8(&self, reg: u8) -> Result<u8, I2cError> {
+        check(unsafe { bindings::wiringPiI2CReadReg8(self.fd, reg as libc::c_int) }).map(|value| value as u8)
+    }
+
+    ///Reads a 16 bit value from the register `reg`, where the low byte is
+    ///received first.
+    pub fn read_reg16(&self, reg: u8) -> Result<u16, I2cError> {
+        check(unsafe { bindings::wiringPiI2CReadReg16(self.fd, reg as libc::c_int) }).map(|value| value as u16)
+    }
+
+    ///Writes an 8 bit value to the register `reg`.
+    pub fn write_reg8(&self, reg: u8, value: u8) -> Result<(), I2cError> {
+        check(unsafe {
+            bindings::wiringPiI2CWriteReg8(self.fd, reg as libc::c_int, value as libc::c_int)
+        }).map(|_| ())
+    }
+
+    ///Writes a 16 bit value to the register `reg`, where the low byte is
+    ///sent first.
+    pub fn write_reg16(&self, reg: u8, value: u16) -> Result<(), I2cError> {
+        check(unsafe {
+            bindings::wiringPiI2CWriteReg16(self.fd, reg as libc::c_int, value as libc::c_int)
+        }).map(|_| ())
+    }
+
+    ///Reads `buffer.len()` bytes in one transfer, which can be at most
+    ///`MAX_BLOCK_LEN` bytes.
+    pub fn read_block(&self, buffer: &mut [u8]) -> Result<(), I2cError> {
+        self.transfer(&[], buffer)
+    }
+
+    ///Writes `data` in one transfer, which can be at most `MAX_BLOCK_LEN`
+    ///bytes.
+    pub fn write_block(&self, data: &[u8]) -> Result<(), I2cError> {
+        self.transfer(data, &mut [])
+    }
+
+    ///Writes `data` and then reads `buffer.len()` bytes, with a repeated
+    ///start condition in between. Each part can be at most `MAX_BLOCK_LEN`
+    ///bytes.
+    pub fn write_read(&self, data: &[u8], buffer: &mut [u8]) -> Result<(), I2cError> {
+        self.transfer(data, buffer)
+    }
+
+    ///Reads `buffer.len()` bytes, starting at the register `reg`.
+    pub fn read_reg_block(&self, reg: u8, buffer: &mut [u8]) -> Result<(), I2cError> {
+        self.write_read(&[reg], buffer)
+    }
+
+    ///Writes `data`, starting at the register `reg`. The register counts
+    ///towards `MAX_BLOCK_LEN`.
+    pub fn write_reg_block(&self, reg: u8, data: &[u8]) -> Result<(), I2cError> {
+        if data.len() >= MAX_BLOCK_LEN {
+            return Err(I2cError::BlockTooLong);
+        }
+
+        let mut message = Vec::with_capacity(data.len() + 1);
+        message.push(reg);
+        message.extend_from_slice(data);
+        self.write_block(&message)
+    }
+
+    fn transfer(&self, write: &[u8], read: &mut [u8]) -> Result<(), I2cError> {
+        if write.len() > MAX_BLOCK_LEN || read.len() > MAX_BLOCK_LEN {
+            return Err(I2cError::BlockTooLong);
+        }
+
+        backend::transfer(self.fd, self.address, write, read).map_err(I2cError::Io)
+    }
+}
+
+impl Drop for I2cDevice {
+    fn drop(&mut self) {
+        backend::close(self.fd);
+    }
+}
+
+fn check(result: libc::c_int) -> Result<libc::c_int, I2cError> {
+    if result < 0 {
+        Err(I2cError::Io(backend::last_error()))
+    } else {
+        Ok(result)
+    }
+}
+
+#[cfg(not(feature = "development"))]
+mod backend {
+    use std::ffi::CString;
+    use std::io;
+
+    use libc;
+
+    const I2C_SLAVE: libc::c_ulong = 0x0703;
+    const I2C_RDWR: libc::c_ulong = 0x0707;
+    const I2C_M_RD: u16 = 0x0001;
+
+    #[repr(C)]
+    struct Message {
+        addr: u16,
+        flags: u16,
+        len: u16,
+        buf: *mut u8,
+    }
+
+    #[repr(C)]
+    struct Messages {
+        msgs: *mut Message,
+        nmsgs: u32,
+    }
+
+    pub fn open(bus: u8, address: u16) -> io::Result<libc::c_int> {
+        let path = CString::new(format!("/dev/i2c-{}", bus)).expect("the path has no zero bytes");
+
+        unsafe {
+            let fd = libc::open(path.as_ptr(), libc::O_RDWR);
+
+            if fd < 0 {
+                return Err(io::Error::last_os_error());
+            }
+
+            if libc::ioctl(fd, I2C_SLAVE as _, address as libc::c_ulong) < 0 {
+                let error = io::Error::last_os_error();
+                libc::close(fd);
+                return Err(error);
+            }
+
+            Ok(fd)
+        }
+    }
+
+    pub fn close(fd: libc::c_int) {
+        unsafe {
+            libc::close(fd);
+        }
+    }
+
+    pub fn transfer(fd: libc::c_int, address: u16, write: &[u8], read: &mut [u8]) -> io::Result<()> {
+        let mut messages = Vec::with_capacity(2);
+
+        if !write.is_empty() {
+            messages.push(Message {
+                addr: address,
+                flags: 0,
+                len: write.len() as u16,
+                buf: write.as_ptr() as *mut u8,
+            });
+        }
+
+        if !read.is_empty() {
+            messages.push(Message {
+                addr: address,
+                flags: I2C_M_RD,
+                len: read.len() as u16,
+                buf: read.as_mut_ptr(),
+            });
+        }
+
+        if messages.is_empty() {
+            return Ok(());
+        }
+
+        let mut data = Messages {
+            msgs: messages.as_mut_ptr(),
+            nmsgs: messages.len() as u32,
+        };
+
+        if unsafe { libc::ioctl(fd, I2C_RDWR as _, &mut data as *mut Messages) } < 0 {
+            Err(io::Error::last_os_error())
+        } else {
+            Ok(())
+        }
+    }
+
+    pub fn last_error() -> io::Error {
+        io::Error::last_os_error()
+    }
+}
+
+#[cfg(feature = "development")]
+mod backend {
+    use std::io;
+
+    use libc;
+
+    use super::simulation;
+
+    pub fn open(bus: u8, address: u16) -> io::Result<libc::c_int> {
+        Ok(simulation::open(bus, address))
+    }
+
+    pub fn close(fd: libc::c_int) {
+        simulation::close(fd);
+    }
+
+    pub fn transfer(fd: libc::c_int, _address: u16, write: &[u8], read: &mut [u8]) -> io::Result<()> {
+        simulation::transfer(fd, write, read)
+    }
+
+    pub fn last_error() -> io::Error {
+        simulation::take_error()
+    }
+}
+
+///Simulated I2C devices for development mode.
+///
+///Devices are attached to a bus address, where `I2cDevice` will find them.
+///Transfers to addresses without a device fail, like they do on a real bus.
+///
+///```
+///use std::sync::{Arc, Mutex};
+///use wiringpi::i2c::I2cDevice;
+///use wiringpi::i2c::simulation::{self, RegisterSlave};
+///
+///let sensor = Arc::new(Mutex::new(RegisterSlave::new()));
+///sensor.lock().unwrap().set(0x0F, 0x33);
+///simulation::attach(1, 0x19, sensor.clone());
+///
+///let device = I2cDevice::new(0x19).unwrap();
+///assert_eq!(device.read_reg8(0x0F).unwrap(), 0x33);
+///
+///device.write_reg8(0x20, 0x57).unwrap();
+///assert_eq!(sensor.lock().unwrap().get(0x20), 0x57);
+///```
+#[cfg(feature = "development")]
+pub mod simulation {
+    use std::cell::RefCell;
+    use std::collections::BTreeMap;
+    use std::io;
+    use std::sync::{Arc, Mutex, MutexGuard};
+
+    use libc;
+
+    ///A simulated device on an I2C bus.
+    pub trait I2cSlave: Send {
+        ///Receives the bytes of a write transfer.
+        fn write(&mut self, data: &[u8]);
+
+        ///Fills the buffer of a read transfer.
+        fn read(&mut self, buffer: &mut [u8]);
+    }
+
+    impl<S: I2cSlave> I2cSlave for Arc<Mutex<S>> {
+        fn write(&mut self, data: &[u8]) {
+            self.lock().unwrap_or_else(|e| e.into_inner()).write(data);
+        }
+
+        fn read(&mut self, buffer: &mut [u8]) {
+            self.lock().unwrap_or_else(|e| e.into_inner()).read(buffer);
+        }
+    }
+
+    ///A device with 256 8 bit registers, like most I2C sensors.
+    ///
+    ///The first byte of each write selects the register, and the following
+    ///bytes are written from there. Reads start at the selected register.
+    ///The register pointer increases after each byte.
+    pub struct RegisterSlave {
+        registers: [u8; 256],
+        pointer: u8,
+    }
+
+    impl RegisterSlave {
+        pub fn new() -> RegisterSlave {
+            RegisterSlave {
+                registers: [0; 256],
+                pointer: 0,
+            }
+        }
+
+        pub fn get(&self, reg: u8) -> u8 {
+            self.registers[reg as usize]
+        }
+
+        pub fn set(&mut self, reg: u8, value: u8) {
+            self.registers[reg as usize] = value;
+        }
+
+        pub fn registers(&self) -> &[u8] {
+            &self.registers
+        }
+    }
+
+    impl Default for RegisterSlave {
+        fn default() -> RegisterSlave {
+            RegisterSlave::new()
+        }
+    }
+
+    impl I2cSlave for RegisterSlave {
+        fn write(&mut self, data: &[u8]) {
+            if let Some((&reg, values)) = data.split_first() {
+                self.pointer = reg;
+
+                for &value in values {
+                    self.registers[self.pointer as usize] = value;
+                    self.pointer = self.pointer.wrapping_add(1);
+                }
+            }
+        }
+
+        fn read(&mut self, buffer: &mut [u8]) {
+            for value in buffer {
+                *value = self.registers[self.pointer as usize];
+                self.pointer = self.pointer.wrapping_add(1);
+            }
+        }
+    }
+
+    struct Bus {
+        slaves: BTreeMap<(u8, u16), Box<dyn I2cSlave>>,
+        handles: BTreeMap<libc::c_int, (u8, u16)>,
+        next_fd: libc::c_int,
+    }
+
+    static BUS: Mutex<Bus> = Mutex::new(Bus {
+        slaves: BTreeMap::new(),
+        handles: BTreeMap::new(),
+        next_fd: 1000,
+    });
+
+    thread_local! {
+        static LAST_ERROR: RefCell<Option<io::Error>> = const { RefCell::new(None) };
+    }
+
+    fn lock_bus() -> MutexGuard<'static, Bus> {
+        BUS.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    ///Attaches `slave` to `address` on `bus`, replacing any device that was
+    ///there before.
+    pub fn attach<S: I2cSlave + 'static>(bus: u8, address: u16, slave: S) {
+        lock_bus().slaves.insert((bus, address), Box::new(slave));
+    }
+
+    ///Removes the device at `address` on `bus`. Returns `false` if there was
+    ///no device.
+    pub fn detach(bus: u8, address: u16) -> bool {
+        lock_bus().slaves.remove(&(bus, address)).is_some()
+    }
+
+    pub(crate) fn open(bus: u8, address: u16) -> libc::c_int {
+        let mut state = lock_bus();
+        let fd = state.next_fd;
+        state.next_fd += 1;
+        state.handles.insert(fd, (bus, address));
+        fd
+    }
+
+    pub(crate) fn close(fd: libc::c_int) {
+        lock_bus().handles.remove(&fd);
+    }
+
+    pub(crate) fn transfer(fd: libc::c_int, write: &[u8], read: &mut [u8]) -> io::Result<()> {
+        let mut bus = lock_bus();
+        let key = *bus.handles.get(&fd).ok_or_else(|| io::Error::from_raw_os_error(libc::EBADF))?;
+        let slave = bus.slaves.get_mut(&key).ok_or_else(|| io::Error::from_raw_os_error(libc::ENXIO))?;
+
+        if !write.is_empty() {
+            slave.write(write);
+        }
+
+        if !read.is_empty() {
+            slave.read(read);
+        }
+
+        Ok(())
+    }
+
+    ///Simulates the SMBus reads in wiringPi, returning -1 on errors.
+    pub(crate) fn smbus_read(fd: libc::c_int, reg: Option<libc::c_int>, length: usize) -> libc::c_int {
+        let mut buffer = [0; 2];
+        let reg = reg.map(|reg| [reg as u8]);
+        let write: &[u8] = match reg {
+            Some(ref reg) => reg,
+            None => &[],
+        };
+
+        match transfer(fd, write, &mut buffer[..length]) {
+            Ok(()) => (buffer[0] as libc::c_int) | (buffer[1] as libc::c_int) << 8,
+            Err(error) => fail(error),
+        }
+    }
+
+    ///Simulates the SMBus writes in wiringPi, returning -1 on errors.
+    pub(crate) fn smbus_write(fd: libc::c_int, data: &[u8]) -> libc::c_int {
+        match transfer(fd, data, &mut []) {
+            Ok(()) => 0,
+            Err(error) => fail(error),
+        }
+    }
+
+    pub(crate) fn take_error() -> io::Error {
+        LAST_ERROR.with(|error| error.borrow_mut().take())
+            .unwrap_or_else(|| io::Error::other("unknown simulated I2C error"))
+    }
+
+    fn fail(error: io::Error) -> libc::c_int {
+        LAST_ERROR.with(|last| *last.borrow_mut() = Some(error));
+        -1
+    }
+}
+
+#[cfg(all(test, feature = "development"))]
+mod tests {
+    use super::{I2cDevice, I2cError, MAX_BLOCK_LEN};
+    use super::simulation::{self, RegisterSlave};
+
+    #[test]
+    fn block_length() {
+        simulation::attach(9, 0x20, RegisterSlave::new());
+        let device = I2cDevice::with_bus(9, 0x20).unwrap();
+
+        assert_eq!(MAX_BLOCK_LEN, 8192);
+
+        let data = vec![0; 8193];
+        let mut buffer = vec![0; 8193];
+
+        assert!(device.write_block(&data[..8192]).is_ok());
+        assert!(device.read_block(&mut buffer[..8192]).is_ok());
+        assert!(device.write_read(&data[..8192], &mut buffer[..8192]).is_ok());
+
+        assert!(matches!(device.write_block(&data), Err(I2cError::BlockTooLong)));
+        assert!(matches!(device.read_block(&mut buffer), Err(I2cError::BlockTooLong)));
+        assert!(matches!(device.write_read(&data[..1], &mut buffer), Err(I2cError::BlockTooLong)));
+        assert!(matches!(device.write_reg_block(0, &data[..8192]), Err(I2cError::BlockTooLong)));
+        assert!(device.write_reg_block(0, &data[..8191]).is_ok());
+    }
+}
@@ -0,0 +1,173 @@
+//! Closure based interrupt handlers.
+//!
+//! `wiringPiISR` only accepts plain functions without any user data, so this
+//! module keeps one trampoline function per pin number and forwards each call
+//! to a boxed closure stored in a per-pin slot.
+
+use std::sync::{Mutex, MutexGuard};
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use bindings;
+use libc;
+use pin::Edge;
+
+///The number of pins that can have an interrupt handler, which is the same
+///as the number of ISR slots in wiringPi.
+pub const MAX_PINS: usize = 64;
+
+type Handler = Box<dyn FnMut() + Send>;
+
+struct Slot {
+    ///The edge wiringPi watches, once the trampoline has been registered.
+    edge: Option<libc::c_int>,
+    ///The generation of the registration that owns the slot.
+    owner: Option<u64>,
+    ///The handler of the owner. It's taken out of the slot while it runs.
+    handler: Option<Handler>,
+}
+
+static SLOTS: [Mutex<Slot>; MAX_PINS] = [const { Mutex::new(Slot { edge: None, owner: None, handler: None }) }; MAX_PINS];
+static GENERATION: AtomicU64 = AtomicU64::new(0);
+
+fn lock(index: usize) -> MutexGuard<'static, Slot> {
+    SLOTS[index].lock().unwrap_or_else(|e| e.into_inner())
+}
+
+extern "C" fn trampoline<const PIN: usize>() {
+    let (owner, mut handler) = {
+        let mut slot = lock(PIN);
+
+        match (slot.owner, slot.handler.take()) {
+            (Some(owner), Some(handler)) => (owner, handler),
+            _ => return,
+        }
+    };
+
+    //The slot is unlocked while the handler runs, so it can't block
+    //registrations, and it's only put back if it's still registered
+    handler();
+
+    let mut slot = lock(PIN);
+    if slot.owner == Some(owner) && slot.handler.is_none() {
+        slot.handler = Some(handler);
+    }
+}
+
+macro_rules! trampolines {
+    ($($pin:expr),+) => (
+        [$(trampoline::<$pin> as extern "C" fn()),+]
+    )
+}
+
+static TRAMPOLINES: [extern "C" fn(); MAX_PINS] = trampolines!(
+    0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15,
+    16, 17, 18, 19, 20, 21, 22, 23, 24, 25, 26, 27, 28, 29, 30, 31,
+    32, 33, 34, 35, 36, 37, 38, 39, 40, 41, 42, 43, 44, 45, 46, 47,
+    48, 49, 50, 51, 52, 53, 54, 55, 56, 57, 58, 59, 60, 61, 62, 63
+);
+
+///Installs `handler` as the interrupt handler for `pin` and returns the
+///generation of the registration.
+///
+///wiringPi starts a new polling thread every time `wiringPiISR` is called,
+///so the trampoline is only registered the first time a pin is used, and the
+///edge of that first registration stays in effect for the pin.
+///
+///Returns `None` if the pin number is out of range, if the pin already has a
+///handler, if the pin is watched for a different edge, or if wiringPi could
+///not set up the interrupt.
+fn register<F>(pin: libc::c_int, edge: Edge, handler: F) -> Option<u64>
+    where F: FnMut() + Send + 'static
+{
+    if pin < 0 || pin as usize >= MAX_PINS {
+        return None;
+    }
+
+    let index = pin as usize;
+    let mode = edge as libc::c_int;
+    let mut slot = lock(index);
+
+    if slot.owner.is_some() {
+        return None;
+    }
+
+    match slot.edge {
+        Some(registered) if registered != mode => return None,
+        Some(_) => {},
+        None => {
+            let result = unsafe {
+                bindings::wiringPiISR(pin, mode, Some(TRAMPOLINES[index]))
+            };
+
+            if result < 0 {
+                return None;
+            }
+
+            slot.edge = Some(mode);
+        },
+    }
+
+    let generation = GENERATION.fetch_add(1, Ordering::SeqCst);
+    slot.owner = Some(generation);
+    slot.handler = Some(Box::new(handler));
+
+    Some(generation)
+}
+
+///Removes the interrupt handler for `pin` if it still belongs to the
+///registration with `generation`. The wiringPi thread keeps running, but the
+///interrupts are ignored until a new handler is registered.
+fn unregister(pin: libc::c_int, generation: u64) {
+    let handler = {
+        let mut slot = lock(pin as usize);
+
+        if slot.owner != Some(generation) {
+            return;
+        }
+
+        slot.owner = None;
+        slot.handler.take()
+    };
+
+    //The handler may own a registration for the same pin
+    drop(handler);
+}
+
+///An interrupt handler for a pin, which is unregistered when dropped.
+///
+///Each pin can only have one handler at a time. Handlers run on the polling
+///thread of wiringPi, without any locks held by this module.
+pub struct Registration {
+    pin: libc::c_int,
+    generation: u64,
+}
+
+impl Registration {
+    ///Registers `handler` for `edge`s on `pin` and returns a guard that
+    ///removes it again.
+    ///
+    ///Returns `None` if the pin number is out of range, if the pin already
+    ///has a handler, if wiringPi could not set up the interrupt, or if
+    ///`edge` is different from the edge of the first registration for the
+    ///pin, since wiringPi can't change it.
+    pub fn new<F>(pin: libc::c_int, edge: Edge, handler: F) -> Option<Registration>
+        where F: FnMut() + Send + 'static
+    {
+        register(pin, edge, handler).map(|generation| Registration {
+            pin,
+            generation,
+        })
+    }
+}
+
+impl Drop for Registration {
+    fn drop(&mut self) {
+        unregister(self.pin, self.generation);
+    }
+}
+
+///Runs the handler for `pin` like an interrupt would.
+#[cfg(all(test, feature = "development"))]
+pub fn trigger(pin: libc::c_int) {
+    TRAMPOLINES[pin as usize]();
+}
@@ -0,0 +1,321 @@
+//! Matrix keypad scanning.
+
+use std::mem;
+use std::sync::mpsc::Receiver;
+use std::time::Duration;
+
+use bindings;
+use pin::{InputPin, OutputPin, Pin, Pull, RequiresRoot, Value};
+use poller::Poller;
+
+///A key changing state.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum KeyEvent<K> {
+    Pressed(K),
+    Released(K),
+}
+
+///A keypad where the keys connect row and column lines in a matrix.
+///
+///The rows are driven low one at a time, while the columns are read with
+///their pull-up resistors enabled. A pressed key pulls its column low when
+///its row is active. The row pins are given as `OutputPin`s, but the
+///inactive rows are switched to inputs, so pressing several keys in the same
+///column doesn't short an active row to an inactive one. Each key has to
+///read the same for a number of consecutive scans before a change is
+///accepted, which debounces the keys.
+///
+///Keypads without diodes can't tell which keys are pressed when three keys
+///form three corners of a rectangle, since the fourth corner will appear to
+///be pressed too. The keypad detects these situations and holds back any
+///changes until they are resolved. `is_ghosting()` tells if that is the case.
+///
+///```no_run
+///use wiringpi::keypad::Keypad;
+///
+///let pi = wiringpi::setup_gpio();
+///let rows = vec![pi.output_pin(5), pi.output_pin(6), pi.output_pin(13), pi.output_pin(19)];
+///let columns = vec![pi.input_pin(12), pi.input_pin(16), pi.input_pin(20)];
+///let layout = vec![
+///    vec!['1', '2', '3'],
+///    vec!['4', '5', '6'],
+///    vec!['7', '8', '9'],
+///    vec!['*', '0', '#'],
+///];
+///
+///let keypad = Keypad::new(rows, columns, layout).unwrap();
+///let scanner = keypad.spawn(std::time::Duration::from_millis(5));
+///
+///for event in scanner.events() {
+///    println!("{:?}", event);
+///}
+///```
+pub struct Keypad<P, K> {
+    rows: Vec<InputPin<P>>,
+    columns: Vec<InputPin<P>>,
+    layout: Vec<Vec<K>>,
+    debouncer: Debouncer,
+}
+
+impl<P: Pin + RequiresRoot, K: Clone> Keypad<P, K> {
+    ///Creates a keypad from its row and column pins, where `layout[row][column]`
+    ///is the key at each position. The rows are switched to inputs until
+    ///they are scanned.
+    ///
+    ///Returns `None` if there are no rows or columns, or if the layout
+    ///doesn't have one entry per row and column.
+    pub fn new(rows: Vec<OutputPin<P>>, columns: Vec<InputPin<P>>, layout: Vec<Vec<K>>) -> Option<Keypad<P, K>> {
+        if rows.is_empty() || columns.is_empty() {
+            return None;
+        }
+
+        if layout.len() != rows.len() || layout.iter().any(|row| row.len() != columns.len()) {
+            return None;
+        }
+
+        //The output level is kept while the rows are inputs, so they go
+        //straight to low when they are switched to outputs
+        let rows = rows.into_iter().map(|row| {
+            row.digital_write(Value::Low);
+            row.into_input()
+        }).collect();
+
+        for column in &columns {
+            column.pull_up_dn_control(Pull::Up);
+        }
+
+        let debouncer = Debouncer::new(layout.len(), columns.len());
+
+        Some(Keypad {
+            rows,
+            columns,
+            layout,
+            debouncer,
+        })
+    }
+
+    ///Sets how many consecutive scans a key has to read the same before a
+    ///change is accepted. The default is 3.
+    pub fn set_debounce_scans(&mut self, scans: u8) {
+        self.debouncer.scans = scans.max(1);
+    }
+
+    ///Scans the keypad once and returns the keys that changed state.
+    pub fn scan(&mut self) -> Vec<KeyEvent<K>> {
+        let raw = self.read_matrix();
+        let columns = self.columns.len();
+        let layout = &self.layout;
+
+        self.debouncer.update(&raw).into_iter().map(|(index, pressed)| {
+            let key = layout[index / columns][index % columns].clone();
+
+            if pressed {
+                KeyEvent::Pressed(key)
+            } else {
+                KeyEvent::Released(key)
+            }
+        }).collect()
+    }
+
+    ///The keys that are currently held down, after debouncing.
+    pub fn pressed_keys(&self) -> Vec<K> {
+        let columns = self.columns.len();
+
+        self.debouncer.pressed.iter()
+            .enumerate()
+            .filter(|&(_, &pressed)| pressed)
+            .map(|(index, _)| self.layout[index / columns][index % columns].clone())
+            .collect()
+    }
+
+    ///Checks if the latest scan saw a combination of keys that can't be
+    ///told apart from a different combination.
+    pub fn is_ghosting(&self) -> bool {
+        self.debouncer.ghosting
+    }
+
+    ///Returns the row and column pins.
+    pub fn into_inner(self) -> (Vec<OutputPin<P>>, Vec<InputPin<P>>) {
+        (self.rows.into_iter().map(InputPin::into_output).collect(), self.columns)
+    }
+
+    fn read_matrix(&mut self) -> Vec<bool> {
+        let mut raw = Vec::with_capacity(self.debouncer.pressed.len());
+
+        for row in mem::take(&mut self.rows) {
+            let row = row.into_output();
+            row.digital_write(Value::Low);
+
+            unsafe {
+                bindings::delayMicroseconds(10);
+            }
+
+            raw.extend(self.columns.iter().map(|column| column.digital_read() == Value::Low));
+            self.rows.push(row.into_input());
+        }
+
+        raw
+    }
+}
+
+///The debouncing and ghosting detection, over the raw matrix of one scan
+///per call, where `raw[row * columns + column]` is set for each key that
+///reads as pressed.
+struct Debouncer {
+    columns: usize,
+    scans: u8,
+    pressed: Vec<bool>,
+    counters: Vec<u8>,
+    ghosting: bool,
+}
+
+impl Debouncer {
+    fn new(rows: usize, columns: usize) -> Debouncer {
+        Debouncer {
+            columns,
+            scans: 3,
+            pressed: vec![false; rows * columns],
+            counters: vec![0; rows * columns],
+            ghosting: false,
+        }
+    }
+
+    ///Returns the index and new state of each key that changed.
+    fn update(&mut self, raw: &[bool]) -> Vec<(usize, bool)> {
+        self.ghosting = is_ambiguous(raw, self.columns);
+
+        let mut changes = Vec::new();
+
+        if self.ghosting {
+            return changes;
+        }
+
+        for (index, &raw_pressed) in raw.iter().enumerate() {
+            if raw_pressed == self.pressed[index] {
+                self.counters[index] = 0;
+                continue;
+            }
+
+            self.counters[index] += 1;
+
+            if self.counters[index] >= self.scans {
+                self.counters[index] = 0;
+                self.pressed[index] = raw_pressed;
+                changes.push((index, raw_pressed));
+            }
+        }
+
+        changes
+    }
+}
+
+///Checks if two rows share two or more pressed columns, which makes the
+///fourth corner of the rectangle ambiguous.
+fn is_ambiguous(raw: &[bool], columns: usize) -> bool {
+    let rows: Vec<&[bool]> = raw.chunks(columns).collect();
+
+    for (index, first) in rows.iter().enumerate() {
+        for second in &rows[index + 1..] {
+            let shared = first.iter().zip(second.iter()).filter(|&(&a, &b)| a && b).count();
+
+            if shared >= 2 {
+                return true;
+            }
+        }
+    }
+
+    false
+}
+
+impl<P, K> Keypad<P, K>
+    where P: Pin + RequiresRoot + Send + 'static,
+          K: Clone + Send + 'static
+{
+    ///Moves the keypad to a background thread that scans it every
+    ///`interval`.
+    pub fn spawn(self, interval: Duration) -> KeypadScanner<P, K> {
+        KeypadScanner {
+            poller: Poller::spawn(self, interval, Keypad::scan),
+        }
+    }
+}
+
+///A keypad that is scanned on a background thread.
+pub struct KeypadScanner<P, K> {
+    poller: Poller<Keypad<P, K>, KeyEvent<K>>,
+}
+
+impl<P, K> KeypadScanner<P, K> {
+    ///The receiver for the key events.
+    pub fn events(&self) -> &Receiver<KeyEvent<K>> {
+        self.poller.events()
+    }
+
+    ///Stops the background scanning and returns the keypad.
+    pub fn stop(self) -> Keypad<P, K> {
+        self.poller.stop().expect("the keypad scanner thread panicked")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use pin::{Gpio, InputPin, OutputPin};
+    use super::{is_ambiguous, Debouncer, Keypad};
+
+    #[test]
+    fn empty_matrix() {
+        let rows = vec![OutputPin::<Gpio>::new(5)];
+        assert!(Keypad::new(rows, Vec::new(), vec![Vec::<char>::new()]).is_none());
+
+        let columns = vec![InputPin::<Gpio>::new(12)];
+        assert!(Keypad::<Gpio, char>::new(Vec::new(), columns, Vec::new()).is_none());
+    }
+
+    #[test]
+    fn debounced_press_and_release() {
+        let mut debouncer = Debouncer::new(2, 2);
+        let pressed = [false, true, false, false];
+        let released = [false; 4];
+
+        assert!(debouncer.update(&pressed).is_empty());
+        assert!(debouncer.update(&pressed).is_empty());
+        assert_eq!(debouncer.update(&pressed), vec![(1, true)]);
+        assert!(debouncer.update(&pressed).is_empty());
+
+        assert!(debouncer.update(&released).is_empty());
+        assert!(debouncer.update(&released).is_empty());
+        assert_eq!(debouncer.update(&released), vec![(1, false)]);
+    }
+
+    #[test]
+    fn bounce_restarts_debouncing() {
+        let mut debouncer = Debouncer::new(1, 2);
+        debouncer.scans = 2;
+
+        assert!(debouncer.update(&[true, false]).is_empty());
+        assert!(debouncer.update(&[false, false]).is_empty());
+        assert!(debouncer.update(&[true, false]).is_empty());
+        assert_eq!(debouncer.update(&[true, false]), vec![(0, true)]);
+    }
+
+    #[test]
+    fn ambiguous_rectangle() {
+        assert!(!is_ambiguous(&[true, true, false, false], 2));
+        assert!(!is_ambiguous(&[true, false, true, false], 2));
+        assert!(!is_ambiguous(&[true, true, true, false], 2));
+        assert!(is_ambiguous(&[true, true, true, true], 2));
+        assert!(is_ambiguous(&[false, true, true, false, false, false, false, true, true], 3));
+    }
+
+    #[test]
+    fn ghosting_holds_back_changes() {
+        let mut debouncer = Debouncer::new(2, 2);
+        debouncer.scans = 1;
+
+        assert_eq!(debouncer.update(&[true, true, true, false]).len(), 3);
+        assert!(debouncer.update(&[true, true, true, true]).is_empty());
+        assert!(debouncer.ghosting);
+        assert_eq!(debouncer.update(&[false, false, false, false]).len(), 3);
+        assert!(!debouncer.ghosting);
+    }
+}
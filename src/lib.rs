@@ -11,7 +11,7 @@ use pin::{Pin, Pwm, GpioClock, RequiresRoot};
 macro_rules! impl_pins {
     ($($name:ident),+) => (
         $(
-            #[derive(Clone, Copy)]
+            #[derive(Debug, Clone, Copy)]
             pub struct $name;
 
             impl Pin for $name {}
@@ -20,12 +20,15 @@ macro_rules! impl_pins {
 }
 
 macro_rules! impl_pwm {
-    ($($name:ident: $pwm:expr),+) => (
+    ($($name:ident: [$($pin:tt => $channel:ident),+]),+) => (
         $(
             impl Pwm for $name {
                 #[inline]
-                fn pwm_pin() -> PwmPin<$name> {
-                    PwmPin::new($pwm)
+                fn pwm_channel(pin: libc::c_int) -> Option<PwmChannel> {
+                    match pin {
+                        $($pin => Some(PwmChannel::$channel),)+
+                        _ => None
+                    }
                 }
             }
         )+
@@ -33,12 +36,15 @@ macro_rules! impl_pwm {
 }
 
 macro_rules! impl_clock {
-    ($($name:ident: $pwm:expr),+) => (
+    ($($name:ident: [$($pin:tt => $channel:ident),+]),+) => (
         $(
             impl GpioClock for $name {
                 #[inline]
-                fn clock_pin() -> ClockPin<$name> {
-                    ClockPin::new($pwm)
+                fn clock_channel(pin: libc::c_int) -> Option<ClockChannel> {
+                    match pin {
+                        $($pin => Some(ClockChannel::$channel),)+
+                        _ => None
+                    }
                 }
             }
         )+
@@ -54,6 +60,29 @@ macro_rules! require_root {
 }
 
 mod bindings;
+mod clock;
+mod interrupt;
+mod poller;
+mod timing;
+
+pub mod adc;
+pub mod button;
+pub mod counter;
+pub mod encoder;
+pub mod expander;
+pub mod extension;
+pub mod i2c;
+pub mod keypad;
+pub mod motor;
+pub mod pulse;
+pub mod serial;
+pub mod servo;
+pub mod shift;
+pub mod shift_register;
+pub mod soft_pwm;
+pub mod spi;
+pub mod stepper;
+pub mod tone;
 
 pub mod thread {
     use bindings;
@@ -85,10 +114,17 @@ pub mod thread {
 
 pub mod pin {
     use bindings;
+    use clock;
     use libc;
+    use pulse;
+    use timing;
     use self::Value::{Low, High};
 
+    use std::error::Error;
+    use std::fmt;
     use std::marker::PhantomData;
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::time::Duration;
 
     const INPUT: libc::c_int = 0;
     const OUTPUT: libc::c_int = 1;
@@ -96,7 +132,7 @@ pub mod pin {
     const GPIO_CLOCK: libc::c_int = 3;
     //const SOFT_PWM_OUTPUT: libc::c_int = 4;
     //const SOFT_TONE_OUTPUT: libc::c_int = 5;
-    //const PWM_TONE_OUTPUT: libc::c_int = 6;
+    const PWM_TONE_OUTPUT: libc::c_int = 6;
 
     ///This returns the BCM_GPIO pin number of the supplied **wiringPi** pin.
     ///
@@ -116,18 +152,30 @@ pub mod pin {
     }
 
     impl_pins!(WiringPi, Gpio, Phys, Sys);
-    impl_pwm!(WiringPi: 1, Gpio: 18, Phys: 12);
-    impl_clock!(WiringPi: 7, Gpio: 4, Phys: 7);
+    impl_pwm!(
+        WiringPi: [1 => Pwm0, 23 => Pwm1, 24 => Pwm1, 26 => Pwm0],
+        Gpio: [12 => Pwm0, 13 => Pwm1, 18 => Pwm0, 19 => Pwm1],
+        Phys: [12 => Pwm0, 32 => Pwm0, 33 => Pwm1, 35 => Pwm1]
+    );
+    impl_clock!(
+        WiringPi: [7 => Gpclk0, 21 => Gpclk1, 22 => Gpclk2, 28 => Gpclk0, 29 => Gpclk1],
+        Gpio: [4 => Gpclk0, 5 => Gpclk1, 6 => Gpclk2, 20 => Gpclk0, 21 => Gpclk1],
+        Phys: [7 => Gpclk0, 29 => Gpclk1, 31 => Gpclk2, 38 => Gpclk0, 40 => Gpclk1]
+    );
     require_root!(WiringPi, Gpio, Phys);
 
     pub trait Pin {}
 
     pub trait Pwm: RequiresRoot + Sized {
-        fn pwm_pin() -> PwmPin<Self>;
+        ///The PWM channel that `pin` is connected to, if it's capable of
+        ///hardware PWM output.
+        fn pwm_channel(pin: libc::c_int) -> Option<PwmChannel>;
     }
 
     pub trait GpioClock: RequiresRoot + Sized {
-        fn clock_pin() -> ClockPin<Self>;
+        ///The clock generator that `pin` is connected to, if it's capable of
+        ///general purpose clock output.
+        fn clock_channel(pin: libc::c_int) -> Option<ClockChannel>;
     }
 
     pub trait RequiresRoot: Pin {}
@@ -154,12 +202,180 @@ pub mod pin {
         Up
     }
 
-    #[derive(Debug, Clone, Copy)]
+    ///The two channels of the PWM generator. Each channel can be routed to
+    ///two of the header pins, but only one of them can use it at a time.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub enum PwmChannel {
+        ///Available on BCM_GPIO 12 and 18.
+        Pwm0 = 0,
+        ///Available on BCM_GPIO 13 and 19.
+        Pwm1
+    }
+
+    ///The three general purpose clock generators. Each generator can be
+    ///routed to one or two of the header pins, but only one of them can use
+    ///it at a time.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub enum ClockChannel {
+        ///Available on BCM_GPIO 4 and 20.
+        Gpclk0 = 0,
+        ///Available on BCM_GPIO 5 and 21. Some models use it internally, so
+        ///check that it's free before using it.
+        Gpclk1,
+        ///Available on BCM_GPIO 6.
+        Gpclk2
+    }
+
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
     pub enum PwmMode {
         MarkSpace = 0,
         Balanced
     }
 
+    ///The frequency of the PWM clock before the divisor is applied. wiringPi
+    ///compensates for the faster clock on newer models, so it's the same for
+    ///all of them.
+    pub const PWM_BASE_CLOCK: f64 = 19_200_000.0;
+
+    const PWM_MIN_DIVISOR: u32 = 2;
+    const PWM_MAX_DIVISOR: u32 = 4095;
+    const PWM_MIN_RANGE: u32 = 2;
+    const PWM_MAX_RANGE: u32 = i32::MAX as u32;
+
+    ///The PWM settings that wiringPi applies every time a pin is switched to
+    ///PWM output.
+    const PWM_DEFAULT_CONFIG: PwmConfig = PwmConfig {
+        mode: PwmMode::Balanced,
+        divisor: 32,
+        range: 1024,
+    };
+
+    ///Set while a `PwmController` exists.
+    static PWM_CONTROLLER_TAKEN: AtomicBool = AtomicBool::new(false);
+
+    #[derive(Debug, Clone, Copy)]
+    struct PwmConfig {
+        mode: PwmMode,
+        divisor: u32,
+        range: u32,
+    }
+
+    impl PwmConfig {
+        fn apply(&self) {
+            unsafe {
+                bindings::pwmSetMode(self.mode as libc::c_int);
+                bindings::pwmSetClock(self.divisor as libc::c_int);
+                bindings::pwmSetRange(self.range as libc::c_uint);
+            }
+        }
+
+        ///Finds the divisor and range that gets closest to `frequency`,
+        ///while keeping as much resolution as possible.
+        fn for_frequency(frequency: f64) -> Result<PwmConfig, PwmError> {
+            if !frequency.is_finite() || frequency <= 0.0 {
+                return Err(PwmError::FrequencyOutOfRange);
+            }
+
+            let ticks = PWM_BASE_CLOCK / frequency;
+            let divisor = (ticks / PWM_MAX_RANGE as f64).ceil().max(PWM_MIN_DIVISOR as f64);
+
+            if divisor > PWM_MAX_DIVISOR as f64 {
+                return Err(PwmError::FrequencyOutOfRange);
+            }
+
+            let range = (ticks / divisor).round();
+
+            if range < PWM_MIN_RANGE as f64 || range > PWM_MAX_RANGE as f64 {
+                return Err(PwmError::FrequencyOutOfRange);
+            }
+
+            Ok(PwmConfig {
+                mode: PwmMode::MarkSpace,
+                divisor: divisor as u32,
+                range: range as u32,
+            })
+        }
+
+        fn frequency(&self) -> f64 {
+            PWM_BASE_CLOCK / (self.divisor as f64 * self.range as f64)
+        }
+
+        fn tick_nanos(&self) -> f64 {
+            self.divisor as f64 * 1_000_000_000.0 / PWM_BASE_CLOCK
+        }
+    }
+
+    ///The ways configuring PWM output can fail.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub enum PwmError {
+        ///The frequency can't be produced by the PWM generator.
+        FrequencyOutOfRange,
+        ///The duty cycle is outside the interval [0, 1].
+        DutyOutOfRange,
+        ///The pulse is longer than the PWM period.
+        PulseTooLong,
+        ///The PWM range is 0 or too large.
+        InvalidRange,
+        ///The clock divisor is outside [2, 4095].
+        InvalidDivisor,
+        ///The pin can't output hardware PWM.
+        NotPwmCapable,
+        ///Another pin is already using the same PWM channel.
+        ChannelInUse,
+        ///The PWM generator is reserved by a `TonePin`, or can't be reserved
+        ///because the other channel is in use.
+        GeneratorInUse,
+    }
+
+    impl fmt::Display for PwmError {
+        fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            let message = match *self {
+                PwmError::FrequencyOutOfRange => "the frequency can't be produced by the PWM generator",
+                PwmError::DutyOutOfRange => "the duty cycle has to be between 0 and 1",
+                PwmError::PulseTooLong => "the pulse is longer than the PWM period",
+                PwmError::InvalidRange => "the PWM range is 0 or too large",
+                PwmError::InvalidDivisor => "the PWM clock divisor has to be between 2 and 4095",
+                PwmError::NotPwmCapable => "the pin can't output hardware PWM",
+                PwmError::ChannelInUse => "the PWM channel is already in use by another pin",
+                PwmError::GeneratorInUse => "the PWM generator is needed by another pin",
+            };
+
+            f.write_str(message)
+        }
+    }
+
+    impl Error for PwmError {}
+
+    ///An output with a PWM signal, which drivers like `servo::Servo` can be
+    ///built on regardless of how the signal is generated.
+    pub trait PwmOutput {
+        ///The length of a PWM period.
+        fn period(&self) -> Duration;
+
+        ///Sets the duty cycle as a fraction in the interval [0, 1].
+        fn set_duty(&self, duty: f64) -> Result<(), PwmError>;
+
+        ///Sets the length of the high pulse in each period.
+        fn set_pulse_width(&self, width: Duration) -> Result<(), PwmError>;
+    }
+
+    ///A digital output, which drivers and application code can be written
+    ///against regardless of whether it's a header pin or an output on an
+    ///expansion chip.
+    pub trait DigitalOutput {
+        ///Sets the output to `High` or `Low`.
+        fn digital_write(&self, value: Value);
+    }
+
+    ///A digital input, which drivers and application code can be written
+    ///against regardless of whether it's a header pin or an input on an
+    ///expansion chip.
+    pub trait DigitalInput {
+        ///Reads the input as `High` or `Low`.
+        fn digital_read(&self) -> Value;
+    }
+
+    #[derive(Debug)]
     pub struct InputPin<Pin>(libc::c_int, PhantomData<Pin>);
 
     impl<P: Pin> InputPin<P> {
@@ -195,7 +411,8 @@ pub mod pin {
         ///This returns the value read on the supplied analog input pin. You
         ///will need to register additional analog modules to enable this
         ///function for devices such as the Gertboard, quick2Wire analog
-        ///board, etc.
+        ///board, etc. The converters in the `adc` module register themselves
+        ///as extension nodes for this.
         pub fn analog_read(&self) -> u16 {
             unsafe {
                 bindings::analogRead(self.number()) as u16
@@ -221,8 +438,9 @@ pub mod pin {
         /// See https://github.com/Ogeon/rust-wiringpi/pull/28 for
         /// ideas on how to work around these limitations if you find them too constraining.
         ///
-        /// ```
-        /// use wiringpi;
+        /// ```no_run
+        /// use wiringpi::pin::Edge;
+        /// use std::{thread, time};
         ///
         /// extern "C" fn change_state() {
         ///   println!("Look ma, I'm being called from an another thread");
@@ -230,11 +448,11 @@ pub mod pin {
         ///
         /// fn main() {
         ///    let pi = wiringpi::setup();
-        ///    let pin = pi.output_pin(0);
+        ///    let pin = pi.input_pin(0);
         ///
         ///    pin.register_isr(Edge::Falling, Some(change_state));
         ///
-        ///    thread::sleep(60000);
+        ///    thread::sleep(time::Duration::from_millis(60000));
         /// }
         ///
         /// ```
@@ -245,6 +463,28 @@ pub mod pin {
                 bindings::wiringPiISR(self.number(), edge as i32, f);
             }
         }
+
+        ///Measures the length of the next pulse at `level` on the pin, like
+        ///`pulseIn` on the Arduino.
+        ///
+        ///It waits for any ongoing pulse to end, then for the pin to change
+        ///to `level` and finally for it to change back. `None` is returned if
+        ///this takes longer than `timeout` in total. The pin is polled in a
+        ///busy loop, so the accuracy depends on how much CPU time the calling
+        ///thread gets.
+        pub fn pulse_in(&self, level: Value, timeout: Duration) -> Option<Duration> {
+            let timeout = timing::duration_to_micros(timeout);
+
+            pulse::measure_pulse(level, timeout, || self.digital_read(), timing::micros)
+                .map(|width| Duration::from_micros(width as u64))
+        }
+    }
+
+
+    impl<P: Pin> DigitalInput for InputPin<P> {
+        fn digital_read(&self) -> Value {
+            InputPin::digital_read(self)
+        }
     }
 
     impl<P: Pin + RequiresRoot> InputPin<P> {
@@ -268,60 +508,140 @@ pub mod pin {
             let InputPin(number, _) = self;
             SoftPwmPin::new(number)
         }
+
+        pub fn into_soft_tone(self) -> SoftTonePin<P> {
+            let InputPin(number, _) = self;
+            SoftTonePin::new(number)
+        }
     }
 
     impl<P: Pin + Pwm> InputPin<P> {
-        pub fn into_pwm(self) -> PwmPin<P> {
-            let InputPin(number, _) = self;
-            PwmPin::new(number)
+        ///Switches the pin to hardware PWM output, or returns it together
+        ///with the error if that isn't possible.
+        pub fn into_pwm<'a>(self, controller: &'a PwmController<P>) -> Result<PwmPin<'a, P>, (Self, PwmError)> {
+            PwmPin::new(self.number(), controller).map_err(|error| (self, error))
         }
     }
 
     impl<P: Pin + GpioClock> InputPin<P> {
-        pub fn into_clock(self) -> ClockPin<P> {
-            let InputPin(number, _) = self;
-            ClockPin::new(number)
+        ///Switches the pin to clock output, or returns it together with the
+        ///error if that isn't possible.
+        pub fn into_clock(self) -> Result<ClockPin<P>, (Self, ClockError)> {
+            ClockPin::new(self.number()).map_err(|error| (self, error))
         }
     }
 
     /// A pin with software controlled PWM output.
     ///
-    /// Due to limitations of the chip only one pin is able to do
-    /// hardware-controlled PWM output. The `SoftPwmPin`s on the
+    /// Due to limitations of the chip only two pins at a time are able
+    /// to do hardware-controlled PWM output. The `SoftPwmPin`s on the
     /// other hand allow for all GPIOs to output PWM signals.
     ///
-    /// The pulse width of the signal will be 100μs with a value range
-    /// of [0,100] \(where `0` is a constant low and `100` is a
-    /// constant high) resulting in a frequenzy of 100 Hz.
+    /// Each step of the value range is a 100μs pulse. The default range
+    /// is [0,100] \(where `0` is a constant low and `100` is a
+    /// constant high) resulting in a frequenzy of 100 Hz. A smaller
+    /// range gives a higher frequency, at the cost of resolution. See
+    /// `soft_pwm::NativeSoftPwm` for shorter pulses.
     ///
     /// **Important**: In order to use software PWM pins *wiringPi*
     /// has to be setup in GPIO mode via `setup_gpio()`.
-    pub struct SoftPwmPin<Pin>(libc::c_int, PhantomData<Pin>);
+    #[derive(Debug)]
+    pub struct SoftPwmPin<Pin>(libc::c_int, u32, PhantomData<Pin>);
+
+    ///The length of each step of a `SoftPwmPin`'s range.
+    const SOFT_PWM_STEP_MICROS: u64 = 100;
 
     impl<P: Pin + RequiresRoot> SoftPwmPin<P> {
         /// Configures the given `pin` to output a software controlled PWM
-        /// signal.
+        /// signal, with the range [0,100].
         pub fn new(pin: libc::c_int) -> SoftPwmPin<P> {
             unsafe {
                 bindings::softPwmCreate(pin, 0, 100);
             }
 
-            SoftPwmPin(pin, PhantomData)
+            SoftPwmPin(pin, 100, PhantomData)
+        }
+
+        /// Configures the given `pin` to output a software controlled PWM
+        /// signal, with the range [0,`range`] and `initial` as the
+        /// starting value.
+        pub fn with_range(pin: libc::c_int, range: u32, initial: u32) -> Result<SoftPwmPin<P>, PwmError> {
+            if range == 0 || range > i32::MAX as u32 {
+                return Err(PwmError::InvalidRange);
+            }
+
+            if initial > range {
+                return Err(PwmError::DutyOutOfRange);
+            }
+
+            unsafe {
+                bindings::softPwmCreate(pin, initial as libc::c_int, range as libc::c_int);
+            }
+
+            Ok(SoftPwmPin(pin, range, PhantomData))
         }
 
         #[inline]
         pub fn number(&self) -> libc::c_int {
-            let &SoftPwmPin(number, _) = self;
+            let &SoftPwmPin(number, _, _) = self;
             number
         }
 
+        /// The upper limit of the value range.
+        #[inline]
+        pub fn range(&self) -> u32 {
+            let &SoftPwmPin(_, range, _) = self;
+            range
+        }
+
+        /// The length of a PWM period, which is 100μs per step of the
+        /// range.
+        pub fn period(&self) -> Duration {
+            Duration::from_micros(self.range() as u64 * SOFT_PWM_STEP_MICROS)
+        }
+
+        /// The PWM frequency in Hz.
+        pub fn frequency(&self) -> f64 {
+            1_000_000.0 / (self.range() as u64 * SOFT_PWM_STEP_MICROS) as f64
+        }
+
         /// Sets the duty cycle.
         ///
-        /// `value` has to be in the interval [0,100].
+        /// `value` is limited to the interval [0,`range`].
         pub fn pwm_write(&self, value: libc::c_int) {
             unsafe {
-                bindings::softPwmWrite(self.number(), value);
+                bindings::softPwmWrite(self.number(), self.clamp(value));
+            }
+        }
+
+        fn clamp(&self, value: libc::c_int) -> libc::c_int {
+            value.max(0).min(self.range() as libc::c_int)
+        }
+
+        /// Sets the duty cycle as a fraction in the interval [0, 1],
+        /// where `0` is a constant low and `1` is a constant high.
+        pub fn set_duty(&self, duty: f64) -> Result<(), PwmError> {
+            if !(0.0..=1.0).contains(&duty) {
+                return Err(PwmError::DutyOutOfRange);
+            }
+
+            self.pwm_write((duty * self.range() as f64).round() as libc::c_int);
+            Ok(())
+        }
+
+        /// Sets the length of the high pulse in each period, rounded to
+        /// the nearest 100μs step.
+        pub fn set_pulse_width(&self, width: Duration) -> Result<(), PwmError> {
+            let steps = (width.as_secs() as f64 * 1_000_000.0 + width.subsec_nanos() as f64 / 1000.0)
+                / SOFT_PWM_STEP_MICROS as f64;
+            let steps = steps.round();
+
+            if steps > self.range() as f64 {
+                return Err(PwmError::PulseTooLong);
             }
+
+            self.pwm_write(steps as libc::c_int);
+            Ok(())
         }
 
         /// Stops the software handling of this pin.
@@ -335,35 +655,167 @@ pub mod pin {
         }
 
         pub fn into_input(self) -> InputPin<P> {
-            let SoftPwmPin(number, _) = self;
+            let SoftPwmPin(number, _, _) = self;
             self.pwm_stop();
             InputPin::new(number)
         }
 
         pub fn into_output(self) -> OutputPin<P> {
-            let SoftPwmPin(number, _) = self;
+            let SoftPwmPin(number, _, _) = self;
             self.pwm_stop();
             OutputPin::new(number)
         }
 
+        pub fn into_soft_tone(self) -> SoftTonePin<P> {
+            let SoftPwmPin(number, _, _) = self;
+            self.pwm_stop();
+            SoftTonePin::new(number)
+        }
+
+    }
+
+    impl<P: Pin + RequiresRoot> PwmOutput for SoftPwmPin<P> {
+        fn period(&self) -> Duration {
+            SoftPwmPin::period(self)
+        }
+
+        fn set_duty(&self, duty: f64) -> Result<(), PwmError> {
+            SoftPwmPin::set_duty(self, duty)
+        }
+
+        fn set_pulse_width(&self, width: Duration) -> Result<(), PwmError> {
+            SoftPwmPin::set_pulse_width(self, width)
+        }
     }
 
     impl<P: Pin + Pwm> SoftPwmPin<P> {
-        pub fn into_pwm(self) -> PwmPin<P> {
-            let SoftPwmPin(number, _) = self;
+        ///Switches the pin to hardware PWM output, or returns it together
+        ///with the error if that isn't possible.
+        pub fn into_pwm<'a>(self, controller: &'a PwmController<P>) -> Result<PwmPin<'a, P>, (Self, PwmError)> {
+            let channel = match PwmPin::claim(self.number(), controller) {
+                Ok(channel) => channel,
+                Err(error) => return Err((self, error)),
+            };
+
+            let SoftPwmPin(number, _, _) = self;
             self.pwm_stop();
-            PwmPin::new(number)
+            Ok(PwmPin::start(number, channel, controller))
         }
     }
 
     impl<P: Pin + GpioClock> SoftPwmPin<P> {
-        pub fn into_clock(self) -> ClockPin<P> {
-            let SoftPwmPin(number, _) = self;
+        ///Switches the pin to clock output, or returns it together with the
+        ///error if that isn't possible.
+        pub fn into_clock(self) -> Result<ClockPin<P>, (Self, ClockError)> {
+            let channel = match ClockPin::<P>::claim(self.number()) {
+                Ok(channel) => channel,
+                Err(error) => return Err((self, error)),
+            };
+
+            let SoftPwmPin(number, _, _) = self;
             self.pwm_stop();
-            ClockPin::new(number)
+            Ok(ClockPin::start(number, channel))
+        }
+    }
+
+    /// A pin with software controlled tone output.
+    ///
+    /// The tone is a square wave that is generated by a background
+    /// thread, which makes it possible to drive a buzzer from any
+    /// GPIO. The frequency is limited to 5000 Hz.
+    ///
+    /// **Important**: In order to use software tone pins *wiringPi*
+    /// has to be setup in GPIO mode via `setup_gpio()`.
+    #[derive(Debug)]
+    pub struct SoftTonePin<Pin>(libc::c_int, PhantomData<Pin>);
+
+    impl<P: Pin + RequiresRoot> SoftTonePin<P> {
+        /// Configures the given `pin` to output a software controlled
+        /// tone.
+        pub fn new(pin: libc::c_int) -> SoftTonePin<P> {
+            unsafe {
+                bindings::softToneCreate(pin);
+            }
+
+            SoftTonePin(pin, PhantomData)
+        }
+
+        #[inline]
+        pub fn number(&self) -> libc::c_int {
+            let &SoftTonePin(number, _) = self;
+            number
+        }
+
+        /// Plays a tone at `frequency` Hz. A frequency of 0 silences
+        /// the pin.
+        pub fn write(&self, frequency: u32) {
+            let frequency = frequency.min(i32::MAX as u32);
+
+            unsafe {
+                bindings::softToneWrite(self.number(), frequency as libc::c_int);
+            }
+        }
+
+        /// Stops the software handling of this pin.
+        ///
+        /// _Note_: In order to play tones on this pin again it will
+        /// need to be recreated using `new()`.
+        pub fn stop(self) {
+            unsafe {
+                bindings::softToneStop(self.number());
+            }
+        }
+
+        pub fn into_input(self) -> InputPin<P> {
+            let SoftTonePin(number, _) = self;
+            self.stop();
+            InputPin::new(number)
+        }
+
+        pub fn into_output(self) -> OutputPin<P> {
+            let SoftTonePin(number, _) = self;
+            self.stop();
+            OutputPin::new(number)
+        }
+
+        pub fn into_soft_pwm(self) -> SoftPwmPin<P> {
+            let SoftTonePin(number, _) = self;
+            self.stop();
+            SoftPwmPin::new(number)
+        }
+    }
+
+    impl<P: Pin + Pwm> SoftTonePin<P> {
+        ///Switches the pin to hardware PWM output, or returns it together
+        ///with the error if that isn't possible.
+        pub fn into_pwm<'a>(self, controller: &'a PwmController<P>) -> Result<PwmPin<'a, P>, (Self, PwmError)> {
+            let channel = match PwmPin::claim(self.number(), controller) {
+                Ok(channel) => channel,
+                Err(error) => return Err((self, error)),
+            };
+
+            let SoftTonePin(number, _) = self;
+            self.stop();
+            Ok(PwmPin::start(number, channel, controller))
         }
     }
 
+    impl<P: Pin + GpioClock> SoftTonePin<P> {
+        ///Switches the pin to clock output, or returns it together with the
+        ///error if that isn't possible.
+        pub fn into_clock(self) -> Result<ClockPin<P>, (Self, ClockError)> {
+            let channel = match ClockPin::<P>::claim(self.number()) {
+                Ok(channel) => channel,
+                Err(error) => return Err((self, error)),
+            };
+
+            let SoftTonePin(number, _) = self;
+            self.stop();
+            Ok(ClockPin::start(number, channel))
+        }
+    }
+
+    #[derive(Debug)]
     pub struct OutputPin<Pin>(libc::c_int, PhantomData<Pin>);
 
     impl<P: Pin> OutputPin<P> {
@@ -399,11 +851,22 @@ pub mod pin {
 
     }
 
+    impl<P: Pin> DigitalOutput for OutputPin<P> {
+        fn digital_write(&self, value: Value) {
+            OutputPin::digital_write(self, value);
+        }
+    }
+
     impl<P: Pin + RequiresRoot> OutputPin<P> {
         pub fn into_soft_pwm(self) -> SoftPwmPin<P> {
             let OutputPin(number, _) = self;
             SoftPwmPin::new(number)
         }
+
+        pub fn into_soft_tone(self) -> SoftTonePin<P> {
+            let OutputPin(number, _) = self;
+            SoftTonePin::new(number)
+        }
     }
 
     impl<P: Pin + RequiresRoot> OutputPin<P> {
@@ -414,125 +877,823 @@ pub mod pin {
     }
 
     impl<P: Pin + Pwm> OutputPin<P> {
-        pub fn into_pwm(self) -> PwmPin<P> {
-            let OutputPin(number, _) = self;
-            PwmPin::new(number)
+        ///Switches the pin to hardware PWM output, or returns it together
+        ///with the error if that isn't possible.
+        pub fn into_pwm<'a>(self, controller: &'a PwmController<P>) -> Result<PwmPin<'a, P>, (Self, PwmError)> {
+            PwmPin::new(self.number(), controller).map_err(|error| (self, error))
         }
     }
 
     impl<P: Pin + GpioClock> OutputPin<P> {
-        pub fn into_clock(self) -> ClockPin<P> {
-            let OutputPin(number, _) = self;
-            ClockPin::new(number)
+        ///Switches the pin to clock output, or returns it together with the
+        ///error if that isn't possible.
+        pub fn into_clock(self) -> Result<ClockPin<P>, (Self, ClockError)> {
+            ClockPin::new(self.number()).map_err(|error| (self, error))
+        }
+    }
+
+    ///Owns the PWM generator settings that are shared by all PWM pins.
+    ///
+    ///The mode, clock divisor and range of the PWM generator affect every
+    ///hardware PWM pin at once. The controller holds these settings and the
+    ///`PwmPin`s borrow it, so the settings can't be changed while any pin
+    ///depends on them.
+    ///
+    ///There can only be one controller at a time. It's obtained from
+    ///`WiringPi::pwm_controller()`.
+    ///
+    ///The controller also keeps track of which of the two PWM channels are in
+    ///use, since the pins that share a channel can't be used at the same
+    ///time.
+    ///
+    ///```no_run
+    ///let pi = wiringpi::setup_gpio();
+    ///let mut pwm = pi.pwm_controller().unwrap();
+    ///pwm.set_frequency(1000.0).unwrap();
+    ///
+    ///let left = pwm.pwm_pin(12).unwrap();
+    ///let right = pwm.pwm_pin(13).unwrap();
+    ///left.set_duty(0.25).unwrap();
+    ///right.set_duty(0.75).unwrap();
+    ///```
+    #[derive(Debug)]
+    pub struct PwmController<Pin> {
+        config: PwmConfig,
+        channels: [AtomicBool; 2],
+        reserved: AtomicBool,
+        _pin: PhantomData<Pin>,
+    }
+
+    impl<P: Pin + Pwm> PwmController<P> {
+        ///Takes control over the PWM generator, or returns `None` if there
+        ///is already a controller.
+        pub fn new() -> Option<PwmController<P>> {
+            if PWM_CONTROLLER_TAKEN.swap(true, Ordering::SeqCst) {
+                return None;
+            }
+
+            Some(PwmController {
+                config: PWM_DEFAULT_CONFIG,
+                channels: [AtomicBool::new(false), AtomicBool::new(false)],
+                reserved: AtomicBool::new(false),
+                _pin: PhantomData,
+            })
+        }
+
+        ///Sets up `pin` for hardware PWM output.
+        ///
+        ///This fails if the pin can't output hardware PWM, or if its channel
+        ///is already used by another pin.
+        pub fn pwm_pin<'a>(&'a self, pin: u16) -> Result<PwmPin<'a, P>, PwmError> {
+            PwmPin::new(pin as libc::c_int, self)
+        }
+
+        ///Checks if a pin is using `channel`.
+        pub fn is_channel_in_use(&self, channel: PwmChannel) -> bool {
+            self.channels[channel as usize].load(Ordering::SeqCst)
+        }
+
+        ///The PWM generator can run in 2 modes – "balanced" and "mark:space".
+        ///
+        ///The mark:space mode is traditional, however the default mode in the
+        ///Pi is "balanced". You can switch modes by supplying the parameter:
+        ///`Balanced` or `MarkSpace`.
+        pub fn set_mode(&mut self, mode: PwmMode) {
+            unsafe {
+                bindings::pwmSetMode(mode as libc::c_int);
+            }
+
+            self.config.mode = mode;
+        }
+
+        ///This sets the range register in the PWM generator. The default is 1024.
+        ///
+        ///The range can't be 0 or larger than `i32::MAX`.
+        pub fn set_range(&mut self, value: u32) -> Result<(), PwmError> {
+            if value == 0 || value > PWM_MAX_RANGE {
+                return Err(PwmError::InvalidRange);
+            }
+
+            unsafe {
+                bindings::pwmSetRange(value as libc::c_uint);
+            }
+
+            self.config.range = value;
+            Ok(())
+        }
+
+        ///This sets the divisor for the PWM clock, which has to be between 2
+        ///and 4095.
+        pub fn set_clock(&mut self, value: u16) -> Result<(), PwmError> {
+            if (value as u32) < PWM_MIN_DIVISOR || value as u32 > PWM_MAX_DIVISOR {
+                return Err(PwmError::InvalidDivisor);
+            }
+
+            unsafe {
+                bindings::pwmSetClock(value as libc::c_int);
+            }
+
+            self.config.divisor = value as u32;
+            Ok(())
+        }
+
+        ///Sets the PWM frequency in Hz, by choosing a clock divisor and range
+        ///that gets as close as possible while keeping the highest
+        ///resolution. The PWM generator is switched to mark:space mode, since
+        ///the frequency isn't fixed in balanced mode.
+        ///
+        ///Returns the frequency that was actually achieved. The duty cycle
+        ///depends on the range, so it has to be set again afterwards.
+        pub fn set_frequency(&mut self, frequency: f64) -> Result<f64, PwmError> {
+            let config = PwmConfig::for_frequency(frequency)?;
+            config.apply();
+            self.config = config;
+
+            Ok(config.frequency())
+        }
+
+        ///The current PWM mode.
+        pub fn mode(&self) -> PwmMode {
+            self.config.mode
+        }
+
+        ///The frequency in Hz that the current clock divisor and range
+        ///results in. This is only the actual output frequency in mark:space
+        ///mode.
+        pub fn frequency(&self) -> f64 {
+            self.config.frequency()
+        }
+
+        ///The length of a PWM period in mark:space mode.
+        pub fn period(&self) -> Duration {
+            Duration::from_nanos((self.config.tick_nanos() * self.config.range as f64).round() as u64)
+        }
+
+        ///The number of steps between a constant low and a constant high
+        ///output, which is the current range.
+        pub fn resolution(&self) -> u32 {
+            self.config.range
+        }
+    }
+
+    impl<P> Drop for PwmController<P> {
+        fn drop(&mut self) {
+            PWM_CONTROLLER_TAKEN.store(false, Ordering::SeqCst);
         }
     }
 
     ///To understand more about the PWM system, you’ll need to read the Broadcom ARM peripherals manual.
-    pub struct PwmPin<Pin>(libc::c_int, PhantomData<Pin>);
+    ///
+    ///The settings that are shared between all PWM pins are managed by the
+    ///`PwmController` that the pin borrows.
+    #[derive(Debug)]
+    pub struct PwmPin<'a, Pin: 'a>(libc::c_int, PwmChannel, &'a PwmController<Pin>);
+
+    impl<'a, P: Pin + Pwm> PwmPin<'a, P> {
+        ///Sets up `pin` for hardware PWM output, claiming its PWM channel.
+        ///
+        ///This fails if the pin can't output hardware PWM, or if its channel
+        ///is already used by another pin.
+        pub fn new(pin: libc::c_int, controller: &'a PwmController<P>) -> Result<PwmPin<'a, P>, PwmError> {
+            let channel = PwmPin::claim(pin, controller)?;
+            Ok(PwmPin::start(pin, channel, controller))
+        }
+
+        ///Checks that `pin` can output hardware PWM and claims its channel.
+        fn claim(pin: libc::c_int, controller: &PwmController<P>) -> Result<PwmChannel, PwmError> {
+            let channel = P::pwm_channel(pin).ok_or(PwmError::NotPwmCapable)?;
+
+            if controller.reserved.load(Ordering::SeqCst) {
+                return Err(PwmError::GeneratorInUse);
+            }
+
+            if controller.channels[channel as usize].swap(true, Ordering::SeqCst) {
+                return Err(PwmError::ChannelInUse);
+            }
+
+            Ok(channel)
+        }
 
-    impl<P: Pin + Pwm> PwmPin<P> {
-        pub fn new(pin: libc::c_int) -> PwmPin<P> {
+        ///Switches `pin` to PWM output on its claimed `channel`.
+        fn start(pin: libc::c_int, channel: PwmChannel, controller: &'a PwmController<P>) -> PwmPin<'a, P> {
             unsafe {
                 bindings::pinMode(pin, PWM_OUTPUT);
             }
 
-            PwmPin(pin, PhantomData)
+            //Switching a pin to PWM output resets the PWM generator
+            controller.config.apply();
+
+            PwmPin(pin, channel, controller)
         }
 
         #[inline]
         pub fn number(&self) -> libc::c_int {
-            let &PwmPin(number, _) = self;
+            let &PwmPin(number, _, _) = self;
             number
         }
 
+        ///The controller that holds the shared PWM settings.
+        #[inline]
+        pub fn controller(&self) -> &'a PwmController<P> {
+            let &PwmPin(_, _, controller) = self;
+            controller
+        }
+
+        ///The PWM channel that the pin is using.
+        #[inline]
+        pub fn channel(&self) -> PwmChannel {
+            let &PwmPin(_, channel, _) = self;
+            channel
+        }
+
         pub fn into_input(self) -> InputPin<P> {
-            let PwmPin(number, _) = self;
+            let PwmPin(number, _, _) = self;
             InputPin::new(number)
         }
 
         pub fn into_output(self) -> OutputPin<P> {
-            let PwmPin(number, _) = self;
+            let PwmPin(number, _, _) = self;
             OutputPin::new(number)
         }
 
         pub fn into_soft_pwm(self) -> SoftPwmPin<P> {
-            let PwmPin(number, _) = self;
+            let PwmPin(number, _, _) = self;
             SoftPwmPin::new(number)
         }
 
+        pub fn into_soft_tone(self) -> SoftTonePin<P> {
+            let PwmPin(number, _, _) = self;
+            SoftTonePin::new(number)
+        }
+
+        ///Switches the pin to tone output.
+        ///
+        ///Tones change the range of the whole PWM generator, so this fails
+        ///if the other PWM channel is in use, and no other PWM pins can be
+        ///set up while the `TonePin` exists. The pin is returned together
+        ///with the error if it fails.
+        pub fn into_tone(self) -> Result<TonePin<'a, P>, (Self, PwmError)> {
+            let other = match self.channel() {
+                PwmChannel::Pwm0 => PwmChannel::Pwm1,
+                PwmChannel::Pwm1 => PwmChannel::Pwm0,
+            };
+
+            let controller = self.controller();
+
+            if controller.is_channel_in_use(other) || controller.reserved.swap(true, Ordering::SeqCst) {
+                return Err((self, PwmError::GeneratorInUse));
+            }
+
+            unsafe {
+                bindings::pinMode(self.number(), PWM_TONE_OUTPUT);
+            }
+
+            let reservation = GeneratorReservation(self.number(), controller);
+            Ok(TonePin(self, reservation))
+        }
+
         ///Writes the value to the PWM register for the given pin.
         ///
-        ///The value must be between 0 and 1024.
-        pub fn write(&self, value: u16) {
+        ///The value must be between 0 and the range, which is 1024 by default.
+        ///Larger values return `Err(PwmError::DutyOutOfRange)`.
+        pub fn write(&self, value: u32) -> Result<(), PwmError> {
+            if value > self.controller().config.range {
+                return Err(PwmError::DutyOutOfRange);
+            }
+
             unsafe {
                 bindings::pwmWrite(self.number(), value as libc::c_int);
             }
+
+            Ok(())
         }
 
-        ///The PWM generator can run in 2 modes – "balanced" and "mark:space".
+        ///The frequency in Hz that the current clock divisor and range
+        ///results in. See `PwmController::frequency`.
+        pub fn frequency(&self) -> f64 {
+            self.controller().frequency()
+        }
+
+        ///The length of a PWM period in mark:space mode.
+        pub fn period(&self) -> Duration {
+            self.controller().period()
+        }
+
+        ///The number of steps between a constant low and a constant high
+        ///output, which is the current range.
+        pub fn resolution(&self) -> u32 {
+            self.controller().resolution()
+        }
+
+        ///Sets the duty cycle as a fraction in the interval [0, 1], where `0`
+        ///is a constant low and `1` is a constant high.
+        pub fn set_duty(&self, duty: f64) -> Result<(), PwmError> {
+            if !(0.0..=1.0).contains(&duty) {
+                return Err(PwmError::DutyOutOfRange);
+            }
+
+            let value = (duty * self.controller().config.range as f64).round();
+
+            unsafe {
+                bindings::pwmWrite(self.number(), value as libc::c_int);
+            }
+
+            Ok(())
+        }
+
+        ///Sets the length of the high pulse in each period. This requires
+        ///mark:space mode and is rounded to the nearest PWM clock tick.
+        pub fn set_pulse_width(&self, width: Duration) -> Result<(), PwmError> {
+            let config = self.controller().config;
+            let nanos = width.as_secs() as f64 * 1_000_000_000.0 + width.subsec_nanos() as f64;
+            let value = (nanos / config.tick_nanos()).round();
+
+            if value > config.range as f64 {
+                return Err(PwmError::PulseTooLong);
+            }
+
+            unsafe {
+                bindings::pwmWrite(self.number(), value as libc::c_int);
+            }
+
+            Ok(())
+        }
+    }
+
+    impl<'a, P: Pin + Pwm> PwmOutput for PwmPin<'a, P> {
+        fn period(&self) -> Duration {
+            PwmPin::period(self)
+        }
+
+        fn set_duty(&self, duty: f64) -> Result<(), PwmError> {
+            PwmPin::set_duty(self, duty)
+        }
+
+        fn set_pulse_width(&self, width: Duration) -> Result<(), PwmError> {
+            PwmPin::set_pulse_width(self, width)
+        }
+    }
+
+    impl<'a, P> Drop for PwmPin<'a, P> {
+        fn drop(&mut self) {
+            let &mut PwmPin(_, channel, controller) = self;
+            controller.channels[channel as usize].store(false, Ordering::SeqCst);
+        }
+    }
+
+    ///A hardware PWM pin that outputs square wave tones, for example to drive
+    ///a piezo buzzer.
+    ///
+    ///The tones are produced by changing the range of the PWM generator
+    ///with a fixed 600 kHz clock, so the `TonePin` reserves the whole
+    ///generator. The previous settings of the `PwmController` are restored
+    ///when it's dropped or turned back into a `PwmPin`.
+    ///
+    ///```no_run
+    ///use std::{thread, time};
+    ///
+    ///let pi = wiringpi::setup_gpio();
+    ///let pwm = pi.pwm_controller().unwrap();
+    ///let buzzer = pwm.pwm_pin(18).unwrap().into_tone().unwrap();
+    ///
+    ///buzzer.play(440);
+    ///thread::sleep(time::Duration::from_millis(500));
+    ///buzzer.stop();
+    ///```
+    #[derive(Debug)]
+    pub struct TonePin<'a, Pin: 'a>(PwmPin<'a, Pin>, GeneratorReservation<'a, Pin>);
+
+    ///The highest frequency a `TonePin` can play, where the range is 2.
+    pub const MAX_TONE_FREQUENCY: u32 = 300_000;
+
+    ///The PWM clock that wiringPi sets up for tone output, which is the base
+    ///clock divided by 32.
+    const TONE_CLOCK: u32 = 600_000;
+
+    ///The range that gets closest to `frequency` at the tone clock, with
+    ///frequencies limited to `MAX_TONE_FREQUENCY`.
+    fn tone_range(frequency: u32) -> u32 {
+        let frequency = frequency.min(MAX_TONE_FREQUENCY);
+        (TONE_CLOCK + frequency / 2) / frequency
+    }
+
+    impl<'a, P: Pin + Pwm> TonePin<'a, P> {
+        #[inline]
+        pub fn number(&self) -> libc::c_int {
+            let &TonePin(_, GeneratorReservation(number, _)) = self;
+            number
+        }
+
+        ///Starts playing a tone at `frequency` Hz. A frequency of 0 is the
+        ///same as calling `stop()` and frequencies above
+        ///`MAX_TONE_FREQUENCY` are limited to it.
         ///
-        ///The mark:space mode is traditional, however the default mode in the
-        ///Pi is "balanced". You can switch modes by supplying the parameter:
-        ///`Balanced` or `MarkSpace`.
-        pub fn set_mode(&self, mode: PwmMode) {
+        ///The range and duty cycle are set here instead of with
+        ///`pwmToneWrite`, which writes half the frequency as the duty cycle
+        ///and leaves the output high above about 1.1 kHz.
+        pub fn play(&self, frequency: u32) {
+            if frequency == 0 {
+                return self.stop();
+            }
+
+            let range = tone_range(frequency);
+
             unsafe {
-                bindings::pwmSetMode(mode as libc::c_int);
+                bindings::pwmSetRange(range as libc::c_uint);
+                bindings::pwmWrite(self.number(), (range / 2) as libc::c_int);
             }
         }
 
-        ///This sets the range register in the PWM generator. The default is 1024.
-        pub fn set_range(&self, value: u16) {
+        ///Stops the current tone.
+        pub fn stop(&self) {
             unsafe {
-                bindings::pwmSetRange(value as libc::c_uint);
+                bindings::pwmWrite(self.number(), 0);
             }
         }
 
-        ///This sets the divisor for the PWM clock.
-        pub fn set_clock(&self, value: u16) {
+        ///Stops the tone output and returns the underlying PWM pin.
+        pub fn into_pwm(self) -> PwmPin<'a, P> {
+            let TonePin(pin, _) = self;
+            pin
+        }
+    }
+
+    ///Keeps the PWM generator reserved for a `TonePin`.
+    #[derive(Debug)]
+    struct GeneratorReservation<'a, Pin: 'a>(libc::c_int, &'a PwmController<Pin>);
+
+    impl<'a, P> Drop for GeneratorReservation<'a, P> {
+        fn drop(&mut self) {
+            let &mut GeneratorReservation(number, controller) = self;
+
             unsafe {
-                bindings::pwmSetClock(value as libc::c_int);
+                bindings::pwmWrite(number, 0);
             }
+
+            controller.config.apply();
+            controller.reserved.store(false, Ordering::SeqCst);
+        }
+    }
+
+    ///The sources that the clock generators can divide down from.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub enum ClockSource {
+        ///The crystal oscillator, at 19.2 MHz, or 54 MHz on the Pi 4.
+        Oscillator = 1,
+        ///PLLC, at 1 GHz. It follows the core clock, so the output changes
+        ///with frequency scaling unless the core clock is fixed.
+        PllC = 5,
+        ///PLLD, at 500 MHz, or 750 MHz on the Pi 4.
+        PllD = 6,
+        ///The HDMI auxiliary clock, at 216 MHz. It's only running while HDMI
+        ///is in use.
+        HdmiAux = 7
+    }
+
+    ///The MASH noise-shaping filter, which allows fractional divisors by
+    ///alternating between neighbouring integer divisors. Higher stages give
+    ///a more accurate average frequency, at the cost of more jitter.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub enum Mash {
+        ///Integer division only, giving a clean square wave.
+        Integer = 0,
+        Stage1,
+        Stage2,
+        Stage3
+    }
+
+    ///The ways configuring a clock pin can fail.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub enum ClockError {
+        ///The frequency can't be produced from the clock source.
+        FrequencyOutOfRange,
+        ///The pin can't output a general purpose clock.
+        NotClockCapable,
+        ///Another pin is already using the same clock generator.
+        ChannelInUse,
+        ///The clock registers couldn't be accessed, which requires root
+        ///privileges.
+        RegisterAccess,
+        ///The clock registers of this SoC are unknown. Only the BCM2835,
+        ///BCM2836, BCM2837 and BCM2711 are supported.
+        UnsupportedSoc,
+    }
+
+    impl fmt::Display for ClockError {
+        fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            let message = match *self {
+                ClockError::FrequencyOutOfRange => "the frequency can't be produced from the clock source",
+                ClockError::NotClockCapable => "the pin can't output a general purpose clock",
+                ClockError::ChannelInUse => "the clock generator is already in use by another pin",
+                ClockError::RegisterAccess => "the clock registers couldn't be accessed",
+                ClockError::UnsupportedSoc => "the clock registers of this SoC are unknown",
+            };
+
+            f.write_str(message)
         }
     }
 
-    pub struct ClockPin<Pin>(libc::c_int, PhantomData<Pin>);
+    impl Error for ClockError {}
+
+    ///Set for each clock generator that is used by a `ClockPin`.
+    static CLOCK_CHANNELS: [AtomicBool; 3] = [const { AtomicBool::new(false) }; 3];
+
+    #[derive(Debug, Clone, Copy)]
+    struct ClockSettings {
+        source: ClockSource,
+        mash: Mash,
+        target: Option<u32>,
+        frequency: Option<f64>,
+    }
+
+    ///A pin with general purpose clock output.
+    ///
+    ///The clock is a square wave divided down from the selected
+    ///`ClockSource`. The output starts at wiringPi's default of 100 kHz
+    ///from the oscillator. Changing the frequency, source or MASH filter
+    ///writes the clock registers directly through `/dev/mem`, which requires
+    ///root privileges and is only supported on the BCM2835, BCM2836, BCM2837
+    ///and BCM2711. Other SoCs return `ClockError::UnsupportedSoc`.
+    #[derive(Debug)]
+    pub struct ClockPin<Pin>(libc::c_int, ClockChannel, ClockSettings, PhantomData<Pin>);
 
     impl<P: Pin + GpioClock> ClockPin<P> {
-        pub fn new(pin: libc::c_int) -> ClockPin<P> {
+        ///Sets up `pin` for clock output, claiming its clock generator.
+        ///
+        ///This fails if the pin can't output a clock, or if its generator is
+        ///already used by another pin.
+        pub fn new(pin: libc::c_int) -> Result<ClockPin<P>, ClockError> {
+            let channel = ClockPin::<P>::claim(pin)?;
+            Ok(ClockPin::start(pin, channel))
+        }
+
+        ///Checks that `pin` can output a clock and claims its generator.
+        fn claim(pin: libc::c_int) -> Result<ClockChannel, ClockError> {
+            let channel = P::clock_channel(pin).ok_or(ClockError::NotClockCapable)?;
+
+            if CLOCK_CHANNELS[channel as usize].swap(true, Ordering::SeqCst) {
+                return Err(ClockError::ChannelInUse);
+            }
+
+            Ok(channel)
+        }
+
+        ///Switches `pin` to clock output on its claimed `channel`.
+        fn start(pin: libc::c_int, channel: ClockChannel) -> ClockPin<P> {
             unsafe {
                 bindings::pinMode(pin, GPIO_CLOCK);
             }
 
-            ClockPin(pin, PhantomData)
+            let settings = ClockSettings {
+                source: ClockSource::Oscillator,
+                mash: Mash::Integer,
+                target: None,
+                frequency: None,
+            };
+
+            ClockPin(pin, channel, settings, PhantomData)
         }
 
         #[inline]
         pub fn number(&self) -> libc::c_int {
-            let &ClockPin(number, _) = self;
+            let &ClockPin(number, _, _, _) = self;
             number
         }
 
+        ///The clock generator that the pin is using.
+        #[inline]
+        pub fn channel(&self) -> ClockChannel {
+            let &ClockPin(_, channel, _, _) = self;
+            channel
+        }
+
         pub fn into_input(self) -> InputPin<P> {
-            let ClockPin(number, _) = self;
+            let ClockPin(number, _, _, _) = self;
             InputPin::new(number)
         }
 
         pub fn into_output(self) -> OutputPin<P> {
-            let ClockPin(number, _) = self;
+            let ClockPin(number, _, _, _) = self;
             OutputPin::new(number)
         }
 
         pub fn into_soft_pwm(self) -> SoftPwmPin<P> {
-            let ClockPin(number, _) = self;
+            let ClockPin(number, _, _, _) = self;
             SoftPwmPin::new(number)
         }
 
+        pub fn into_soft_tone(self) -> SoftTonePin<P> {
+            let ClockPin(number, _, _, _) = self;
+            SoftTonePin::new(number)
+        }
+
+        ///Sets the frequency in Hz and returns the frequency that was
+        ///achieved, which is as close as the divisor allows.
+        pub fn set_frequency(&mut self, frequency: u32) -> Result<f64, ClockError> {
+            let mut settings = self.2;
+            settings.target = Some(frequency);
+            self.apply(settings)
+        }
+
+        ///The achieved frequency in Hz, or `None` if it hasn't been set with
+        ///`set_frequency()`.
+        pub fn achieved_frequency(&self) -> Option<f64> {
+            self.2.frequency
+        }
+
         ///Set the freuency on a GPIO clock pin.
+        ///
+        ///This uses wiringPi's `gpioClockSet` and doesn't update
+        ///`achieved_frequency()`.
+        #[deprecated(note = "use `set_frequency` instead")]
         pub fn frequency(&self, freq: u16) {
             unsafe {
                 bindings::gpioClockSet(self.number(), freq as libc::c_int);
             }
         }
+
+        pub fn source(&self) -> ClockSource {
+            self.2.source
+        }
+
+        ///Selects the source to divide the clock from. A running clock is
+        ///restarted with the same target frequency.
+        pub fn set_source(&mut self, source: ClockSource) -> Result<(), ClockError> {
+            let mut settings = self.2;
+            settings.source = source;
+            self.apply(settings).map(|_| ())
+        }
+
+        pub fn mash(&self) -> Mash {
+            self.2.mash
+        }
+
+        ///Selects the MASH filter. A running clock is restarted with the same
+        ///target frequency.
+        pub fn set_mash(&mut self, mash: Mash) -> Result<(), ClockError> {
+            let mut settings = self.2;
+            settings.mash = mash;
+            self.apply(settings).map(|_| ())
+        }
+
+        ///Stops the clock output, leaving the pin low.
+        pub fn stop(&mut self) -> Result<(), ClockError> {
+            clock::stop(self.channel())?;
+            self.2.target = None;
+            self.2.frequency = None;
+            Ok(())
+        }
+
+        fn apply(&mut self, mut settings: ClockSettings) -> Result<f64, ClockError> {
+            let target = match settings.target {
+                Some(target) => target,
+                None => {
+                    self.2 = settings;
+                    return Ok(0.0);
+                },
+            };
+
+            let source_frequency = clock::source_frequency(settings.source)?;
+            let divisor = clock::Divisor::for_frequency(source_frequency, settings.mash, target)?;
+
+            clock::start(self.channel(), settings.source, settings.mash, divisor)?;
+
+            let frequency = divisor.frequency(source_frequency);
+            settings.frequency = Some(frequency);
+            self.2 = settings;
+
+            Ok(frequency)
+        }
+    }
+
+    impl<P> Drop for ClockPin<P> {
+        fn drop(&mut self) {
+            let &mut ClockPin(_, channel, _, _) = self;
+            CLOCK_CHANNELS[channel as usize].store(false, Ordering::SeqCst);
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use std::sync::{Mutex, MutexGuard};
+        use std::time::Duration;
+
+        use super::{tone_range, Gpio, InputPin, PwmChannel, PwmConfig, PwmController, PwmError, PwmMode, SoftPwmPin, MAX_TONE_FREQUENCY};
+
+        ///Serializes the tests that need the `PwmController`, since only one
+        ///can exist at a time.
+        static CONTROLLER: Mutex<()> = Mutex::new(());
+
+        fn lock_controller() -> MutexGuard<'static, ()> {
+            CONTROLLER.lock().unwrap_or_else(|e| e.into_inner())
+        }
+
+        #[test]
+        fn pwm_config_for_frequency() {
+            let config = PwmConfig::for_frequency(1000.0).unwrap();
+            assert_eq!((config.divisor, config.range), (2, 9600));
+            assert_eq!(config.mode, PwmMode::MarkSpace);
+            assert_eq!(config.frequency(), 1000.0);
+
+            let config = PwmConfig::for_frequency(50.0).unwrap();
+            assert_eq!((config.divisor, config.range), (2, 192_000));
+
+            let config = PwmConfig::for_frequency(440.0).unwrap();
+            assert_eq!((config.divisor, config.range), (2, 21_818));
+            assert!((config.frequency() - 440.0).abs() < 0.01);
+
+            let config = PwmConfig::for_frequency(4_800_000.0).unwrap();
+            assert_eq!((config.divisor, config.range), (2, 2));
+        }
+
+        #[test]
+        fn pwm_config_for_low_frequency() {
+            let config = PwmConfig::for_frequency(0.001).unwrap();
+            assert_eq!(config.divisor, 9);
+            assert!(config.range <= i32::MAX as u32);
+            assert!((config.frequency() - 0.001).abs() < 1e-9);
+        }
+
+        #[test]
+        fn pwm_controller_settings() {
+            let _lock = lock_controller();
+            let mut pwm = PwmController::<Gpio>::new().unwrap();
+
+            assert_eq!(pwm.set_range(0), Err(PwmError::InvalidRange));
+            assert_eq!(pwm.set_range(i32::MAX as u32 + 1), Err(PwmError::InvalidRange));
+            assert_eq!(pwm.set_clock(1), Err(PwmError::InvalidDivisor));
+            assert_eq!(pwm.set_clock(4096), Err(PwmError::InvalidDivisor));
+            assert_eq!(pwm.frequency(), 19_200_000.0 / (32.0 * 1024.0));
+
+            pwm.set_range(1000).unwrap();
+            pwm.set_clock(192).unwrap();
+            assert_eq!(pwm.frequency(), 100.0);
+
+            let pin = InputPin::<Gpio>::new(17);
+            let (pin, error) = pin.into_pwm(&pwm).unwrap_err();
+            assert_eq!(error, PwmError::NotPwmCapable);
+            assert_eq!(pin.number(), 17);
+
+            pwm.set_frequency(50.0).unwrap();
+            assert_eq!(pwm.resolution(), 192_000);
+
+            let pin = pwm.pwm_pin(18).unwrap();
+            assert_eq!(pin.write(150_000), Ok(()));
+            assert_eq!(pin.write(192_000), Ok(()));
+            assert_eq!(pin.write(192_001), Err(PwmError::DutyOutOfRange));
+        }
+
+        #[test]
+        fn soft_pwm_range() {
+            assert_eq!(SoftPwmPin::<Gpio>::with_range(17, 0, 0).unwrap_err(), PwmError::InvalidRange);
+            assert_eq!(SoftPwmPin::<Gpio>::with_range(17, i32::MAX as u32 + 1, 0).unwrap_err(), PwmError::InvalidRange);
+            assert_eq!(SoftPwmPin::<Gpio>::with_range(17, 200, 201).unwrap_err(), PwmError::DutyOutOfRange);
+
+            let pin = SoftPwmPin::<Gpio>::with_range(17, 200, 200).unwrap();
+            assert_eq!(pin.range(), 200);
+            assert_eq!(pin.period(), Duration::from_millis(20));
+            assert_eq!(pin.clamp(-5), 0);
+            assert_eq!(pin.clamp(150), 150);
+            assert_eq!(pin.clamp(201), 200);
+            assert_eq!(pin.set_duty(1.5), Err(PwmError::DutyOutOfRange));
+            assert_eq!(pin.set_pulse_width(Duration::from_micros(20_100)), Err(PwmError::PulseTooLong));
+        }
+
+        #[test]
+        fn tone_frequency() {
+            assert_eq!(tone_range(1), 600_000);
+            assert_eq!(tone_range(440), 1364);
+            assert_eq!(tone_range(2000), 300);
+            assert_eq!(tone_range(MAX_TONE_FREQUENCY), 2);
+            assert_eq!(tone_range(MAX_TONE_FREQUENCY + 1), 2);
+            assert_eq!(tone_range(u32::MAX), 2);
+        }
+
+        #[test]
+        fn tone_generator_reservation() {
+            let _lock = lock_controller();
+            let pwm = PwmController::<Gpio>::new().unwrap();
+
+            let other = pwm.pwm_pin(19).unwrap();
+            let (pin, error) = pwm.pwm_pin(18).unwrap().into_tone().unwrap_err();
+            assert_eq!(error, PwmError::GeneratorInUse);
+            drop(other);
+
+            let tone = pin.into_tone().unwrap();
+            assert_eq!(pwm.pwm_pin(19).unwrap_err(), PwmError::GeneratorInUse);
+            assert_eq!(pwm.pwm_pin(13).unwrap_err(), PwmError::GeneratorInUse);
+
+            let pin = tone.into_pwm();
+            assert_eq!(pin.channel(), PwmChannel::Pwm0);
+            assert!(pwm.pwm_pin(19).is_ok());
+        }
+
+        #[test]
+        fn pwm_config_out_of_range() {
+            for &frequency in &[0.0, -1.0, 1e-6, 10_000_000.0, f64::NAN, f64::INFINITY] {
+                assert_eq!(PwmConfig::for_frequency(frequency).unwrap_err(), PwmError::FrequencyOutOfRange);
+            }
+        }
     }
 }
 
@@ -643,14 +1804,19 @@ impl<P: Pin> WiringPi<P> {
 }
 
 impl<P: Pwm + Pin> WiringPi<P> {
-    pub fn pwm_pin(&self) -> pin::PwmPin<P> {
-        Pwm::pwm_pin()
+    ///Takes control over the hardware PWM generator, which is needed to set
+    ///up PWM pins. Returns `None` if there is already a `PwmController`.
+    pub fn pwm_controller(&self) -> Option<pin::PwmController<P>> {
+        pin::PwmController::new()
     }
 }
 
 impl<P: GpioClock + Pin> WiringPi<P> {
-    pub fn clock_pin(&self) -> pin::ClockPin<P> {
-        GpioClock::clock_pin()
+    ///Sets up `pin` for general purpose clock output. Fails if the pin
+    ///can't output a clock, or if its clock generator is in use.
+    pub fn clock_pin(&self, pin: u16) -> Result<pin::ClockPin<P>, pin::ClockError> {
+        let pin = pin as libc::c_int;
+        pin::ClockPin::new(pin)
     }
 }
 
@@ -659,4 +1825,16 @@ impl<P: Pin + RequiresRoot> WiringPi<P> {
         let pin = pin as libc::c_int;
         pin::SoftPwmPin::new(pin)
     }
+
+    ///Sets up a software PWM pin with the range [0,`range`] and `initial`
+    ///as the starting value. Each step of the range is 100μs long.
+    pub fn soft_pwm_pin_with(&self, pin: u16, range: u32, initial: u32) -> Result<pin::SoftPwmPin<P>, pin::PwmError> {
+        let pin = pin as libc::c_int;
+        pin::SoftPwmPin::with_range(pin, range, initial)
+    }
+
+    pub fn soft_tone_pin(&self, pin: u16) -> pin::SoftTonePin<P> {
+        let pin = pin as libc::c_int;
+        pin::SoftTonePin::new(pin)
+    }
 }
@@ -0,0 +1,419 @@
+//! DC motor control through H-bridge drivers.
+
+use std::error::Error;
+use std::fmt;
+use std::thread;
+use std::time::Duration;
+
+use pin::{OutputPin, Pin, PwmError, PwmOutput, Value};
+
+///How often the speed is updated while ramping.
+const RAMP_INTERVAL_MILLIS: u64 = 10;
+
+///How the PWM output and the direction pins are connected to the H-bridge.
+pub enum Wiring<P> {
+    ///The PWM output drives IN1 and `direction` drives IN2, like on the
+    ///DRV8833 or L9110. Backward speeds set `direction` high and invert the
+    ///duty cycle. Braking sets both inputs high.
+    InIn { direction: OutputPin<P> },
+
+    ///The PWM output sets the speed and `direction` the direction, like on
+    ///the DRV8838 or MD10C. Many of these drivers can't coast, so coasting
+    ///and braking both set the duty cycle to 0.
+    PwmDir { direction: OutputPin<P> },
+
+    ///The PWM output drives the enable input, while `in1` and `in2` set the
+    ///direction, like on the L298N or L293D. Braking sets both inputs low
+    ///with the bridge enabled.
+    EnableInIn { in1: OutputPin<P>, in2: OutputPin<P> },
+}
+
+///The ways controlling a motor can fail.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MotorError {
+    ///The speed is outside the interval [-1, 1].
+    SpeedOutOfRange,
+    ///The acceleration limit is negative or zero.
+    AccelerationOutOfRange,
+    ///The PWM output rejected the duty cycle.
+    Pwm(PwmError),
+}
+
+impl fmt::Display for MotorError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            MotorError::SpeedOutOfRange => f.write_str("the speed has to be between -1 and 1"),
+            MotorError::AccelerationOutOfRange => f.write_str("the acceleration limit has to be positive"),
+            MotorError::Pwm(ref error) => write!(f, "PWM error: {}", error),
+        }
+    }
+}
+
+impl Error for MotorError {}
+
+impl From<PwmError> for MotorError {
+    fn from(error: PwmError) -> MotorError {
+        MotorError::Pwm(error)
+    }
+}
+
+///A DC motor driven by an H-bridge, with a signed speed in the interval
+///[-1, 1].
+///
+///The PWM output can be any `PwmOutput`. Most H-bridges work well with
+///frequencies in the range of a few kHz to 20 kHz, which are too high for
+///`SoftPwmPin`s, so a hardware `PwmPin` or a `NativeSoftPwm` is the better
+///choice.
+///
+///```no_run
+///use wiringpi::motor::Motor;
+///
+///let pi = wiringpi::setup_gpio();
+///let mut pwm = pi.pwm_controller().unwrap();
+///pwm.set_frequency(20_000.0).unwrap();
+///
+///let mut motor = Motor::enable_in_in(pwm.pwm_pin(12).unwrap(), pi.output_pin(5), pi.output_pin(6));
+///motor.set_max_acceleration(Some(2.0)).unwrap();
+///
+///motor.ramp_to(0.8).unwrap();
+///motor.ramp_to(-0.8).unwrap();
+///motor.brake().unwrap();
+///```
+pub struct Motor<T, P> {
+    output: T,
+    wiring: Wiring<P>,
+    speed: f64,
+    ///The direction that the direction pins are set to, or `None` if it's
+    ///unknown or both inputs are low.
+    forward: Option<bool>,
+    inverted: bool,
+    max_acceleration: Option<f64>,
+}
+
+impl<T: PwmOutput, P: Pin> Motor<T, P> {
+    ///Creates a motor with the given wiring. The motor starts out coasting.
+    pub fn new(output: T, wiring: Wiring<P>) -> Motor<T, P> {
+        let mut motor = Motor {
+            output,
+            wiring,
+            speed: 0.0,
+            forward: None,
+            inverted: false,
+            max_acceleration: None,
+        };
+
+        let _ = motor.coast_outputs();
+        motor
+    }
+
+    ///Creates a motor where `output` drives IN1 and `direction` drives IN2.
+    pub fn in_in(output: T, direction: OutputPin<P>) -> Motor<T, P> {
+        Motor::new(output, Wiring::InIn { direction })
+    }
+
+    ///Creates a motor where `output` sets the speed and `direction` the
+    ///direction.
+    pub fn pwm_dir(output: T, direction: OutputPin<P>) -> Motor<T, P> {
+        Motor::new(output, Wiring::PwmDir { direction })
+    }
+
+    ///Creates a motor where `output` drives the enable input and `in1` and
+    ///`in2` set the direction.
+    pub fn enable_in_in(output: T, in1: OutputPin<P>, in2: OutputPin<P>) -> Motor<T, P> {
+        Motor::new(output, Wiring::EnableInIn { in1, in2 })
+    }
+
+    pub fn output(&self) -> &T {
+        &self.output
+    }
+
+    pub fn wiring(&self) -> &Wiring<P> {
+        &self.wiring
+    }
+
+    ///The current speed, where negative values are backward.
+    pub fn speed(&self) -> f64 {
+        self.speed
+    }
+
+    ///Swaps forward and backward, for motors that are wired the other way
+    ///around. Takes effect with the next speed change.
+    pub fn set_inverted(&mut self, inverted: bool) {
+        self.inverted = inverted;
+    }
+
+    ///Limits how fast `ramp_to()` changes the speed, in full speeds per
+    ///second. There is no limit by default.
+    pub fn set_max_acceleration(&mut self, acceleration: Option<f64>) -> Result<(), MotorError> {
+        if let Some(acceleration) = acceleration {
+            if acceleration.is_nan() || acceleration <= 0.0 {
+                return Err(MotorError::AccelerationOutOfRange);
+            }
+        }
+
+        self.max_acceleration = acceleration;
+        Ok(())
+    }
+
+    ///Changes the speed immediately.
+    pub fn set_speed(&mut self, speed: f64) -> Result<(), MotorError> {
+        if !(-1.0..=1.0).contains(&speed) {
+            return Err(MotorError::SpeedOutOfRange);
+        }
+
+        self.write(speed)
+    }
+
+    ///Changes the speed within the acceleration limit, blocking the current
+    ///thread until `speed` is reached. The speed is changed immediately if
+    ///there is no limit.
+    pub fn ramp_to(&mut self, speed: f64) -> Result<(), MotorError> {
+        if !(-1.0..=1.0).contains(&speed) {
+            return Err(MotorError::SpeedOutOfRange);
+        }
+
+        let acceleration = match self.max_acceleration {
+            Some(acceleration) => acceleration,
+            None => return self.write(speed),
+        };
+
+        let interval = Duration::from_millis(RAMP_INTERVAL_MILLIS);
+        let step = acceleration * RAMP_INTERVAL_MILLIS as f64 / 1000.0;
+
+        while (speed - self.speed).abs() > step {
+            let next = if speed > self.speed { self.speed + step } else { self.speed - step };
+            self.write(next)?;
+            thread::sleep(interval);
+        }
+
+        self.write(speed)
+    }
+
+    ///Lets the motor spin freely, by disconnecting it from the supply.
+    pub fn coast(&mut self) -> Result<(), MotorError> {
+        self.coast_outputs()?;
+        self.speed = 0.0;
+        Ok(())
+    }
+
+    ///Stops the motor quickly, by shorting its terminals.
+    pub fn brake(&mut self) -> Result<(), MotorError> {
+        match self.wiring {
+            Wiring::InIn { ref direction } => {
+                direction.digital_write(Value::High);
+                self.forward = Some(false);
+                self.output.set_duty(1.0)?;
+            },
+            Wiring::PwmDir { .. } => {
+                self.output.set_duty(0.0)?;
+            },
+            Wiring::EnableInIn { ref in1, ref in2 } => {
+                self.output.set_duty(0.0)?;
+                in1.digital_write(Value::Low);
+                in2.digital_write(Value::Low);
+                self.forward = None;
+                self.output.set_duty(1.0)?;
+            },
+        }
+
+        self.speed = 0.0;
+        Ok(())
+    }
+
+    ///Coasts the motor and returns the PWM output and the direction pins.
+    pub fn into_inner(mut self) -> (T, Wiring<P>) {
+        let _ = self.coast_outputs();
+        (self.output, self.wiring)
+    }
+
+    fn coast_outputs(&mut self) -> Result<(), MotorError> {
+        if let Wiring::InIn { ref direction } = self.wiring {
+            self.output.set_duty(0.0)?;
+            direction.digital_write(Value::Low);
+            self.forward = Some(true);
+        }
+
+        self.output.set_duty(0.0)?;
+        Ok(())
+    }
+
+    fn write(&mut self, speed: f64) -> Result<(), MotorError> {
+        let signed = if self.inverted { -speed } else { speed };
+        let (forward, magnitude) = (signed >= 0.0, signed.abs());
+
+        //The motor is stopped before the direction changes, so it doesn't
+        //run at the old duty cycle in the new direction
+        if self.forward != Some(forward) {
+            let stopped = match self.wiring {
+                Wiring::InIn { .. } if self.forward == Some(false) => 1.0,
+                _ => 0.0,
+            };
+
+            self.output.set_duty(stopped)?;
+
+            match self.wiring {
+                Wiring::InIn { ref direction } | Wiring::PwmDir { ref direction } => {
+                    direction.digital_write(if forward { Value::Low } else { Value::High });
+                },
+                Wiring::EnableInIn { ref in1, ref in2 } => {
+                    in1.digital_write(if forward { Value::High } else { Value::Low });
+                    in2.digital_write(if forward { Value::Low } else { Value::High });
+                },
+            }
+
+            self.forward = Some(forward);
+        }
+
+        let duty = match self.wiring {
+            Wiring::InIn { .. } if !forward => 1.0 - magnitude,
+            _ => magnitude,
+        };
+
+        self.output.set_duty(duty)?;
+        self.speed = speed;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+    use std::time::Duration;
+
+    use extension::{self, Node, Registration};
+    use pin::{Gpio, OutputPin, PwmError, PwmOutput, Value};
+    use super::Motor;
+
+    #[derive(Debug, Clone, Copy, PartialEq)]
+    enum Event {
+        Duty(f64),
+        Pin(u16, Value),
+    }
+
+    #[derive(Clone, Default)]
+    struct Log(Arc<Mutex<Vec<Event>>>);
+
+    impl Log {
+        fn push(&self, event: Event) {
+            self.0.lock().unwrap().push(event);
+        }
+
+        fn take(&self) -> Vec<Event> {
+            ::std::mem::take(&mut *self.0.lock().unwrap())
+        }
+    }
+
+    impl PwmOutput for Log {
+        fn period(&self) -> Duration {
+            Duration::from_micros(50)
+        }
+
+        fn set_duty(&self, duty: f64) -> Result<(), PwmError> {
+            self.push(Event::Duty(duty));
+            Ok(())
+        }
+
+        fn set_pulse_width(&self, _width: Duration) -> Result<(), PwmError> {
+            unimplemented!()
+        }
+    }
+
+    impl Node for Log {
+        fn digital_write(&self, pin: u16, value: Value) {
+            self.push(Event::Pin(pin, value));
+        }
+    }
+
+    fn pins(pin_base: u16) -> (Log, Registration, OutputPin<Gpio>, OutputPin<Gpio>) {
+        let log = Log::default();
+        let registration = extension::register(pin_base, 2, log.clone()).unwrap();
+        let first = OutputPin::new(pin_base as i32);
+        let second = OutputPin::new(pin_base as i32 + 1);
+
+        (log, registration, first, second)
+    }
+
+    #[test]
+    fn in_in_reversal() {
+        let (log, _registration, direction, _) = pins(1300);
+        let mut motor = Motor::in_in(log.clone(), direction);
+        assert_eq!(log.take(), vec![Event::Duty(0.0), Event::Pin(0, Value::Low), Event::Duty(0.0)]);
+
+        motor.set_speed(0.5).unwrap();
+        assert_eq!(log.take(), vec![Event::Duty(0.5)]);
+
+        motor.set_speed(-0.25).unwrap();
+        assert_eq!(log.take(), vec![Event::Duty(0.0), Event::Pin(0, Value::High), Event::Duty(0.75)]);
+
+        motor.set_speed(-0.5).unwrap();
+        assert_eq!(log.take(), vec![Event::Duty(0.5)]);
+
+        motor.set_speed(0.5).unwrap();
+        assert_eq!(log.take(), vec![Event::Duty(1.0), Event::Pin(0, Value::Low), Event::Duty(0.5)]);
+        assert_eq!(motor.speed(), 0.5);
+    }
+
+    #[test]
+    fn inverted_pwm_dir() {
+        let (log, _registration, direction, _) = pins(1310);
+        let mut motor = Motor::pwm_dir(log.clone(), direction);
+        motor.set_inverted(true);
+        log.take();
+
+        motor.set_speed(0.5).unwrap();
+        assert_eq!(log.take(), vec![Event::Duty(0.0), Event::Pin(0, Value::High), Event::Duty(0.5)]);
+
+        motor.set_speed(-0.25).unwrap();
+        assert_eq!(log.take(), vec![Event::Duty(0.0), Event::Pin(0, Value::Low), Event::Duty(0.25)]);
+        assert_eq!(motor.speed(), -0.25);
+    }
+
+    #[test]
+    fn in_in_brake_and_coast() {
+        let (log, _registration, direction, _) = pins(1320);
+        let mut motor = Motor::in_in(log.clone(), direction);
+        motor.set_speed(0.5).unwrap();
+        log.take();
+
+        motor.brake().unwrap();
+        assert_eq!(log.take(), vec![Event::Pin(0, Value::High), Event::Duty(1.0)]);
+        assert_eq!(motor.speed(), 0.0);
+
+        motor.set_speed(-0.5).unwrap();
+        assert_eq!(log.take(), vec![Event::Duty(0.5)]);
+
+        motor.coast().unwrap();
+        assert_eq!(log.take(), vec![Event::Duty(0.0), Event::Pin(0, Value::Low), Event::Duty(0.0)]);
+    }
+
+    #[test]
+    fn enable_in_in_brake() {
+        let (log, _registration, in1, in2) = pins(1330);
+        let mut motor = Motor::enable_in_in(log.clone(), in1, in2);
+        log.take();
+
+        motor.set_speed(0.5).unwrap();
+        assert_eq!(log.take(), vec![
+            Event::Duty(0.0),
+            Event::Pin(0, Value::High),
+            Event::Pin(1, Value::Low),
+            Event::Duty(0.5),
+        ]);
+
+        motor.brake().unwrap();
+        assert_eq!(log.take(), vec![
+            Event::Duty(0.0),
+            Event::Pin(0, Value::Low),
+            Event::Pin(1, Value::Low),
+            Event::Duty(1.0),
+        ]);
+
+        motor.set_speed(0.5).unwrap();
+        assert_eq!(log.take(), vec![
+            Event::Duty(0.0),
+            Event::Pin(0, Value::High),
+            Event::Pin(1, Value::Low),
+            Event::Duty(0.5),
+        ]);
+    }
+}
@@ -0,0 +1,114 @@
+//! A background thread that polls a device and sends its events.
+
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+///Owns `device` on a background thread, which calls `poll` every
+///`interval` and sends the events it returns. The thread stops when the
+///`Poller` is stopped or dropped, or when the receiver is dropped.
+pub struct Poller<D, E> {
+    events: Receiver<E>,
+    stop: Arc<AtomicBool>,
+    handle: Option<JoinHandle<D>>,
+}
+
+impl<D: Send + 'static, E: Send + 'static> Poller<D, E> {
+    pub fn spawn<F, I>(mut device: D, interval: Duration, mut poll: F) -> Poller<D, E>
+        where F: FnMut(&mut D) -> I + Send + 'static,
+              I: IntoIterator<Item = E>
+    {
+        let (events_tx, events_rx) = mpsc::channel();
+        let stop = Arc::new(AtomicBool::new(false));
+        let thread_stop = stop.clone();
+
+        let handle = thread::spawn(move || {
+            while !thread_stop.load(Ordering::SeqCst) {
+                for event in poll(&mut device) {
+                    if events_tx.send(event).is_err() {
+                        return device;
+                    }
+                }
+
+                //Parked instead of sleeping, so that stopping wakes it up
+                let deadline = Instant::now() + interval;
+
+                while !thread_stop.load(Ordering::SeqCst) {
+                    let now = Instant::now();
+
+                    if now >= deadline {
+                        break;
+                    }
+
+                    thread::park_timeout(deadline - now);
+                }
+            }
+
+            device
+        });
+
+        Poller {
+            events: events_rx,
+            stop,
+            handle: Some(handle),
+        }
+    }
+}
+
+impl<D, E> Poller<D, E> {
+    pub fn events(&self) -> &Receiver<E> {
+        &self.events
+    }
+
+    ///Stops the thread and returns the device, or `None` if the thread
+    ///panicked.
+    pub fn stop(mut self) -> Option<D> {
+        self.join()
+    }
+
+    fn join(&mut self) -> Option<D> {
+        self.stop.store(true, Ordering::SeqCst);
+
+        self.handle.take().and_then(|handle| {
+            handle.thread().unpark();
+            handle.join().ok()
+        })
+    }
+}
+
+impl<D, E> Drop for Poller<D, E> {
+    fn drop(&mut self) {
+        self.join();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use super::Poller;
+
+    #[test]
+    fn polls_and_returns_device() {
+        let poller = Poller::spawn(0u32, Duration::from_millis(1), |count| {
+            *count += 1;
+            Some(*count)
+        });
+
+        let events: Vec<_> = poller.events().iter().take(3).collect();
+        assert_eq!(events, [1, 2, 3]);
+        assert!(poller.stop().unwrap() >= 3);
+    }
+
+    #[test]
+    fn stopping_interrupts_the_interval() {
+        let poller = Poller::spawn((), Duration::from_secs(60), |_| Some(()));
+        poller.events().recv().unwrap();
+
+        let start = Instant::now();
+        drop(poller);
+        assert!(start.elapsed() < Duration::from_secs(5));
+    }
+}
@@ -0,0 +1,295 @@
+//! Continuous pulse width measurement.
+
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use bindings;
+use interrupt::Registration;
+use libc;
+use pin::{Edge, InputPin, Pin, Value};
+use timing;
+
+#[derive(Default)]
+struct Pulses {
+    level: Option<Value>,
+    last_edge: Option<u32>,
+    high: Option<u32>,
+    low: Option<u32>,
+    count: u64,
+}
+
+impl Pulses {
+    ///Records a change to `level` at the raw `micros` timestamp `now`, which
+    ///finishes the pulse at the previous level.
+    fn edge(&mut self, level: Value, now: u32) {
+        if self.level == Some(level) {
+            return;
+        }
+
+        if let (Some(previous), Some(last_edge)) = (self.level, self.last_edge) {
+            let width = now.wrapping_sub(last_edge);
+
+            match previous {
+                Value::High => self.high = Some(width),
+                Value::Low => self.low = Some(width),
+            }
+
+            self.count += 1;
+        }
+
+        self.level = Some(level);
+        self.last_edge = Some(now);
+    }
+}
+
+///Measures the length of the next pulse at `level`, by polling `read` and
+///timing it with the `micros` timestamps from `clock`. This is the logic
+///behind `InputPin::pulse_in`.
+///
+///Returns `None` if it takes more than `timeout` microseconds in total.
+pub(crate) fn measure_pulse<R, C>(level: Value, timeout: u32, mut read: R, mut clock: C) -> Option<u32>
+    where R: FnMut() -> Value,
+          C: FnMut() -> u32
+{
+    let start = clock();
+
+    while read() == level {
+        if clock().wrapping_sub(start) > timeout {
+            return None;
+        }
+    }
+
+    while read() != level {
+        if clock().wrapping_sub(start) > timeout {
+            return None;
+        }
+    }
+
+    let pulse_start = clock();
+
+    while read() == level {
+        if clock().wrapping_sub(start) > timeout {
+            return None;
+        }
+    }
+
+    Some(clock().wrapping_sub(pulse_start))
+}
+
+///Measures the high and low time of a signal, using interrupts and the
+///`micros()` clock.
+///
+///Every edge on the pin finishes the measurement of the preceding pulse, so
+///the durations are always those of the latest complete high and low pulses.
+///This is useful for ultrasonic range finders, PWM output from sensors and RC
+///receivers, where a blocking `InputPin::pulse_in` would be in the way.
+///
+///```no_run
+///use wiringpi::pulse::PulseMeter;
+///
+///let pi = wiringpi::setup_gpio();
+///let meter = PulseMeter::new(pi.input_pin(24)).unwrap();
+///
+///if let Some(width) = meter.high_time() {
+///    println!("the RC channel is at {} µs", width.as_micros());
+///}
+///```
+pub struct PulseMeter<P> {
+    pin: InputPin<P>,
+    pulses: Arc<Mutex<Pulses>>,
+    _registration: Registration,
+}
+
+impl<P: Pin> PulseMeter<P> {
+    ///Starts measuring pulses on `pin`.
+    ///
+    ///Returns `None` if an interrupt handler could not be registered for the
+    ///pin.
+    pub fn new(pin: InputPin<P>) -> Option<PulseMeter<P>> {
+        let number = pin.number();
+        let pulses = Arc::new(Mutex::new(Pulses::default()));
+        let isr_pulses = pulses.clone();
+
+        let registration = Registration::new(number, Edge::Both, move || {
+            let now = timing::micros();
+            let level = if unsafe { bindings::digitalRead(number) } == 0 {
+                Value::Low
+            } else {
+                Value::High
+            };
+
+            isr_pulses.lock().unwrap_or_else(|e| e.into_inner()).edge(level, now);
+        });
+
+        registration.map(|registration| PulseMeter {
+            pin,
+            pulses,
+            _registration: registration,
+        })
+    }
+
+    #[inline]
+    pub fn number(&self) -> libc::c_int {
+        self.pin.number()
+    }
+
+    ///The duration of the latest complete high pulse.
+    pub fn high_time(&self) -> Option<Duration> {
+        self.with_pulses(|pulses| pulses.high).map(micros_to_duration)
+    }
+
+    ///The duration of the latest complete low pulse.
+    pub fn low_time(&self) -> Option<Duration> {
+        self.with_pulses(|pulses| pulses.low).map(micros_to_duration)
+    }
+
+    ///The sum of the latest high and low durations.
+    pub fn period(&self) -> Option<Duration> {
+        self.high_low().map(|(high, low)| micros_to_duration(high.saturating_add(low)))
+    }
+
+    ///The fraction of the period where the signal is high, in the interval
+    ///[0, 1].
+    pub fn duty_cycle(&self) -> Option<f64> {
+        self.high_low().and_then(|(high, low)| {
+            let period = high as f64 + low as f64;
+
+            if period > 0.0 {
+                Some(high as f64 / period)
+            } else {
+                None
+            }
+        })
+    }
+
+    ///The frequency of the signal in Hz, based on the latest period.
+    pub fn frequency(&self) -> Option<f64> {
+        self.high_low().and_then(|(high, low)| {
+            let period = high as f64 + low as f64;
+
+            if period > 0.0 {
+                Some(1_000_000.0 / period)
+            } else {
+                None
+            }
+        })
+    }
+
+    ///The time since the latest edge. A signal that has stopped toggling
+    ///will keep reporting its last durations, so this can be used to detect
+    ///stale measurements.
+    pub fn since_last_edge(&self) -> Option<Duration> {
+        self.with_pulses(|pulses| pulses.last_edge)
+            .map(|last_edge| micros_to_duration(timing::micros().wrapping_sub(last_edge)))
+    }
+
+    ///The number of complete pulses that have been measured.
+    pub fn pulse_count(&self) -> u64 {
+        self.with_pulses(|pulses| pulses.count)
+    }
+
+    ///Stops measuring and returns the underlying pin.
+    pub fn into_inner(self) -> InputPin<P> {
+        let PulseMeter { pin, .. } = self;
+        pin
+    }
+
+    fn high_low(&self) -> Option<(u32, u32)> {
+        self.with_pulses(|pulses| match (pulses.high, pulses.low) {
+            (Some(high), Some(low)) => Some((high, low)),
+            _ => None,
+        })
+    }
+
+    fn with_pulses<T, F: FnOnce(&Pulses) -> T>(&self, f: F) -> T {
+        f(&self.pulses.lock().unwrap_or_else(|e| e.into_inner()))
+    }
+}
+
+fn micros_to_duration(micros: u32) -> Duration {
+    Duration::from_micros(micros as u64)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::Cell;
+
+    use pin::Value;
+    use super::{measure_pulse, Pulses};
+
+    ///Measures a pulse on a signal that is high in the intervals of
+    ///`high`, with a clock that starts at `start` and advances by one
+    ///microsecond every time it's read.
+    fn measure(level: Value, timeout: u32, start: u32, high: &[(u32, u32)]) -> (Option<u32>, u32) {
+        let time = Cell::new(start);
+        let read = || {
+            let elapsed = time.get().wrapping_sub(start);
+
+            if high.iter().any(|&(from, to)| elapsed >= from && elapsed < to) {
+                Value::High
+            } else {
+                Value::Low
+            }
+        };
+        let clock = || {
+            let now = time.get();
+            time.set(now.wrapping_add(1));
+            now
+        };
+
+        let width = measure_pulse(level, timeout, read, clock);
+        (width, time.get().wrapping_sub(start))
+    }
+
+    #[test]
+    fn pulse_width() {
+        let (width, _) = measure(Value::High, 1000, 0, &[(0, 10), (30, 55)]);
+        assert!((24..=26).contains(&width.unwrap()), "{:?}", width);
+
+        let (width, _) = measure(Value::Low, 1000, 0, &[(0, 10), (30, 55)]);
+        assert!((19..=21).contains(&width.unwrap()), "{:?}", width);
+    }
+
+    #[test]
+    fn pulse_width_across_wraparound() {
+        let (width, _) = measure(Value::High, 1000, u32::MAX - 40, &[(30, 80)]);
+        assert!((49..=51).contains(&width.unwrap()), "{:?}", width);
+    }
+
+    #[test]
+    fn pulse_timeout() {
+        let (width, elapsed) = measure(Value::High, 100, 0, &[]);
+        assert_eq!(width, None);
+        assert!((100..=103).contains(&elapsed), "{}", elapsed);
+
+        let (width, _) = measure(Value::High, 100, 0, &[(0, 200)]);
+        assert_eq!(width, None);
+
+        let (width, _) = measure(Value::High, 100, u32::MAX - 10, &[(50, 500)]);
+        assert_eq!(width, None);
+    }
+
+    #[test]
+    fn edges() {
+        let mut pulses = Pulses::default();
+
+        pulses.edge(Value::High, 100);
+        assert_eq!((pulses.high, pulses.low, pulses.count), (None, None, 0));
+
+        pulses.edge(Value::High, 150);
+        pulses.edge(Value::Low, 400);
+        assert_eq!((pulses.high, pulses.low, pulses.count), (Some(300), None, 1));
+
+        pulses.edge(Value::High, 1400);
+        assert_eq!((pulses.high, pulses.low, pulses.count), (Some(300), Some(1000), 2));
+    }
+
+    #[test]
+    fn edges_across_wraparound() {
+        let mut pulses = Pulses::default();
+
+        pulses.edge(Value::Low, u32::MAX - 99);
+        pulses.edge(Value::High, 100);
+        assert_eq!(pulses.low, Some(200));
+    }
+}
@@ -0,0 +1,401 @@
+//! Serial ports.
+
+use std::ffi::CString;
+use std::io::{self, Read, Write};
+use std::os::unix::ffi::OsStrExt;
+use std::os::unix::io::{AsRawFd, RawFd};
+use std::path::Path;
+use std::time::Duration;
+
+use bindings;
+use libc;
+
+///The baud rates that `SerialPort::open` accepts.
+pub const BAUD_RATES: [u32; 18] = [
+    50, 75, 110, 134, 150, 200, 300, 600, 1200, 1800, 2400, 4800, 9600, 19200, 38400, 57600, 115200, 230400,
+];
+
+///A serial port, set up for raw 8N1 communication.
+///
+///Reads block until at least one byte is available, or until the read
+///timeout has passed. Once the other end hangs up and all data has been
+///read, reads return 0 bytes.
+///
+///The port can be any terminal device, including the UART on the header,
+///USB adapters and pseudo-terminals.
+///
+///```
+///# extern crate libc;
+///# extern crate wiringpi;
+///use std::ffi::CStr;
+///use std::io::{Read, Write};
+///use std::time::Duration;
+///use wiringpi::serial::SerialPort;
+///
+///# fn main() {
+///let (master, name) = unsafe {
+///    let master = libc::posix_openpt(libc::O_RDWR | libc::O_NOCTTY);
+///    libc::grantpt(master);
+///    libc::unlockpt(master);
+///    (master, CStr::from_ptr(libc::ptsname(master)).to_string_lossy().into_owned())
+///};
+///
+///let mut port = SerialPort::open(&name, 115200).unwrap();
+///port.set_read_timeout(Some(Duration::from_millis(100)));
+///
+///unsafe {
+///    libc::write(master, b"ping".as_ptr() as *const _, 4);
+///}
+///
+///let mut buffer = [0; 4];
+///port.read_exact(&mut buffer).unwrap();
+///assert_eq!(&buffer, b"ping");
+///port.write_all(b"pong").unwrap();
+///# }
+///```
+pub struct SerialPort {
+    fd: libc::c_int,
+    timeout: Option<Duration>,
+}
+
+impl SerialPort {
+    ///Opens `device` at `baud`, which has to be one of `BAUD_RATES`.
+    pub fn open<P: AsRef<Path>>(device: P, baud: u32) -> io::Result<SerialPort> {
+        if !BAUD_RATES.contains(&baud) {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, "unsupported baud rate"));
+        }
+
+        let device = CString::new(device.as_ref().as_os_str().as_bytes())
+            .map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "the device path contains a zero byte"))?;
+
+        let fd = unsafe { bindings::serialOpen(device.as_ptr(), baud as libc::c_int) };
+
+        if fd < 0 {
+            Err(io::Error::last_os_error())
+        } else {
+            Ok(SerialPort {
+                fd,
+                timeout: None,
+            })
+        }
+    }
+
+    ///How long reads wait for data, or `None` to wait forever.
+    pub fn read_timeout(&self) -> Option<Duration> {
+        self.timeout
+    }
+
+    ///Sets how long reads wait for data before failing with
+    ///`ErrorKind::TimedOut`. They wait forever by default.
+    pub fn set_read_timeout(&mut self, timeout: Option<Duration>) {
+        self.timeout = timeout;
+    }
+
+    ///The number of bytes that can be read without waiting.
+    pub fn bytes_available(&self) -> io::Result<usize> {
+        let available = unsafe { bindings::serialDataAvail(self.fd) };
+
+        if available < 0 {
+            Err(io::Error::last_os_error())
+        } else {
+            Ok(available as usize)
+        }
+    }
+
+    ///Reads a single byte, waiting for the read timeout.
+    pub fn read_byte(&mut self) -> io::Result<u8> {
+        self.wait_for_data()?;
+
+        match unsafe { bindings::serialGetchar(self.fd) } {
+            -1 => Err(io::Error::new(io::ErrorKind::TimedOut, "no data was received")),
+            byte => Ok(byte as u8),
+        }
+    }
+
+    ///Writes a single byte.
+    pub fn write_byte(&mut self, byte: u8) {
+        unsafe {
+            bindings::serialPutchar(self.fd, byte as libc::c_uchar);
+        }
+    }
+
+    ///Writes a string, which can't contain any zero bytes.
+    pub fn puts(&mut self, string: &str) -> io::Result<()> {
+        let string = CString::new(string)
+            .map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "the string contains a zero byte"))?;
+
+        unsafe {
+            bindings::serialPuts(self.fd, string.as_ptr());
+        }
+
+        Ok(())
+    }
+
+    ///Discards all data that has been received but not read, and all data
+    ///that has been written but not sent.
+    pub fn clear(&mut self) {
+        unsafe {
+            bindings::serialFlush(self.fd);
+        }
+    }
+
+    ///Waits until data can be read, and returns whether the other end has
+    ///hung up.
+    fn wait_for_data(&self) -> io::Result<bool> {
+        let timeout = match self.timeout {
+            Some(timeout) => timeout.as_secs()
+                .saturating_mul(1000)
+                .saturating_add(timeout.subsec_millis() as u64)
+                .min(libc::c_int::MAX as u64) as libc::c_int,
+            None => -1,
+        };
+
+        let mut poll_fd = libc::pollfd {
+            fd: self.fd,
+            events: libc::POLLIN,
+            revents: 0,
+        };
+
+        loop {
+            match unsafe { libc::poll(&mut poll_fd, 1, timeout) } {
+                0 => return Err(io::Error::new(io::ErrorKind::TimedOut, "no data was received")),
+                result if result > 0 => return Ok(poll_fd.revents & libc::POLLHUP != 0),
+                _ => {
+                    let error = io::Error::last_os_error();
+
+                    if error.kind() != io::ErrorKind::Interrupted {
+                        return Err(error);
+                    }
+                },
+            }
+        }
+    }
+}
+
+impl Read for SerialPort {
+    fn read(&mut self, buffer: &mut [u8]) -> io::Result<usize> {
+        if buffer.is_empty() {
+            return Ok(0);
+        }
+
+        let available = if self.wait_for_data()? {
+            //The data that was received before the hangup is read first,
+            //but some drivers fail with EIO instead of reporting it
+            match self.bytes_available() {
+                Ok(0) | Err(_) => return Ok(0),
+                Ok(available) => available,
+            }
+        } else {
+            self.bytes_available()?
+        };
+
+        let available = available.max(1).min(buffer.len());
+
+        for (index, byte) in buffer[..available].iter_mut().enumerate() {
+            match unsafe { bindings::serialGetchar(self.fd) } {
+                -1 if index == 0 => return Err(io::Error::new(io::ErrorKind::TimedOut, "no data was received")),
+                -1 => return Ok(index),
+                value => *byte = value as u8,
+            }
+        }
+
+        Ok(available)
+    }
+}
+
+impl Write for SerialPort {
+    fn write(&mut self, data: &[u8]) -> io::Result<usize> {
+        let written = unsafe { libc::write(self.fd, data.as_ptr() as *const libc::c_void, data.len()) };
+
+        if written < 0 {
+            Err(io::Error::last_os_error())
+        } else {
+            Ok(written as usize)
+        }
+    }
+
+    ///Waits until all written data has been sent.
+    fn flush(&mut self) -> io::Result<()> {
+        if unsafe { libc::tcdrain(self.fd) } < 0 {
+            Err(io::Error::last_os_error())
+        } else {
+            Ok(())
+        }
+    }
+}
+
+impl AsRawFd for SerialPort {
+    fn as_raw_fd(&self) -> RawFd {
+        self.fd
+    }
+}
+
+impl Drop for SerialPort {
+    fn drop(&mut self) {
+        unsafe {
+            bindings::serialClose(self.fd);
+        }
+    }
+}
+
+///A port of wiringSerial, so that serial ports work in development mode.
+#[cfg(feature = "development")]
+pub(crate) mod native {
+    use std::mem;
+
+    use libc;
+
+    fn speed(baud: libc::c_int) -> Option<libc::speed_t> {
+        let speed = match baud {
+            50 => libc::B50,
+            75 => libc::B75,
+            110 => libc::B110,
+            134 => libc::B134,
+            150 => libc::B150,
+            200 => libc::B200,
+            300 => libc::B300,
+            600 => libc::B600,
+            1200 => libc::B1200,
+            1800 => libc::B1800,
+            2400 => libc::B2400,
+            4800 => libc::B4800,
+            9600 => libc::B9600,
+            19200 => libc::B19200,
+            38400 => libc::B38400,
+            57600 => libc::B57600,
+            115200 => libc::B115200,
+            230400 => libc::B230400,
+            _ => return None,
+        };
+
+        Some(speed)
+    }
+
+    pub unsafe fn open(device: *const libc::c_char, baud: libc::c_int) -> libc::c_int {
+        let speed = match speed(baud) {
+            Some(speed) => speed,
+            None => return -1,
+        };
+
+        let fd = libc::open(device, libc::O_RDWR | libc::O_NOCTTY | libc::O_NDELAY | libc::O_NONBLOCK);
+
+        if fd < 0 {
+            return -1;
+        }
+
+        libc::fcntl(fd, libc::F_SETFL, libc::O_RDWR);
+
+        let mut options: libc::termios = mem::zeroed();
+        libc::tcgetattr(fd, &mut options);
+        libc::cfmakeraw(&mut options);
+        libc::cfsetispeed(&mut options, speed);
+        libc::cfsetospeed(&mut options, speed);
+
+        options.c_cflag |= libc::CLOCAL | libc::CREAD;
+        options.c_cflag &= !(libc::PARENB | libc::CSTOPB | libc::CSIZE);
+        options.c_cflag |= libc::CS8;
+        options.c_lflag &= !(libc::ICANON | libc::ECHO | libc::ECHOE | libc::ISIG);
+        options.c_oflag &= !libc::OPOST;
+        options.c_cc[libc::VMIN] = 0;
+        options.c_cc[libc::VTIME] = 100;
+
+        libc::tcsetattr(fd, libc::TCSANOW, &options);
+
+        fd
+    }
+
+    pub unsafe fn close(fd: libc::c_int) {
+        libc::close(fd);
+    }
+
+    pub unsafe fn flush(fd: libc::c_int) {
+        libc::tcflush(fd, libc::TCIOFLUSH);
+    }
+
+    pub unsafe fn putchar(fd: libc::c_int, c: libc::c_uchar) {
+        libc::write(fd, &c as *const libc::c_uchar as *const libc::c_void, 1);
+    }
+
+    pub unsafe fn puts(fd: libc::c_int, s: *const libc::c_char) {
+        libc::write(fd, s as *const libc::c_void, libc::strlen(s));
+    }
+
+    pub unsafe fn data_avail(fd: libc::c_int) -> libc::c_int {
+        let mut result: libc::c_int = 0;
+
+        if libc::ioctl(fd, libc::FIONREAD as _, &mut result) == -1 {
+            return -1;
+        }
+
+        result
+    }
+
+    pub unsafe fn getchar(fd: libc::c_int) -> libc::c_int {
+        let mut c: libc::c_uchar = 0;
+
+        if libc::read(fd, &mut c as *mut libc::c_uchar as *mut libc::c_void, 1) != 1 {
+            return -1;
+        }
+
+        c as libc::c_int
+    }
+}
+
+#[cfg(all(test, feature = "development"))]
+mod tests {
+    use std::ffi::CStr;
+    use std::io::Read;
+    use std::time::Duration;
+
+    use libc;
+    use super::SerialPort;
+
+    fn open_pty() -> (libc::c_int, SerialPort) {
+        let (master, name) = unsafe {
+            let master = libc::posix_openpt(libc::O_RDWR | libc::O_NOCTTY);
+            assert!(master >= 0);
+            libc::grantpt(master);
+            libc::unlockpt(master);
+            (master, CStr::from_ptr(libc::ptsname(master)).to_string_lossy().into_owned())
+        };
+
+        let mut port = SerialPort::open(&name, 115200).unwrap();
+        port.set_read_timeout(Some(Duration::from_secs(5)));
+
+        (master, port)
+    }
+
+    #[test]
+    fn hangup_ends_the_stream() {
+        let (master, mut port) = open_pty();
+
+        unsafe {
+            libc::write(master, b"bye".as_ptr() as *const _, 3);
+        }
+
+        let mut buffer = [0; 3];
+        port.read_exact(&mut buffer).unwrap();
+        assert_eq!(&buffer, b"bye");
+
+        unsafe {
+            libc::close(master);
+        }
+
+        let mut data = Vec::new();
+        assert_eq!(port.read_to_end(&mut data).unwrap(), 0);
+        assert_eq!(port.read(&mut buffer).unwrap(), 0);
+    }
+
+    #[test]
+    fn timeout_without_hangup() {
+        let (master, mut port) = open_pty();
+        port.set_read_timeout(Some(Duration::from_millis(10)));
+
+        let mut buffer = [0; 4];
+        assert_eq!(port.read(&mut buffer).unwrap_err().kind(), ::std::io::ErrorKind::TimedOut);
+
+        unsafe {
+            libc::close(master);
+        }
+    }
+}
@@ -0,0 +1,456 @@
+//! Hobby servo control.
+
+use std::error::Error;
+use std::fmt;
+use std::thread;
+use std::time::Duration;
+
+use pin::{PwmError, PwmOutput};
+
+///The usual signal frequency of hobby servos, in Hz.
+pub const SERVO_FREQUENCY: f64 = 50.0;
+
+///The pulse widths a servo responds to.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ServoConfig {
+    ///The pulse width for 0 degrees, or full speed backward for continuous
+    ///rotation servos.
+    pub min_pulse: Duration,
+
+    ///The pulse width for `range` degrees, or full speed forward for
+    ///continuous rotation servos.
+    pub max_pulse: Duration,
+
+    ///The angle in degrees between the `min_pulse` and `max_pulse`
+    ///positions.
+    pub range: f64,
+}
+
+impl Default for ServoConfig {
+    fn default() -> ServoConfig {
+        ServoConfig {
+            min_pulse: Duration::from_micros(1000),
+            max_pulse: Duration::from_micros(2000),
+            range: 180.0,
+        }
+    }
+}
+
+///The ways controlling a servo can fail.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ServoError {
+    ///The angle is outside the calibrated range.
+    AngleOutOfRange,
+    ///The speed is negative, zero or outside the interval [-1, 1] for
+    ///continuous rotation servos.
+    SpeedOutOfRange,
+    ///The pulse widths are in the wrong order, don't fit in the PWM period
+    ///or the range isn't positive.
+    InvalidCalibration,
+    ///The PWM output rejected the pulse width.
+    Pwm(PwmError),
+}
+
+impl fmt::Display for ServoError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            ServoError::AngleOutOfRange => f.write_str("the angle is outside the range of the servo"),
+            ServoError::SpeedOutOfRange => f.write_str("the speed is out of range"),
+            ServoError::InvalidCalibration => f.write_str("the servo calibration doesn't fit the PWM output"),
+            ServoError::Pwm(ref error) => write!(f, "PWM error: {}", error),
+        }
+    }
+}
+
+impl Error for ServoError {}
+
+impl From<PwmError> for ServoError {
+    fn from(error: PwmError) -> ServoError {
+        ServoError::Pwm(error)
+    }
+}
+
+///A positional servo, driven by any `PwmOutput`.
+///
+///The output should have a period of around 20ms, which is
+///`SERVO_FREQUENCY`. Hardware PWM pins get there with
+///`PwmController::set_frequency(SERVO_FREQUENCY)`, and software PWM pins with
+///a range of 200.
+///
+///```no_run
+///use std::time::Duration;
+///use wiringpi::servo::{Servo, ServoConfig, SERVO_FREQUENCY};
+///
+///let pi = wiringpi::setup_gpio();
+///let mut pwm = pi.pwm_controller().unwrap();
+///pwm.set_frequency(SERVO_FREQUENCY).unwrap();
+///
+///let config = ServoConfig {
+///    min_pulse: Duration::from_micros(600),
+///    max_pulse: Duration::from_micros(2400),
+///    range: 180.0,
+///};
+///
+///let mut servo = Servo::new(pwm.pwm_pin(18).unwrap(), config).unwrap();
+///servo.set_angle(90.0).unwrap();
+///servo.sweep_to(0.0, 60.0).unwrap();
+///```
+pub struct Servo<T> {
+    output: T,
+    config: ServoConfig,
+    angle: Option<f64>,
+}
+
+impl<T: PwmOutput> Servo<T> {
+    ///Creates a servo on `output`. No pulses are sent until the first
+    ///position is set.
+    ///
+    ///Returns `Err(ServoError::InvalidCalibration)` if the pulse widths are
+    ///in the wrong order or the longest pulse doesn't fit in the period of
+    ///the output.
+    pub fn new(output: T, config: ServoConfig) -> Result<Servo<T>, ServoError> {
+        if !(config.range.is_finite() && config.range > 0.0) {
+            return Err(ServoError::InvalidCalibration);
+        }
+
+        check_pulses(&output, config.min_pulse, config.max_pulse)?;
+
+        Ok(Servo {
+            output,
+            config,
+            angle: None,
+        })
+    }
+
+    pub fn config(&self) -> &ServoConfig {
+        &self.config
+    }
+
+    pub fn output(&self) -> &T {
+        &self.output
+    }
+
+    ///The latest angle that was set, or `None` if the position is unknown.
+    pub fn angle(&self) -> Option<f64> {
+        self.angle
+    }
+
+    ///Moves the servo to `angle` degrees, as fast as it goes.
+    pub fn set_angle(&mut self, angle: f64) -> Result<(), ServoError> {
+        if !(0.0..=self.config.range).contains(&angle) {
+            return Err(ServoError::AngleOutOfRange);
+        }
+
+        self.write_angle(angle)
+    }
+
+    ///Sends a pulse of `width` for calibrating the servo. The pulse isn't
+    ///limited to the calibrated range.
+    pub fn set_pulse_width(&mut self, width: Duration) -> Result<(), ServoError> {
+        self.output.set_pulse_width(width)?;
+
+        let (min, max) = (nanos(self.config.min_pulse), nanos(self.config.max_pulse));
+        let angle = (nanos(width) - min) / (max - min) * self.config.range;
+        self.angle = if (0.0..=self.config.range).contains(&angle) { Some(angle) } else { None };
+        Ok(())
+    }
+
+    ///Moves the servo to `angle` degrees at `speed` degrees per second,
+    ///blocking the current thread until the movement is done.
+    ///
+    ///The position is updated once per PWM period. If the current position
+    ///is unknown the servo jumps to `angle` instead.
+    pub fn sweep_to(&mut self, angle: f64, speed: f64) -> Result<(), ServoError> {
+        if !(0.0..=self.config.range).contains(&angle) {
+            return Err(ServoError::AngleOutOfRange);
+        }
+
+        if speed.is_nan() || speed <= 0.0 {
+            return Err(ServoError::SpeedOutOfRange);
+        }
+
+        let period = self.output.period();
+        let step = speed * nanos(period) / 1_000_000_000.0;
+
+        loop {
+            let current = match self.angle {
+                Some(current) => current,
+                None => return self.write_angle(angle),
+            };
+
+            if (angle - current).abs() <= step {
+                return self.write_angle(angle);
+            }
+
+            let next = if angle > current { current + step } else { current - step };
+            self.write_angle(next)?;
+            thread::sleep(period);
+        }
+    }
+
+    ///Stops sending pulses, which lets most servos turn freely. The next
+    ///position that is set enables them again.
+    pub fn detach(&mut self) -> Result<(), ServoError> {
+        self.output.set_duty(0.0)?;
+        Ok(())
+    }
+
+    ///Returns the underlying PWM output.
+    pub fn into_inner(self) -> T {
+        self.output
+    }
+
+    fn write_angle(&mut self, angle: f64) -> Result<(), ServoError> {
+        let (min, max) = (nanos(self.config.min_pulse), nanos(self.config.max_pulse));
+        let width = min + (max - min) * angle / self.config.range;
+
+        self.output.set_pulse_width(Duration::from_nanos(width.round() as u64))?;
+        self.angle = Some(angle);
+        Ok(())
+    }
+}
+
+///A continuous rotation servo, where the pulse width sets the speed
+///instead of the position.
+///
+///The servo stands still at the neutral pulse width, which is halfway
+///between `min_pulse` and `max_pulse` unless it's trimmed with
+///`set_neutral()`. `ServoConfig::range` is ignored.
+///
+///```no_run
+///use wiringpi::servo::{ContinuousServo, ServoConfig};
+///
+///let pi = wiringpi::setup_gpio();
+///let wheel = pi.soft_pwm_pin_with(17, 200, 0).unwrap();
+///let wheel = ContinuousServo::new(wheel, ServoConfig::default()).unwrap();
+///
+///wheel.set_speed(-0.5).unwrap();
+///```
+pub struct ContinuousServo<T> {
+    output: T,
+    config: ServoConfig,
+    neutral: Duration,
+}
+
+impl<T: PwmOutput> ContinuousServo<T> {
+    ///Creates a continuous rotation servo on `output`. No pulses are sent
+    ///until the first speed is set.
+    pub fn new(output: T, config: ServoConfig) -> Result<ContinuousServo<T>, ServoError> {
+        check_pulses(&output, config.min_pulse, config.max_pulse)?;
+
+        let neutral = (nanos(config.min_pulse) + nanos(config.max_pulse)) / 2.0;
+
+        Ok(ContinuousServo {
+            output,
+            config,
+            neutral: Duration::from_nanos(neutral.round() as u64),
+        })
+    }
+
+    pub fn config(&self) -> &ServoConfig {
+        &self.config
+    }
+
+    pub fn output(&self) -> &T {
+        &self.output
+    }
+
+    ///The pulse width where the servo stands still.
+    pub fn neutral(&self) -> Duration {
+        self.neutral
+    }
+
+    ///Trims the pulse width where the servo stands still. It has to be
+    ///strictly between `min_pulse` and `max_pulse`.
+    pub fn set_neutral(&mut self, neutral: Duration) -> Result<(), ServoError> {
+        if neutral <= self.config.min_pulse || neutral >= self.config.max_pulse {
+            return Err(ServoError::InvalidCalibration);
+        }
+
+        self.neutral = neutral;
+        Ok(())
+    }
+
+    ///Sets the speed as a fraction in the interval [-1, 1], where negative
+    ///values turn backward and 0 stands still.
+    pub fn set_speed(&self, speed: f64) -> Result<(), ServoError> {
+        if !(-1.0..=1.0).contains(&speed) {
+            return Err(ServoError::SpeedOutOfRange);
+        }
+
+        let neutral = nanos(self.neutral);
+        let width = if speed >= 0.0 {
+            neutral + (nanos(self.config.max_pulse) - neutral) * speed
+        } else {
+            neutral + (neutral - nanos(self.config.min_pulse)) * speed
+        };
+
+        self.output.set_pulse_width(Duration::from_nanos(width.round() as u64))?;
+        Ok(())
+    }
+
+    ///Holds the servo still with the neutral pulse width.
+    pub fn stop(&self) -> Result<(), ServoError> {
+        self.set_speed(0.0)
+    }
+
+    ///Stops sending pulses.
+    pub fn detach(&self) -> Result<(), ServoError> {
+        self.output.set_duty(0.0)?;
+        Ok(())
+    }
+
+    ///Returns the underlying PWM output.
+    pub fn into_inner(self) -> T {
+        self.output
+    }
+}
+
+fn check_pulses<T: PwmOutput>(output: &T, min: Duration, max: Duration) -> Result<(), ServoError> {
+    if min >= max || max > output.period() {
+        return Err(ServoError::InvalidCalibration);
+    }
+
+    Ok(())
+}
+
+fn nanos(duration: Duration) -> f64 {
+    duration.as_secs() as f64 * 1_000_000_000.0 + duration.subsec_nanos() as f64
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::Cell;
+    use std::time::Duration;
+
+    use pin::{PwmError, PwmOutput};
+    use super::{ContinuousServo, Servo, ServoConfig, ServoError};
+
+    struct MockPwm {
+        period: Duration,
+        pulse: Cell<Option<Duration>>,
+    }
+
+    impl MockPwm {
+        fn new() -> MockPwm {
+            MockPwm {
+                period: Duration::from_millis(20),
+                pulse: Cell::new(None),
+            }
+        }
+    }
+
+    impl PwmOutput for MockPwm {
+        fn period(&self) -> Duration {
+            self.period
+        }
+
+        fn set_duty(&self, duty: f64) -> Result<(), PwmError> {
+            self.pulse.set(Some(Duration::from_nanos((duty * 20_000_000.0) as u64)));
+            Ok(())
+        }
+
+        fn set_pulse_width(&self, width: Duration) -> Result<(), PwmError> {
+            if width > self.period {
+                return Err(PwmError::PulseTooLong);
+            }
+
+            self.pulse.set(Some(width));
+            Ok(())
+        }
+    }
+
+    fn micros(micros: u64) -> Option<Duration> {
+        Some(Duration::from_micros(micros))
+    }
+
+    #[test]
+    fn calibration() {
+        let config = |min, max, range| ServoConfig {
+            min_pulse: Duration::from_micros(min),
+            max_pulse: Duration::from_micros(max),
+            range,
+        };
+
+        assert!(Servo::new(MockPwm::new(), config(1000, 2000, 180.0)).is_ok());
+        assert!(Servo::new(MockPwm::new(), config(1000, 20_000, 180.0)).is_ok());
+
+        for &(min, max, range) in &[(2000, 1000, 180.0), (1000, 1000, 180.0), (1000, 20_001, 180.0), (1000, 2000, 0.0), (1000, 2000, f64::NAN)] {
+            assert_eq!(Servo::new(MockPwm::new(), config(min, max, range)).err(), Some(ServoError::InvalidCalibration));
+        }
+
+        assert_eq!(ContinuousServo::new(MockPwm::new(), config(2000, 1000, 0.0)).err(), Some(ServoError::InvalidCalibration));
+    }
+
+    #[test]
+    fn angle_to_pulse() {
+        let mut servo = Servo::new(MockPwm::new(), ServoConfig::default()).unwrap();
+        assert_eq!(servo.angle(), None);
+
+        servo.set_angle(0.0).unwrap();
+        assert_eq!(servo.output().pulse.get(), micros(1000));
+
+        servo.set_angle(180.0).unwrap();
+        assert_eq!(servo.output().pulse.get(), micros(2000));
+
+        servo.set_angle(45.0).unwrap();
+        assert_eq!(servo.output().pulse.get(), micros(1250));
+        assert_eq!(servo.angle(), Some(45.0));
+
+        assert_eq!(servo.set_angle(-0.1), Err(ServoError::AngleOutOfRange));
+        assert_eq!(servo.set_angle(180.1), Err(ServoError::AngleOutOfRange));
+        assert_eq!(servo.output().pulse.get(), micros(1250));
+    }
+
+    #[test]
+    fn pulse_to_angle() {
+        let mut servo = Servo::new(MockPwm::new(), ServoConfig::default()).unwrap();
+
+        servo.set_pulse_width(Duration::from_micros(1500)).unwrap();
+        assert_eq!(servo.angle(), Some(90.0));
+
+        servo.set_pulse_width(Duration::from_micros(2000)).unwrap();
+        assert_eq!(servo.angle(), Some(180.0));
+
+        servo.set_pulse_width(Duration::from_micros(2100)).unwrap();
+        assert_eq!(servo.angle(), None);
+        assert_eq!(servo.output().pulse.get(), micros(2100));
+
+        assert_eq!(servo.set_pulse_width(Duration::from_millis(21)), Err(ServoError::Pwm(PwmError::PulseTooLong)));
+    }
+
+    #[test]
+    fn sweep_without_position_jumps() {
+        let mut servo = Servo::new(MockPwm::new(), ServoConfig::default()).unwrap();
+
+        servo.sweep_to(90.0, 1000.0).unwrap();
+        assert_eq!(servo.angle(), Some(90.0));
+        assert_eq!(servo.sweep_to(90.0, 0.0), Err(ServoError::SpeedOutOfRange));
+        assert_eq!(servo.sweep_to(181.0, 10.0), Err(ServoError::AngleOutOfRange));
+    }
+
+    #[test]
+    fn speed_to_pulse() {
+        let mut servo = ContinuousServo::new(MockPwm::new(), ServoConfig::default()).unwrap();
+        assert_eq!(servo.neutral(), Duration::from_micros(1500));
+
+        servo.set_speed(1.0).unwrap();
+        assert_eq!(servo.output().pulse.get(), micros(2000));
+
+        servo.set_speed(-1.0).unwrap();
+        assert_eq!(servo.output().pulse.get(), micros(1000));
+
+        servo.stop().unwrap();
+        assert_eq!(servo.output().pulse.get(), micros(1500));
+
+        servo.set_neutral(Duration::from_micros(1600)).unwrap();
+        servo.set_speed(0.5).unwrap();
+        assert_eq!(servo.output().pulse.get(), micros(1800));
+        servo.set_speed(-0.5).unwrap();
+        assert_eq!(servo.output().pulse.get(), micros(1300));
+
+        assert_eq!(servo.set_speed(1.5), Err(ServoError::SpeedOutOfRange));
+        assert_eq!(servo.set_neutral(Duration::from_micros(1000)), Err(ServoError::InvalidCalibration));
+        assert_eq!(servo.set_neutral(Duration::from_micros(2000)), Err(ServoError::InvalidCalibration));
+    }
+}
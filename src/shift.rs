@@ -0,0 +1,136 @@
+//! Bit-banged serial transfers for shift registers.
+
+use std::time::Duration;
+
+use bindings;
+use pin::{InputPin, OutputPin, Pin, Value};
+use timing;
+
+///The order the bits of a byte are shifted in or out.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BitOrder {
+    ///The most significant bit comes first.
+    MsbFirst,
+    ///The least significant bit comes first.
+    LsbFirst,
+}
+
+impl BitOrder {
+    ///The bit mask for the `index`th bit in a transfer.
+    fn mask(self, index: u8) -> u8 {
+        match self {
+            BitOrder::MsbFirst => 0x80 >> index,
+            BitOrder::LsbFirst => 1 << index,
+        }
+    }
+}
+
+///The levels of the bits of `byte`, in the order they're shifted out.
+pub(crate) fn bits(order: BitOrder, byte: u8) -> impl Iterator<Item = Value> {
+    (0..8).map(move |index| if byte & order.mask(index) != 0 { Value::High } else { Value::Low })
+}
+
+///Assembles a byte from the first 8 `bits`, in the order they're shifted
+///in.
+pub(crate) fn from_bits<I: IntoIterator<Item = Value>>(order: BitOrder, bits: I) -> u8 {
+    bits.into_iter()
+        .take(8)
+        .zip(0..8)
+        .filter(|&(value, _)| value == Value::High)
+        .fold(0, |byte, (_, index)| byte | order.mask(index))
+}
+
+///Shifts out `byte` on `data`, one bit per pulse on `clock`.
+///
+///Each bit is written to `data` before `clock` goes high, so it's picked up
+///by devices that sample on the rising edge. This is the same as
+///`shiftOut` in wiringPi.
+///
+///```no_run
+///use wiringpi::pin::Value;
+///use wiringpi::shift::{self, BitOrder};
+///
+///let pi = wiringpi::setup();
+///let data = pi.output_pin(0);
+///let clock = pi.output_pin(1);
+///let latch = pi.output_pin(2);
+///
+///shift::shift_out(&data, &clock, BitOrder::MsbFirst, 0b1010_0101);
+///latch.digital_write(Value::High);
+///latch.digital_write(Value::Low);
+///```
+pub fn shift_out<P: Pin>(data: &OutputPin<P>, clock: &OutputPin<P>, order: BitOrder, byte: u8) {
+    shift_out_with_delay(data, clock, order, byte, Duration::from_secs(0));
+}
+
+///Shifts out `byte` like `shift_out`, but waits for `delay` after each
+///change, for devices that can't keep up with the pins.
+pub fn shift_out_with_delay<P: Pin>(data: &OutputPin<P>, clock: &OutputPin<P>, order: BitOrder, byte: u8, delay: Duration) {
+    for value in bits(order, byte) {
+        data.digital_write(value);
+        pause(delay);
+        clock.digital_write(Value::High);
+        pause(delay);
+        clock.digital_write(Value::Low);
+    }
+}
+
+///Shifts in a byte from `data`, one bit per pulse on `clock`.
+///
+///Each bit is read from `data` after `clock` has gone high. This is the same
+///as `shiftIn` in wiringPi.
+pub fn shift_in<P: Pin>(data: &InputPin<P>, clock: &OutputPin<P>, order: BitOrder) -> u8 {
+    shift_in_with_delay(data, clock, order, Duration::from_secs(0))
+}
+
+///Shifts in a byte like `shift_in`, but waits for `delay` after each
+///change, for devices that can't keep up with the pins.
+pub fn shift_in_with_delay<P: Pin>(data: &InputPin<P>, clock: &OutputPin<P>, order: BitOrder, delay: Duration) -> u8 {
+    from_bits(order, (0..8).map(|_| {
+        clock.digital_write(Value::High);
+        pause(delay);
+
+        let value = data.digital_read();
+
+        clock.digital_write(Value::Low);
+        pause(delay);
+
+        value
+    }))
+}
+
+fn pause(delay: Duration) {
+    if delay > Duration::from_secs(0) {
+        unsafe {
+            bindings::delayMicroseconds(timing::duration_to_micros(delay));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use pin::Value::{self, High, Low};
+    use super::{bits, from_bits, BitOrder};
+
+    #[test]
+    fn bit_order() {
+        let msb: Vec<Value> = bits(BitOrder::MsbFirst, 0b1100_0101).collect();
+        assert_eq!(msb, [High, High, Low, Low, Low, High, Low, High]);
+
+        let lsb: Vec<Value> = bits(BitOrder::LsbFirst, 0b1100_0101).collect();
+        assert_eq!(lsb, [High, Low, High, Low, Low, Low, High, High]);
+    }
+
+    #[test]
+    fn assemble_bits() {
+        assert_eq!(from_bits(BitOrder::MsbFirst, vec![High, Low, Low, Low, Low, Low, Low, Low]), 0x80);
+        assert_eq!(from_bits(BitOrder::LsbFirst, vec![High, Low, Low, Low, Low, Low, Low, Low]), 0x01);
+        assert_eq!(from_bits(BitOrder::MsbFirst, vec![High; 12]), 0xFF);
+
+        for &order in &[BitOrder::MsbFirst, BitOrder::LsbFirst] {
+            for byte in 0..=255u8 {
+                assert_eq!(from_bits(order, bits(order, byte)), byte);
+            }
+        }
+    }
+}
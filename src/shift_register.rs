@@ -0,0 +1,559 @@
+//! Chains of shift registers that extend the number of pins.
+
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::sync::mpsc::Receiver;
+use std::time::Duration;
+
+use pin::{DigitalInput, DigitalOutput, InputPin, OutputPin, Pin, Value};
+use poller::Poller;
+use shift::{self, BitOrder};
+
+struct OutputState {
+    buffer: Vec<u8>,
+    auto_latch: bool,
+}
+
+///A chain of 74HC595 serial-in/parallel-out shift registers, where each
+///output can be used as a virtual output pin.
+///
+///The registers are daisy-chained by connecting Q7' of each one to DS of
+///the next. DS of the first register is connected to `data`, and all of
+///them share `clock` (SHCP) and `latch` (STCP). Output 0 is Q0 of the first
+///register, output 8 is Q0 of the second register, and so on.
+///
+///Writes go to a buffer, which is shifted out and latched all at once. That
+///happens after every write by default, but auto-latching can be turned off
+///to update several outputs at the same time with `latch()`.
+///
+///```no_run
+///use wiringpi::pin::{DigitalOutput, Value};
+///use wiringpi::shift_register::ShiftRegisterChain;
+///
+///fn set_relay<O: DigitalOutput>(relay: &O, on: bool) {
+///    relay.digital_write(if on { Value::High } else { Value::Low });
+///}
+///
+///let pi = wiringpi::setup();
+///let chain = ShiftRegisterChain::new(pi.output_pin(0), pi.output_pin(1), pi.output_pin(2), 2).unwrap();
+///let relays = chain.pins();
+///
+///set_relay(&relays[3], true);
+///set_relay(&pi.output_pin(4), true);
+///
+///chain.set_auto_latch(false);
+///for relay in &relays {
+///    set_relay(relay, false);
+///}
+///chain.latch();
+///```
+pub struct ShiftRegisterChain<P> {
+    data: OutputPin<P>,
+    clock: OutputPin<P>,
+    latch: OutputPin<P>,
+    state: Mutex<OutputState>,
+}
+
+impl<P: Pin> ShiftRegisterChain<P> {
+    ///Sets up a chain of `registers` shift registers and sets all of their
+    ///outputs low. Returns `None` if `registers` is 0.
+    pub fn new(data: OutputPin<P>, clock: OutputPin<P>, latch: OutputPin<P>, registers: usize) -> Option<ShiftRegisterChain<P>> {
+        if registers == 0 {
+            return None;
+        }
+
+        let chain = ShiftRegisterChain {
+            data,
+            clock,
+            latch,
+            state: Mutex::new(OutputState {
+                buffer: vec![0; registers],
+                auto_latch: true,
+            }),
+        };
+
+        chain.latch.digital_write(Value::Low);
+        chain.clock.digital_write(Value::Low);
+        chain.latch();
+
+        Some(chain)
+    }
+
+    ///The number of registers in the chain.
+    pub fn registers(&self) -> usize {
+        self.lock().buffer.len()
+    }
+
+    ///The number of outputs in the chain, which is 8 per register.
+    pub fn outputs(&self) -> usize {
+        self.registers() * 8
+    }
+
+    ///A virtual pin for output number `index`, or `None` if there is no such
+    ///output.
+    pub fn pin<'a>(&'a self, index: usize) -> Option<ShiftRegisterPin<'a, P>> {
+        if index < self.outputs() {
+            Some(ShiftRegisterPin {
+                chain: self,
+                index,
+            })
+        } else {
+            None
+        }
+    }
+
+    ///Virtual pins for all of the outputs, in order.
+    pub fn pins<'a>(&'a self) -> Vec<ShiftRegisterPin<'a, P>> {
+        (0..self.outputs()).map(|index| ShiftRegisterPin { chain: self, index }).collect()
+    }
+
+    ///Whether the outputs are updated after every write.
+    pub fn auto_latch(&self) -> bool {
+        self.lock().auto_latch
+    }
+
+    ///Turns updating the outputs after every write on or off. The writes are
+    ///kept in the buffer until `latch()` is called when it's off.
+    pub fn set_auto_latch(&self, auto_latch: bool) {
+        self.lock().auto_latch = auto_latch;
+    }
+
+    ///Sets output number `index` to `value`.
+    ///
+    ///Panics if `index` is not less than `outputs()`.
+    pub fn write(&self, index: usize, value: Value) {
+        let mut state = self.lock();
+        let mask = 1 << (index % 8);
+
+        match value {
+            Value::High => state.buffer[index / 8] |= mask,
+            Value::Low => state.buffer[index / 8] &= !mask,
+        }
+
+        self.update(&state);
+    }
+
+    ///The buffered value of output number `index`, which may not have been
+    ///latched yet.
+    ///
+    ///Panics if `index` is not less than `outputs()`.
+    pub fn value(&self, index: usize) -> Value {
+        if self.lock().buffer[index / 8] & (1 << (index % 8)) != 0 {
+            Value::High
+        } else {
+            Value::Low
+        }
+    }
+
+    ///Sets all 8 outputs of a register at once, with Q0 in the least
+    ///significant bit.
+    ///
+    ///Panics if `register` is not less than `registers()`.
+    pub fn set_register(&self, register: usize, byte: u8) {
+        let mut state = self.lock();
+        state.buffer[register] = byte;
+        self.update(&state);
+    }
+
+    ///The buffered outputs of a register, with Q0 in the least significant
+    ///bit.
+    ///
+    ///Panics if `register` is not less than `registers()`.
+    pub fn register(&self, register: usize) -> u8 {
+        self.lock().buffer[register]
+    }
+
+    ///Sets all of the outputs to `value`.
+    pub fn set_all(&self, value: Value) {
+        let mut state = self.lock();
+        let byte = match value {
+            Value::High => 0xFF,
+            Value::Low => 0x00,
+        };
+
+        for register in state.buffer.iter_mut() {
+            *register = byte;
+        }
+
+        self.update(&state);
+    }
+
+    ///Shifts out the buffer and updates the outputs.
+    pub fn latch(&self) {
+        self.shift_out(&self.lock());
+    }
+
+    ///Returns the data, clock and latch pins. The outputs keep their current
+    ///levels.
+    pub fn into_inner(self) -> (OutputPin<P>, OutputPin<P>, OutputPin<P>) {
+        (self.data, self.clock, self.latch)
+    }
+
+    fn lock(&self) -> MutexGuard<'_, OutputState> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn update(&self, state: &OutputState) {
+        if state.auto_latch {
+            self.shift_out(state);
+        }
+    }
+
+    fn shift_out(&self, state: &OutputState) {
+        for value in chain_bits(&state.buffer) {
+            self.data.digital_write(value);
+            self.clock.digital_write(Value::High);
+            self.clock.digital_write(Value::Low);
+        }
+
+        self.latch.digital_write(Value::High);
+        self.latch.digital_write(Value::Low);
+    }
+}
+
+///One output of a `ShiftRegisterChain`, which can be used like an
+///`OutputPin`.
+pub struct ShiftRegisterPin<'a, P: 'a> {
+    chain: &'a ShiftRegisterChain<P>,
+    index: usize,
+}
+
+///The bits to shift out to a chain of 74HC595s with the outputs in
+///`buffer`. The last register comes first, since the bits are pushed
+///through the first one to reach it, and Q7 comes first in each register.
+fn chain_bits<'a>(buffer: &'a [u8]) -> impl Iterator<Item = Value> + 'a {
+    buffer.iter().rev().flat_map(|&byte| shift::bits(BitOrder::MsbFirst, byte))
+}
+
+impl<'a, P: Pin> ShiftRegisterPin<'a, P> {
+    ///The index of the output in the chain.
+    pub fn index(&self) -> usize {
+        self.index
+    }
+
+    pub fn chain(&self) -> &'a ShiftRegisterChain<P> {
+        self.chain
+    }
+
+    ///Sets the output to `value`, which is latched right away if
+    ///auto-latching is on.
+    pub fn digital_write(&self, value: Value) {
+        self.chain.write(self.index, value);
+    }
+
+    ///The buffered value of the output.
+    pub fn value(&self) -> Value {
+        self.chain.value(self.index)
+    }
+}
+
+impl<'a, P: Pin> DigitalOutput for ShiftRegisterPin<'a, P> {
+    fn digital_write(&self, value: Value) {
+        ShiftRegisterPin::digital_write(self, value);
+    }
+}
+
+///An input of a `ParallelInShiftRegister` changing level.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct InputChange {
+    ///The index of the input in the chain.
+    pub index: usize,
+    ///The new level of the input.
+    pub value: Value,
+}
+
+///A chain of 74HC165 parallel-in/serial-out shift registers, where each
+///input can be used as a virtual input pin.
+///
+///The registers are daisy-chained by connecting Q7 of each one to DS of the
+///previous one. Q7 of the first register is connected to `data`, and all of
+///them share `load` (SH/LD) and `clock` (CLK). CE has to be tied low. Input
+///0 is D0 of the first register, input 8 is D0 of the second register, and
+///so on.
+///
+///The inputs are read with `poll()`, which also reports which of them have
+///changed since the previous poll, or periodically on a background thread
+///with `spawn()`. The virtual pins from `pin()` and `pins()` read the levels
+///from the latest poll, and keep working while the chain is polled in the
+///background.
+///
+///```no_run
+///use std::time::Duration;
+///use wiringpi::pin::{DigitalInput, Value};
+///use wiringpi::shift_register::ParallelInShiftRegister;
+///
+///let pi = wiringpi::setup();
+///let chain = ParallelInShiftRegister::new(pi.output_pin(3), pi.output_pin(4), pi.input_pin(5), 2).unwrap();
+///let door = chain.pin(9).unwrap();
+///let poller = chain.spawn(Duration::from_millis(10));
+///
+///for change in poller.events() {
+///    println!("input {} is now {:?}", change.index, change.value);
+///
+///    if door.digital_read() == Value::High {
+///        println!("the door is open");
+///    }
+///}
+///```
+pub struct ParallelInShiftRegister<P> {
+    load: OutputPin<P>,
+    clock: OutputPin<P>,
+    data: InputPin<P>,
+    values: Arc<Mutex<Vec<u8>>>,
+}
+
+impl<P: Pin> ParallelInShiftRegister<P> {
+    ///Sets up a chain of `registers` shift registers and reads their inputs.
+    ///Returns `None` if `registers` is 0.
+    pub fn new(load: OutputPin<P>, clock: OutputPin<P>, data: InputPin<P>, registers: usize) -> Option<ParallelInShiftRegister<P>> {
+        if registers == 0 {
+            return None;
+        }
+
+        load.digital_write(Value::High);
+        clock.digital_write(Value::Low);
+
+        let mut chain = ParallelInShiftRegister {
+            load,
+            clock,
+            data,
+            values: Arc::new(Mutex::new(vec![0; registers])),
+        };
+
+        chain.poll();
+
+        Some(chain)
+    }
+
+    ///The number of registers in the chain.
+    pub fn registers(&self) -> usize {
+        lock_values(&self.values).len()
+    }
+
+    ///The number of inputs in the chain, which is 8 per register.
+    pub fn inputs(&self) -> usize {
+        self.registers() * 8
+    }
+
+    ///Reads all of the inputs and returns the ones that changed since the
+    ///previous poll.
+    pub fn poll(&mut self) -> Vec<InputChange> {
+        let registers = self.registers();
+        let current = self.read_chain(registers);
+        let mut values = lock_values(&self.values);
+        let changes = changes(&values, &current);
+
+        *values = current;
+        changes
+    }
+
+    ///The level of input number `index` at the latest poll.
+    ///
+    ///Panics if `index` is not less than `inputs()`.
+    pub fn value(&self, index: usize) -> Value {
+        read_bit(&self.values, index)
+    }
+
+    ///The inputs of a register at the latest poll, with D0 in the least
+    ///significant bit.
+    ///
+    ///Panics if `register` is not less than `registers()`.
+    pub fn register(&self, register: usize) -> u8 {
+        lock_values(&self.values)[register]
+    }
+
+    ///A virtual pin for input number `index`, or `None` if there is no such
+    ///input.
+    pub fn pin(&self, index: usize) -> Option<ShiftInputPin> {
+        if index < self.inputs() {
+            Some(ShiftInputPin {
+                values: self.values.clone(),
+                index,
+            })
+        } else {
+            None
+        }
+    }
+
+    ///Virtual pins for all of the inputs, in order.
+    pub fn pins(&self) -> Vec<ShiftInputPin> {
+        (0..self.inputs()).map(|index| ShiftInputPin { values: self.values.clone(), index }).collect()
+    }
+
+    ///Returns the load, clock and data pins.
+    pub fn into_inner(self) -> (OutputPin<P>, OutputPin<P>, InputPin<P>) {
+        (self.load, self.clock, self.data)
+    }
+
+    ///Loads the inputs into the registers and shifts them in. Q7 shows the
+    ///next bit before the rising edge of the clock, so each bit is read
+    ///before pulsing the clock.
+    fn read_chain(&self, registers: usize) -> Vec<u8> {
+        self.load.digital_write(Value::Low);
+        self.load.digital_write(Value::High);
+
+        read_registers(registers, || {
+            let value = self.data.digital_read();
+
+            self.clock.digital_write(Value::High);
+            self.clock.digital_write(Value::Low);
+
+            value
+        })
+    }
+}
+
+///Assembles the inputs of a chain of 74HC165s from the bits returned by
+///`next_bit`. The first register comes first, since it's connected to
+///`data`, and D7 comes first in each register.
+fn read_registers<F: FnMut() -> Value>(registers: usize, mut next_bit: F) -> Vec<u8> {
+    (0..registers).map(|_| shift::from_bits(BitOrder::MsbFirst, (0..8).map(|_| next_bit()))).collect()
+}
+
+///The inputs that are different in `new` than in `old`, in order.
+fn changes(old: &[u8], new: &[u8]) -> Vec<InputChange> {
+    let mut changes = Vec::new();
+
+    for (register, (&old, &new)) in old.iter().zip(new.iter()).enumerate() {
+        let changed = old ^ new;
+
+        for bit in (0..8).filter(|bit| changed & (1 << bit) != 0) {
+            changes.push(InputChange {
+                index: register * 8 + bit,
+                value: if new & (1 << bit) != 0 { Value::High } else { Value::Low },
+            });
+        }
+    }
+
+    changes
+}
+
+impl<P: Pin + Send + 'static> ParallelInShiftRegister<P> {
+    ///Moves the chain to a background thread that polls it every `interval`.
+    pub fn spawn(self, interval: Duration) -> ShiftRegisterPoller<P> {
+        ShiftRegisterPoller {
+            poller: Poller::spawn(self, interval, ParallelInShiftRegister::poll),
+        }
+    }
+}
+
+///A `ParallelInShiftRegister` that is polled on a background thread.
+pub struct ShiftRegisterPoller<P> {
+    poller: Poller<ParallelInShiftRegister<P>, InputChange>,
+}
+
+impl<P> ShiftRegisterPoller<P> {
+    ///The receiver for the input changes.
+    pub fn events(&self) -> &Receiver<InputChange> {
+        self.poller.events()
+    }
+
+    ///Stops the background polling and returns the chain.
+    pub fn stop(self) -> ParallelInShiftRegister<P> {
+        self.poller.stop().expect("the shift register poller thread panicked")
+    }
+}
+
+///One input of a `ParallelInShiftRegister`, which can be used like an
+///`InputPin`. It reads the level from the latest poll of the chain.
+#[derive(Clone)]
+pub struct ShiftInputPin {
+    values: Arc<Mutex<Vec<u8>>>,
+    index: usize,
+}
+
+impl ShiftInputPin {
+    ///The index of the input in the chain.
+    pub fn index(&self) -> usize {
+        self.index
+    }
+
+    ///The level of the input at the latest poll.
+    pub fn digital_read(&self) -> Value {
+        read_bit(&self.values, self.index)
+    }
+}
+
+impl DigitalInput for ShiftInputPin {
+    fn digital_read(&self) -> Value {
+        ShiftInputPin::digital_read(self)
+    }
+}
+
+fn lock_values(values: &Mutex<Vec<u8>>) -> MutexGuard<'_, Vec<u8>> {
+    values.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+fn read_bit(values: &Mutex<Vec<u8>>, index: usize) -> Value {
+    if lock_values(values)[index / 8] & (1 << (index % 8)) != 0 {
+        Value::High
+    } else {
+        Value::Low
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use pin::Value::{self, High, Low};
+    use super::{chain_bits, changes, read_registers, InputChange};
+
+    ///Clocks `bits` into a chain of 74HC595s, where each one shifts Q7 into
+    ///the next one, and returns the outputs.
+    fn shift_into_595s(registers: usize, bits: &[Value]) -> Vec<u8> {
+        let mut outputs = vec![0u8; registers];
+
+        for &value in bits {
+            let mut carry = value == High;
+
+            for register in outputs.iter_mut() {
+                let next = *register & 0x80 != 0;
+                *register = (*register << 1) | carry as u8;
+                carry = next;
+            }
+        }
+
+        outputs
+    }
+
+    #[test]
+    fn output_chain_order() {
+        let bits: Vec<_> = chain_bits(&[0b0000_0001]).collect();
+        assert_eq!(bits, [Low, Low, Low, Low, Low, Low, Low, High]);
+
+        let buffer = [0x01, 0x80, 0xA5];
+        let bits: Vec<_> = chain_bits(&buffer).collect();
+        assert_eq!(bits.len(), 24);
+        assert_eq!(bits[..8], [High, Low, High, Low, Low, High, Low, High]);
+        assert_eq!(shift_into_595s(3, &bits), buffer);
+    }
+
+    #[test]
+    fn input_chain_order() {
+        //Q7 of the first register shows D7 after loading, and each clock
+        //shifts the next register in behind it
+        let inputs = [0b1000_0001u8, 0b0100_0000, 0xFF];
+        let mut chain = inputs.to_vec();
+
+        let values = read_registers(3, || {
+            let value = if chain[0] & 0x80 != 0 { High } else { Low };
+
+            for register in 0..chain.len() {
+                let carry = chain.get(register + 1).map_or(0, |next| next >> 7);
+                chain[register] = (chain[register] << 1) | carry;
+            }
+
+            value
+        });
+
+        assert_eq!(values, inputs);
+    }
+
+    #[test]
+    fn change_detection() {
+        assert_eq!(changes(&[0x00, 0x00], &[0x00, 0x00]), []);
+        assert_eq!(changes(&[0x00, 0xFF], &[0x81, 0x7F]), [
+            InputChange { index: 0, value: High },
+            InputChange { index: 7, value: High },
+            InputChange { index: 15, value: Low },
+        ]);
+    }
+}
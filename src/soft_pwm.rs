@@ -0,0 +1,246 @@
+//! Software PWM with a native timing loop.
+
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+use bindings;
+use libc;
+use pin::{OutputPin, Pin, PwmError, PwmOutput, Value};
+use timing;
+
+///The longest period, which is one hour.
+const MAX_PERIOD_NANOS: u64 = 3_600_000_000_000;
+
+struct Timing {
+    period: AtomicU64,
+    high: AtomicU64,
+}
+
+///A PWM signal on any `OutputPin`, generated by a dedicated thread.
+///
+///Unlike `SoftPwmPin`, which is limited to steps of 100μs, the period and
+///pulse width can be set with nanosecond precision. The thread sleeps until
+///shortly before each edge and spins for the rest of the time, so the
+///accuracy is usually in the range of a few microseconds, but it depends on
+///the system load. Periods that are too short to keep up with are stretched
+///instead of accumulating a delay.
+///
+///```no_run
+///use std::time::Duration;
+///use wiringpi::soft_pwm::NativeSoftPwm;
+///
+///let pi = wiringpi::setup_gpio();
+///let led = NativeSoftPwm::with_frequency(pi.output_pin(17), 1000.0).unwrap();
+///
+///led.set_duty(0.25).unwrap();
+///std::thread::sleep(Duration::from_secs(1));
+///
+///let pin = led.stop();
+///```
+pub struct NativeSoftPwm<P> {
+    pin: OutputPin<P>,
+    timing: Arc<Timing>,
+    _worker: Worker,
+}
+
+impl<P: Pin> NativeSoftPwm<P> {
+    ///Starts a PWM signal with the given `period` on `pin`. The duty cycle
+    ///starts at 0.
+    ///
+    ///Returns `Err(PwmError::FrequencyOutOfRange)` if the period is 0 or
+    ///longer than an hour.
+    pub fn new(pin: OutputPin<P>, period: Duration) -> Result<NativeSoftPwm<P>, PwmError> {
+        let period = period_to_nanos(period)?;
+
+        let timing = Arc::new(Timing {
+            period: AtomicU64::new(period),
+            high: AtomicU64::new(0),
+        });
+
+        let worker = Worker::spawn(pin.number(), timing.clone());
+
+        Ok(NativeSoftPwm {
+            pin,
+            timing,
+            _worker: worker,
+        })
+    }
+
+    ///Starts a PWM signal at `frequency` Hz on `pin`. The duty cycle starts
+    ///at 0.
+    pub fn with_frequency(pin: OutputPin<P>, frequency: f64) -> Result<NativeSoftPwm<P>, PwmError> {
+        NativeSoftPwm::new(pin, frequency_to_period(frequency)?)
+    }
+
+    #[inline]
+    pub fn number(&self) -> libc::c_int {
+        self.pin.number()
+    }
+
+    ///The length of a PWM period.
+    pub fn period(&self) -> Duration {
+        Duration::from_nanos(self.timing.period.load(Ordering::SeqCst))
+    }
+
+    ///The PWM frequency in Hz.
+    pub fn frequency(&self) -> f64 {
+        1_000_000_000.0 / self.timing.period.load(Ordering::SeqCst) as f64
+    }
+
+    ///The current duty cycle as a fraction in the interval [0, 1].
+    pub fn duty(&self) -> f64 {
+        self.timing.high.load(Ordering::SeqCst) as f64 / self.timing.period.load(Ordering::SeqCst) as f64
+    }
+
+    ///Sets the duty cycle as a fraction in the interval [0, 1], where `0` is
+    ///a constant low and `1` is a constant high.
+    pub fn set_duty(&self, duty: f64) -> Result<(), PwmError> {
+        if !(0.0..=1.0).contains(&duty) {
+            return Err(PwmError::DutyOutOfRange);
+        }
+
+        let period = self.timing.period.load(Ordering::SeqCst);
+        self.timing.high.store((duty * period as f64).round() as u64, Ordering::SeqCst);
+        Ok(())
+    }
+
+    ///Sets the length of the high pulse in each period.
+    pub fn set_pulse_width(&self, width: Duration) -> Result<(), PwmError> {
+        let width = timing::duration_to_nanos(width);
+
+        if width > self.timing.period.load(Ordering::SeqCst) {
+            return Err(PwmError::PulseTooLong);
+        }
+
+        self.timing.high.store(width, Ordering::SeqCst);
+        Ok(())
+    }
+
+    ///Changes the length of a PWM period, while keeping the duty cycle. The
+    ///period can't be 0 or longer than an hour.
+    pub fn set_period(&self, period: Duration) -> Result<(), PwmError> {
+        let period = period_to_nanos(period)?;
+
+        let duty = self.duty();
+        self.timing.period.store(period, Ordering::SeqCst);
+        self.timing.high.store((duty * period as f64).round() as u64, Ordering::SeqCst);
+        Ok(())
+    }
+
+    ///Changes the PWM frequency, while keeping the duty cycle.
+    pub fn set_frequency(&self, frequency: f64) -> Result<(), PwmError> {
+        self.set_period(frequency_to_period(frequency)?)
+    }
+
+    ///Stops the PWM signal, leaving the pin low, and returns the underlying
+    ///pin.
+    pub fn stop(self) -> OutputPin<P> {
+        let NativeSoftPwm { pin, .. } = self;
+        pin
+    }
+}
+
+impl<P: Pin> PwmOutput for NativeSoftPwm<P> {
+    fn period(&self) -> Duration {
+        NativeSoftPwm::period(self)
+    }
+
+    fn set_duty(&self, duty: f64) -> Result<(), PwmError> {
+        NativeSoftPwm::set_duty(self, duty)
+    }
+
+    fn set_pulse_width(&self, width: Duration) -> Result<(), PwmError> {
+        NativeSoftPwm::set_pulse_width(self, width)
+    }
+}
+
+struct Worker {
+    stop: Arc<AtomicBool>,
+    handle: Option<JoinHandle<()>>,
+}
+
+impl Worker {
+    fn spawn(pin: libc::c_int, timing: Arc<Timing>) -> Worker {
+        let stop = Arc::new(AtomicBool::new(false));
+        let thread_stop = stop.clone();
+
+        let handle = thread::spawn(move || {
+            let mut level = Value::Low;
+            write(pin, level);
+
+            let mut period_start = Instant::now();
+
+            while !thread_stop.load(Ordering::SeqCst) {
+                let period = timing.period.load(Ordering::SeqCst);
+                let high = timing.high.load(Ordering::SeqCst).min(period);
+
+                if high > 0 && level != Value::High {
+                    level = Value::High;
+                    write(pin, level);
+                }
+
+                if high > 0 && high < period && !timing::delay_until_or_stop(period_start + Duration::from_nanos(high), &thread_stop) {
+                    break;
+                }
+
+                if high < period && level != Value::Low {
+                    level = Value::Low;
+                    write(pin, level);
+                }
+
+                let now = Instant::now();
+
+                match period_start.checked_add(Duration::from_nanos(period)) {
+                    Some(next) if next >= now => {
+                        period_start = next;
+                        timing::delay_until_or_stop(next, &thread_stop);
+                    },
+                    _ => period_start = now,
+                }
+            }
+
+            write(pin, Value::Low);
+        });
+
+        Worker {
+            stop,
+            handle: Some(handle),
+        }
+    }
+}
+
+impl Drop for Worker {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::SeqCst);
+
+        if let Some(handle) = self.handle.take() {
+            let _ = handle.join();
+        }
+    }
+}
+
+fn write(pin: libc::c_int, value: Value) {
+    unsafe {
+        bindings::digitalWrite(pin, value as libc::c_int);
+    }
+}
+
+fn frequency_to_period(frequency: f64) -> Result<Duration, PwmError> {
+    if !(frequency > 0.0 && frequency <= 1_000_000_000.0) {
+        return Err(PwmError::FrequencyOutOfRange);
+    }
+
+    Ok(Duration::from_nanos((1_000_000_000.0 / frequency).round() as u64))
+}
+
+fn period_to_nanos(period: Duration) -> Result<u64, PwmError> {
+    let period = timing::duration_to_nanos(period);
+
+    if period == 0 || period > MAX_PERIOD_NANOS {
+        Err(PwmError::FrequencyOutOfRange)
+    } else {
+        Ok(period)
+    }
+}
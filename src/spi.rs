@@ -0,0 +1,461 @@
+//! The SPI bus.
+
+use std::error::Error;
+use std::fmt;
+use std::io;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use bindings;
+use libc;
+
+///The two chip enable lines of the SPI bus on the header.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SpiChannel {
+    ///CE0, on BCM_GPIO 8.
+    Ce0 = 0,
+    ///CE1, on BCM_GPIO 7.
+    Ce1
+}
+
+///The clock polarity and phase. Check the datasheet of the device to find
+///out which one it uses.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SpiMode {
+    ///The clock idles low and data is sampled on the rising edge.
+    Mode0 = 0,
+    ///The clock idles low and data is sampled on the falling edge.
+    Mode1,
+    ///The clock idles high and data is sampled on the falling edge.
+    Mode2,
+    ///The clock idles high and data is sampled on the rising edge.
+    Mode3
+}
+
+///The ways using the SPI bus can fail.
+#[derive(Debug)]
+pub enum SpiError {
+    ///Another `SpiBus` is already using the channel.
+    ChannelInUse,
+    ///The speed is 0 or too high.
+    InvalidSpeed,
+    ///The buffer is longer than the buffer of the spidev driver, so it
+    ///can't be sent in a single transfer.
+    TransferTooLong,
+    ///The SPI device couldn't be opened or the transfer failed.
+    Io(io::Error),
+}
+
+impl fmt::Display for SpiError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            SpiError::ChannelInUse => f.write_str("the SPI channel is already in use"),
+            SpiError::InvalidSpeed => f.write_str("the SPI speed is 0 or too high"),
+            SpiError::TransferTooLong => f.write_str("the buffer is too long for a single SPI transfer"),
+            SpiError::Io(ref error) => write!(f, "SPI error: {}", error),
+        }
+    }
+}
+
+impl Error for SpiError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match *self {
+            SpiError::Io(ref error) => Some(error),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for SpiError {
+    fn from(error: io::Error) -> SpiError {
+        SpiError::Io(error)
+    }
+}
+
+///The default size of the spidev buffer, which limits the length of a
+///single transfer. It can be changed with the `bufsiz` parameter of the
+///spidev module.
+pub const DEFAULT_BUFSIZ: usize = 4096;
+
+///Set for each channel that is used by an `SpiBus`.
+static CHANNELS: [AtomicBool; 2] = [const { AtomicBool::new(false) }; 2];
+
+///One channel of the SPI bus, where the device is selected with its chip
+///enable line.
+///
+///In development mode, the transfers go to devices that are attached with
+///`spi::simulation::attach`.
+///
+///```no_run
+///use wiringpi::spi::{SpiBus, SpiChannel, SpiMode};
+///
+///let adc = SpiBus::new(SpiChannel::Ce0, 1_000_000, SpiMode::Mode0).unwrap();
+///
+///let mut data = [0x01, 0x80, 0x00];
+///adc.transfer(&mut data).unwrap();
+///println!("channel 0: {}", ((data[1] as u16 & 0x03) << 8) | data[2] as u16);
+///```
+pub struct SpiBus {
+    channel: SpiChannel,
+    speed: u32,
+    mode: SpiMode,
+    max_transfer_len: usize,
+    open: bool,
+}
+
+impl SpiBus {
+    ///Opens `channel` with the clock `speed` in Hz and `mode`.
+    ///
+    ///This fails if the channel is already in use, or if the SPI device
+    ///can't be opened, which usually means that SPI is disabled.
+    pub fn new(channel: SpiChannel, speed: u32, mode: SpiMode) -> Result<SpiBus, SpiError> {
+        check_speed(speed)?;
+
+        if CHANNELS[channel as usize].swap(true, Ordering::SeqCst) {
+            return Err(SpiError::ChannelInUse);
+        }
+
+        let mut bus = SpiBus {
+            channel,
+            speed,
+            mode,
+            max_transfer_len: backend::bufsiz(),
+            open: false,
+        };
+
+        //The channel is released by drop if this fails
+        bus.setup()?;
+
+        Ok(bus)
+    }
+
+    pub fn channel(&self) -> SpiChannel {
+        self.channel
+    }
+
+    ///The clock speed in Hz.
+    pub fn speed(&self) -> u32 {
+        self.speed
+    }
+
+    pub fn mode(&self) -> SpiMode {
+        self.mode
+    }
+
+    ///The maximum number of bytes in a single transfer, which is the
+    ///`bufsiz` parameter of the spidev module.
+    pub fn max_transfer_len(&self) -> usize {
+        self.max_transfer_len
+    }
+
+    ///Changes the clock speed in Hz.
+    pub fn set_speed(&mut self, speed: u32) -> Result<(), SpiError> {
+        check_speed(speed)?;
+        self.speed = speed;
+        self.reopen()
+    }
+
+    pub fn set_mode(&mut self, mode: SpiMode) -> Result<(), SpiError> {
+        self.mode = mode;
+        self.reopen()
+    }
+
+    ///Sends the bytes in `data` while receiving the same number of bytes,
+    ///which replace them.
+    ///
+    ///`data` can't be longer than `max_transfer_len`, which is 4096 bytes
+    ///unless spidev is configured differently.
+    pub fn transfer(&self, data: &mut [u8]) -> Result<(), SpiError> {
+        check_len(data.len(), self.max_transfer_len)?;
+
+        let result = unsafe {
+            bindings::wiringPiSPIDataRW(self.channel as libc::c_int, data.as_mut_ptr(), data.len() as libc::c_int)
+        };
+
+        if result < 0 {
+            Err(transfer_error(io::Error::last_os_error()))
+        } else {
+            Ok(())
+        }
+    }
+
+    ///Sends the bytes in `data`, ignoring what is received.
+    pub fn write(&self, data: &[u8]) -> Result<(), SpiError> {
+        self.transfer(&mut data.to_vec())
+    }
+
+    ///Receives `buffer.len()` bytes, while sending zeros.
+    pub fn read(&self, buffer: &mut [u8]) -> Result<(), SpiError> {
+        for byte in buffer.iter_mut() {
+            *byte = 0;
+        }
+
+        self.transfer(buffer)
+    }
+
+    fn setup(&mut self) -> Result<(), SpiError> {
+        backend::check_access(self.channel)?;
+
+        let result = unsafe {
+            bindings::wiringPiSPISetupMode(self.channel as libc::c_int, self.speed as libc::c_int, self.mode as libc::c_int)
+        };
+
+        if result < 0 {
+            Err(SpiError::Io(io::Error::last_os_error()))
+        } else {
+            self.open = true;
+            Ok(())
+        }
+    }
+
+    ///wiringPi opens the device again for each setup, so the old file has
+    ///to be closed first.
+    fn reopen(&mut self) -> Result<(), SpiError> {
+        self.close();
+        self.setup()
+    }
+
+    fn close(&mut self) {
+        if self.open {
+            backend::close(self.channel);
+            self.open = false;
+        }
+    }
+}
+
+impl Drop for SpiBus {
+    fn drop(&mut self) {
+        self.close();
+        CHANNELS[self.channel as usize].store(false, Ordering::SeqCst);
+    }
+}
+
+fn check_speed(speed: u32) -> Result<(), SpiError> {
+    if speed == 0 || speed > i32::MAX as u32 {
+        Err(SpiError::InvalidSpeed)
+    } else {
+        Ok(())
+    }
+}
+
+fn check_len(len: usize, max: usize) -> Result<(), SpiError> {
+    if len > max || len > i32::MAX as usize {
+        Err(SpiError::TransferTooLong)
+    } else {
+        Ok(())
+    }
+}
+
+///spidev fails with `EMSGSIZE` if the transfer doesn't fit in its buffer,
+///which can still happen if `bufsiz` couldn't be read.
+fn transfer_error(error: io::Error) -> SpiError {
+    if error.raw_os_error() == Some(libc::EMSGSIZE) {
+        SpiError::TransferTooLong
+    } else {
+        SpiError::Io(error)
+    }
+}
+
+///Parses the contents of the `bufsiz` parameter file.
+#[cfg_attr(feature = "development", allow(dead_code))]
+fn parse_bufsiz(contents: &str) -> Option<usize> {
+    match contents.trim().parse() {
+        Ok(0) | Err(_) => None,
+        Ok(bufsiz) => Some(bufsiz),
+    }
+}
+
+#[cfg(not(feature = "development"))]
+mod backend {
+    use std::ffi::CString;
+    use std::fs;
+    use std::io;
+
+    use bindings;
+    use libc;
+
+    use super::SpiChannel;
+
+    ///wiringPi exits the process if the device can't be opened, so it's
+    ///checked first.
+    pub fn check_access(channel: SpiChannel) -> io::Result<()> {
+        let path = CString::new(format!("/dev/spidev0.{}", channel as u8)).expect("the path has no zero bytes");
+
+        if unsafe { libc::access(path.as_ptr(), libc::R_OK | libc::W_OK) } < 0 {
+            Err(io::Error::last_os_error())
+        } else {
+            Ok(())
+        }
+    }
+
+    ///Reads the buffer size of spidev, or falls back to the default if the
+    ///parameter isn't available.
+    pub fn bufsiz() -> usize {
+        fs::read_to_string("/sys/module/spidev/parameters/bufsiz").ok()
+            .and_then(|contents| super::parse_bufsiz(&contents))
+            .unwrap_or(super::DEFAULT_BUFSIZ)
+    }
+
+    pub fn close(channel: SpiChannel) {
+        unsafe {
+            let fd = bindings::wiringPiSPIGetFd(channel as libc::c_int);
+
+            if fd > 0 {
+                libc::close(fd);
+            }
+        }
+    }
+}
+
+#[cfg(feature = "development")]
+mod backend {
+    use std::io;
+
+    use super::SpiChannel;
+
+    pub fn check_access(_channel: SpiChannel) -> io::Result<()> {
+        Ok(())
+    }
+
+    pub fn bufsiz() -> usize {
+        super::DEFAULT_BUFSIZ
+    }
+
+    pub fn close(_channel: SpiChannel) {}
+}
+
+///Simulated SPI devices for development mode.
+///
+///Each channel can have one device attached. Transfers on channels without
+///a device receive zeros.
+///
+///```
+///use wiringpi::spi::{SpiBus, SpiChannel, SpiMode};
+///use wiringpi::spi::simulation::{self, Loopback};
+///
+///simulation::attach(SpiChannel::Ce1, Loopback);
+///
+///let bus = SpiBus::new(SpiChannel::Ce1, 500_000, SpiMode::Mode0).unwrap();
+///let mut data = [1, 2, 3];
+///bus.transfer(&mut data).unwrap();
+///assert_eq!(data, [1, 2, 3]);
+///```
+#[cfg(feature = "development")]
+pub mod simulation {
+    use std::sync::{Arc, Mutex, MutexGuard};
+
+    use libc;
+
+    use super::SpiChannel;
+
+    ///A simulated device on the SPI bus.
+    pub trait SpiSlave: Send {
+        ///Receives the bytes that are sent in `data` and replaces them with
+        ///the bytes to send back.
+        fn transfer(&mut self, data: &mut [u8]);
+    }
+
+    impl<S: SpiSlave> SpiSlave for Arc<Mutex<S>> {
+        fn transfer(&mut self, data: &mut [u8]) {
+            self.lock().unwrap_or_else(|e| e.into_inner()).transfer(data);
+        }
+    }
+
+    ///Sends back what it receives, like a wire between MOSI and MISO.
+    #[derive(Debug, Clone, Copy, Default)]
+    pub struct Loopback;
+
+    impl SpiSlave for Loopback {
+        fn transfer(&mut self, _data: &mut [u8]) {}
+    }
+
+    static SLAVES: Mutex<[Option<Box<dyn SpiSlave>>; 2]> = Mutex::new([None, None]);
+
+    fn lock_slaves() -> MutexGuard<'static, [Option<Box<dyn SpiSlave>>; 2]> {
+        SLAVES.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    ///Attaches `slave` to `channel`, replacing any device that was there
+    ///before.
+    pub fn attach<S: SpiSlave + 'static>(channel: SpiChannel, slave: S) {
+        lock_slaves()[channel as usize] = Some(Box::new(slave));
+    }
+
+    ///Removes the device from `channel`. Returns `false` if there was no
+    ///device.
+    pub fn detach(channel: SpiChannel) -> bool {
+        lock_slaves()[channel as usize].take().is_some()
+    }
+
+    ///Simulates `wiringPiSPIDataRW`.
+    pub(crate) unsafe fn data_rw(channel: libc::c_int, data: *mut libc::c_uchar, len: libc::c_int) -> libc::c_int {
+        let data = ::std::slice::from_raw_parts_mut(data, len as usize);
+
+        match lock_slaves()[(channel & 1) as usize] {
+            Some(ref mut slave) => slave.transfer(data),
+            None => {
+                for byte in data.iter_mut() {
+                    *byte = 0;
+                }
+            },
+        }
+
+        len
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io;
+
+    use libc;
+    use super::{check_len, parse_bufsiz, transfer_error, SpiError, DEFAULT_BUFSIZ};
+
+    #[test]
+    fn bufsiz_parameter() {
+        assert_eq!(parse_bufsiz("4096\n"), Some(4096));
+        assert_eq!(parse_bufsiz("65536"), Some(65536));
+        assert_eq!(parse_bufsiz("0\n"), None);
+        assert_eq!(parse_bufsiz(""), None);
+        assert_eq!(parse_bufsiz("-1"), None);
+    }
+
+    #[test]
+    fn transfer_length() {
+        assert!(check_len(0, DEFAULT_BUFSIZ).is_ok());
+        assert!(check_len(DEFAULT_BUFSIZ, DEFAULT_BUFSIZ).is_ok());
+        assert!(matches!(check_len(DEFAULT_BUFSIZ + 1, DEFAULT_BUFSIZ), Err(SpiError::TransferTooLong)));
+        assert!(matches!(check_len(i32::MAX as usize + 1, usize::MAX), Err(SpiError::TransferTooLong)));
+    }
+
+    #[test]
+    fn message_size_error() {
+        let error = transfer_error(io::Error::from_raw_os_error(libc::EMSGSIZE));
+        assert!(matches!(error, SpiError::TransferTooLong));
+
+        let error = transfer_error(io::Error::from_raw_os_error(libc::EIO));
+        assert!(matches!(error, SpiError::Io(_)));
+    }
+}
+
+#[cfg(all(test, feature = "development"))]
+mod simulation_tests {
+    use super::{SpiBus, SpiChannel, SpiError, SpiMode, DEFAULT_BUFSIZ};
+    use super::simulation::{self, Loopback};
+
+    #[test]
+    fn transfer_limit() {
+        simulation::attach(SpiChannel::Ce0, Loopback);
+        let bus = SpiBus::new(SpiChannel::Ce0, 1_000_000, SpiMode::Mode0).unwrap();
+        assert_eq!(bus.max_transfer_len(), DEFAULT_BUFSIZ);
+
+        let mut data = vec![0x5a; DEFAULT_BUFSIZ];
+        bus.transfer(&mut data).unwrap();
+        assert!(data.iter().all(|&byte| byte == 0x5a));
+
+        let mut data = vec![0; DEFAULT_BUFSIZ + 1];
+        assert!(matches!(bus.transfer(&mut data), Err(SpiError::TransferTooLong)));
+        assert!(matches!(bus.write(&data), Err(SpiError::TransferTooLong)));
+
+        simulation::detach(SpiChannel::Ce0);
+    }
+}
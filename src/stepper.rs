@@ -0,0 +1,539 @@
+//! Stepper motor control.
+
+use std::error::Error;
+use std::fmt;
+use std::sync::{Arc, Condvar, Mutex, MutexGuard};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+use bindings;
+use pin::{OutputPin, Pin, Value};
+use timing;
+
+///The coil patterns for half-stepping, in the order of the coil pins. Wave
+///drive uses the even entries and full-stepping the odd entries.
+const SEQUENCE: [[bool; 4]; 8] = [
+    [true, false, false, false],
+    [true, true, false, false],
+    [false, true, false, false],
+    [false, true, true, false],
+    [false, false, true, false],
+    [false, false, true, true],
+    [false, false, false, true],
+    [true, false, false, true],
+];
+
+///The width of the pulses on the STEP pin, and the setup time after a
+///direction change, in microseconds.
+const STEP_PULSE_MICROS: u32 = 2;
+
+///How a motor is stepped through its coil patterns.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StepMode {
+    ///Energize one coil at a time. Uses the least power.
+    Wave,
+    ///Energize two coils at a time. Gives the most torque.
+    Full,
+    ///Alternate between one and two coils, doubling the resolution.
+    Half,
+}
+
+///How the motor is connected.
+pub enum Drive<P> {
+    ///Four pins that drive the coils through transistors or a driver like
+    ///the ULN2003, in the order A, B, A', B'.
+    Coils { pins: [OutputPin<P>; 4], mode: StepMode },
+
+    ///A driver chip like the A4988 or DRV8825, which takes a step on each
+    ///rising edge of `step`. `direction` is low for forward steps.
+    StepDir { step: OutputPin<P>, direction: OutputPin<P> },
+}
+
+///The ways configuring a stepper can fail.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StepperError {
+    ///The speed is not a finite number, or not positive where a maximum
+    ///speed is expected.
+    SpeedOutOfRange,
+    ///The acceleration is negative, zero or not a finite number.
+    AccelerationOutOfRange,
+}
+
+impl fmt::Display for StepperError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let message = match *self {
+            StepperError::SpeedOutOfRange => "the speed is out of range",
+            StepperError::AccelerationOutOfRange => "the acceleration has to be positive",
+        };
+
+        f.write_str(message)
+    }
+}
+
+impl Error for StepperError {}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Goal {
+    Idle,
+    Position(i64),
+    Speed(f64),
+}
+
+struct Status {
+    position: i64,
+    speed: f64,
+    goal: Goal,
+    max_speed: f64,
+    acceleration: Option<f64>,
+    release: bool,
+    exit: bool,
+}
+
+impl Status {
+    ///The speed for the next step, `Some(0.0)` if the motor has to come to
+    ///rest before it can change direction, or `None` if the goal is reached.
+    fn next_speed(&self) -> Option<f64> {
+        let speed = self.speed;
+
+        let target = match self.goal {
+            Goal::Idle => return None,
+            Goal::Speed(target) => {
+                if target == 0.0 && speed == 0.0 {
+                    return None;
+                }
+
+                target.max(-self.max_speed).min(self.max_speed)
+            },
+            Goal::Position(position) => {
+                let distance = position - self.position;
+                let stop_steps = self.acceleration.map_or(0.0, |acceleration| speed * speed / (2.0 * acceleration));
+
+                if distance == 0 && stop_steps < 1.0 {
+                    return None;
+                }
+
+                let heading = distance.signum() as f64;
+
+                if speed * heading >= 0.0 && stop_steps < distance.abs() as f64 {
+                    heading * self.max_speed
+                } else {
+                    0.0
+                }
+            },
+        };
+
+        let acceleration = match self.acceleration {
+            Some(acceleration) => acceleration,
+            None => return Some(target),
+        };
+
+        if speed == 0.0 || (speed * target > 0.0 && target.abs() > speed.abs()) {
+            let faster = (speed * speed + 2.0 * acceleration).sqrt().min(target.abs());
+            Some(faster.copysign(target))
+        } else {
+            let slower = speed * speed - 2.0 * acceleration;
+
+            // Below the speed of the first step from rest, the motor might as
+            // well stop.
+            if slower < 2.0 * acceleration {
+                Some(0.0)
+            } else if speed * target > 0.0 {
+                Some(slower.sqrt().max(target.abs()).copysign(speed))
+            } else {
+                Some(slower.sqrt().copysign(speed))
+            }
+        }
+    }
+}
+
+struct Shared {
+    status: Mutex<Status>,
+    changed: Condvar,
+}
+
+impl Shared {
+    fn lock(&self) -> MutexGuard<'_, Status> {
+        self.status.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn update<T, F: FnOnce(&mut Status) -> T>(&self, f: F) -> T {
+        let result = f(&mut self.lock());
+        self.changed.notify_all();
+        result
+    }
+}
+
+///A stepper motor, moved by a dedicated timing thread.
+///
+///The thread sleeps until shortly before each step and uses
+///`delayMicroseconds` for the rest of the wait. Movements are either
+///constant speed, or trapezoidal if an acceleration is set, where the motor
+///accelerates up to the maximum speed and decelerates in time to stop at the
+///target position. Speeds are in steps per second, which are half-steps in
+///`StepMode::Half`.
+///
+///All movement commands return immediately. `wait()` blocks until the motor
+///has stopped.
+///
+///```no_run
+///use wiringpi::stepper::{Stepper, StepMode};
+///
+///let pi = wiringpi::setup_gpio();
+///let pins = [pi.output_pin(17), pi.output_pin(18), pi.output_pin(27), pi.output_pin(22)];
+///let stepper = Stepper::coils(pins, StepMode::Half);
+///
+///stepper.set_max_speed(800.0).unwrap();
+///stepper.set_acceleration(Some(1600.0)).unwrap();
+///
+///stepper.move_to(4096);
+///stepper.wait();
+///stepper.release();
+///```
+pub struct Stepper<P> {
+    shared: Arc<Shared>,
+    handle: Option<JoinHandle<Drive<P>>>,
+}
+
+impl<P: Pin + Send + 'static> Stepper<P> {
+    ///Starts the timing thread for a motor connected through `drive`. The
+    ///motor starts at position 0 with a maximum speed of 100 steps per
+    ///second and no acceleration limit.
+    pub fn new(drive: Drive<P>) -> Stepper<P> {
+        let shared = Arc::new(Shared {
+            status: Mutex::new(Status {
+                position: 0,
+                speed: 0.0,
+                goal: Goal::Idle,
+                max_speed: 100.0,
+                acceleration: None,
+                release: false,
+                exit: false,
+            }),
+            changed: Condvar::new(),
+        });
+
+        let thread_shared = shared.clone();
+        let handle = thread::spawn(move || run(Driver::new(drive), &thread_shared));
+
+        Stepper {
+            shared,
+            handle: Some(handle),
+        }
+    }
+
+    ///Creates a stepper that drives the four coil pins directly.
+    pub fn coils(pins: [OutputPin<P>; 4], mode: StepMode) -> Stepper<P> {
+        Stepper::new(Drive::Coils { pins, mode })
+    }
+
+    ///Creates a stepper that is controlled through a STEP/DIR driver chip.
+    pub fn step_dir(step: OutputPin<P>, direction: OutputPin<P>) -> Stepper<P> {
+        Stepper::new(Drive::StepDir { step, direction })
+    }
+}
+
+impl<P> Stepper<P> {
+    ///The current position in steps.
+    pub fn position(&self) -> i64 {
+        self.shared.lock().position
+    }
+
+    ///Changes the current position without moving, for example after
+    ///homing.
+    pub fn set_position(&self, position: i64) {
+        self.shared.update(|status| status.position = position)
+    }
+
+    ///The current speed in steps per second, where negative values are
+    ///backward.
+    pub fn speed(&self) -> f64 {
+        self.shared.lock().speed
+    }
+
+    ///Checks if the motor is moving or has a movement to do.
+    pub fn is_moving(&self) -> bool {
+        self.shared.lock().goal != Goal::Idle
+    }
+
+    ///Sets the maximum speed in steps per second.
+    pub fn set_max_speed(&self, speed: f64) -> Result<(), StepperError> {
+        if !(speed.is_finite() && speed > 0.0) {
+            return Err(StepperError::SpeedOutOfRange);
+        }
+
+        self.shared.update(|status| status.max_speed = speed);
+        Ok(())
+    }
+
+    ///Sets the acceleration and deceleration in steps per second squared,
+    ///or `None` to change speed instantly.
+    pub fn set_acceleration(&self, acceleration: Option<f64>) -> Result<(), StepperError> {
+        if let Some(acceleration) = acceleration {
+            if !(acceleration.is_finite() && acceleration > 0.0) {
+                return Err(StepperError::AccelerationOutOfRange);
+            }
+        }
+
+        self.shared.update(|status| status.acceleration = acceleration);
+        Ok(())
+    }
+
+    ///Moves to the absolute `position`.
+    pub fn move_to(&self, position: i64) {
+        self.shared.update(|status| status.goal = Goal::Position(position))
+    }
+
+    ///Moves by `steps` relative to the current position.
+    pub fn move_by(&self, steps: i64) {
+        self.shared.update(|status| status.goal = Goal::Position(status.position.saturating_add(steps)))
+    }
+
+    ///Runs at a constant `speed` in steps per second, until another command
+    ///is given. Negative speeds run backward and the speed is limited to the
+    ///maximum speed.
+    pub fn run(&self, speed: f64) -> Result<(), StepperError> {
+        if !speed.is_finite() {
+            return Err(StepperError::SpeedOutOfRange);
+        }
+
+        self.shared.update(|status| status.goal = Goal::Speed(speed));
+        Ok(())
+    }
+
+    ///Decelerates to a stop.
+    pub fn stop(&self) {
+        self.shared.update(|status| status.goal = Goal::Speed(0.0))
+    }
+
+    ///Stops immediately, without decelerating.
+    pub fn halt(&self) {
+        self.shared.update(|status| {
+            status.goal = Goal::Idle;
+            status.speed = 0.0;
+        })
+    }
+
+    ///Stops immediately and turns off the coils, which lets the motor turn
+    ///freely. The coils are turned on again with the next step. This does
+    ///nothing for STEP/DIR drivers.
+    pub fn release(&self) {
+        self.shared.update(|status| {
+            status.goal = Goal::Idle;
+            status.speed = 0.0;
+            status.release = true;
+        })
+    }
+
+    ///Blocks the current thread until the motor has stopped.
+    pub fn wait(&self) {
+        let mut status = self.shared.lock();
+
+        while status.goal != Goal::Idle && !status.exit {
+            status = self.shared.changed.wait(status).unwrap_or_else(|e| e.into_inner());
+        }
+    }
+
+    ///Stops the motor and the timing thread, and returns the pins.
+    pub fn into_inner(mut self) -> Drive<P> {
+        self.join().expect("the stepper thread panicked")
+    }
+
+    fn join(&mut self) -> Option<Drive<P>> {
+        self.shared.update(|status| status.exit = true);
+        self.handle.take().and_then(|handle| handle.join().ok())
+    }
+}
+
+impl<P> Drop for Stepper<P> {
+    fn drop(&mut self) {
+        self.join();
+    }
+}
+
+struct Driver<P> {
+    drive: Drive<P>,
+    phase: usize,
+    forward: Option<bool>,
+}
+
+impl<P: Pin> Driver<P> {
+    fn new(drive: Drive<P>) -> Driver<P> {
+        let phase = match drive {
+            Drive::Coils { mode: StepMode::Full, .. } => 1,
+            _ => 0,
+        };
+
+        Driver {
+            drive,
+            phase,
+            forward: None,
+        }
+    }
+
+    fn step(&mut self, forward: bool) {
+        match self.drive {
+            Drive::Coils { ref pins, mode } => {
+                let stride = if mode == StepMode::Half { 1 } else { 2 };
+                let stride = if forward { stride } else { SEQUENCE.len() - stride };
+                self.phase = (self.phase + stride) % SEQUENCE.len();
+
+                for (pin, &on) in pins.iter().zip(SEQUENCE[self.phase].iter()) {
+                    pin.digital_write(if on { Value::High } else { Value::Low });
+                }
+            },
+            Drive::StepDir { ref step, ref direction } => {
+                if self.forward != Some(forward) {
+                    direction.digital_write(if forward { Value::Low } else { Value::High });
+                    self.forward = Some(forward);
+                    delay_micros(STEP_PULSE_MICROS);
+                }
+
+                step.digital_write(Value::High);
+                delay_micros(STEP_PULSE_MICROS);
+                step.digital_write(Value::Low);
+            },
+        }
+    }
+
+    fn release(&mut self) {
+        if let Drive::Coils { ref pins, .. } = self.drive {
+            for pin in pins {
+                pin.digital_write(Value::Low);
+            }
+        }
+    }
+}
+
+fn run<P: Pin>(mut driver: Driver<P>, shared: &Shared) -> Drive<P> {
+    let mut last_step = Instant::now();
+    let mut status = shared.lock();
+
+    loop {
+        if status.exit {
+            driver.release();
+            return driver.drive;
+        }
+
+        if status.release {
+            driver.release();
+            status.release = false;
+        }
+
+        let speed = match status.next_speed() {
+            Some(speed) => speed,
+            None => {
+                if status.goal != Goal::Idle {
+                    status.goal = Goal::Idle;
+                    shared.changed.notify_all();
+                }
+
+                status.speed = 0.0;
+                status = shared.changed.wait(status).unwrap_or_else(|e| e.into_inner());
+                last_step = Instant::now();
+                continue;
+            },
+        };
+
+        if speed == 0.0 {
+            status.speed = 0.0;
+            continue;
+        }
+
+        let deadline = last_step + Duration::from_nanos((1_000_000_000.0 / speed.abs()) as u64);
+        let now = Instant::now();
+
+        // Wait on the condition variable for long intervals, so that new
+        // commands are picked up before the step.
+        if deadline > now + Duration::from_millis(2) {
+            let timeout = deadline - now - Duration::from_millis(1);
+            status = shared.changed.wait_timeout(status, timeout).unwrap_or_else(|e| e.into_inner()).0;
+            continue;
+        }
+
+        drop(status);
+        timing::delay_until(deadline);
+        driver.step(speed > 0.0);
+
+        let now = Instant::now();
+        last_step = if now > deadline + Duration::from_millis(1) { now } else { deadline };
+
+        status = shared.lock();
+        status.position += if speed > 0.0 { 1 } else { -1 };
+        status.speed = speed;
+    }
+}
+
+fn delay_micros(micros: u32) {
+    unsafe {
+        bindings::delayMicroseconds(micros);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Goal, Status};
+
+    fn status(position: i64, speed: f64, goal: Goal, acceleration: Option<f64>) -> Status {
+        Status {
+            position,
+            speed,
+            goal,
+            max_speed: 100.0,
+            acceleration,
+            release: false,
+            exit: false,
+        }
+    }
+
+    fn assert_speed(speed: Option<f64>, expected: f64) {
+        let speed = speed.expect("the goal should not be reached");
+        assert!((speed - expected).abs() < 1e-9, "expected {}, got {}", expected, speed);
+    }
+
+    #[test]
+    fn reached_goals() {
+        assert_eq!(status(0, 0.0, Goal::Idle, None).next_speed(), None);
+        assert_eq!(status(0, 0.0, Goal::Speed(0.0), Some(100.0)).next_speed(), None);
+        assert_eq!(status(10, 0.0, Goal::Position(10), None).next_speed(), None);
+        assert_eq!(status(10, 14.0, Goal::Position(10), Some(100.0)).next_speed(), None);
+    }
+
+    #[test]
+    fn constant_speed() {
+        assert_speed(status(0, 0.0, Goal::Speed(500.0), None).next_speed(), 100.0);
+        assert_speed(status(0, 0.0, Goal::Speed(-50.0), None).next_speed(), -50.0);
+        assert_speed(status(0, 0.0, Goal::Position(10), None).next_speed(), 100.0);
+        assert_speed(status(0, 0.0, Goal::Position(-10), None).next_speed(), -100.0);
+    }
+
+    #[test]
+    fn acceleration() {
+        let far = Goal::Position(1000);
+
+        assert_speed(status(0, 0.0, far, Some(100.0)).next_speed(), 200.0f64.sqrt());
+        assert_speed(status(0, 50.0, far, Some(100.0)).next_speed(), 2700.0f64.sqrt());
+        assert_speed(status(0, 99.9, far, Some(100.0)).next_speed(), 100.0);
+        assert_speed(status(0, 100.0, far, Some(100.0)).next_speed(), 100.0);
+        assert_speed(status(0, 0.0, Goal::Speed(-100.0), Some(100.0)).next_speed(), -(200.0f64.sqrt()));
+    }
+
+    #[test]
+    fn deceleration() {
+        //Stopping from 100 steps/s takes 50 steps at 100 steps/s²
+        assert_speed(status(0, 100.0, Goal::Position(51), Some(100.0)).next_speed(), 100.0);
+        assert_speed(status(0, 100.0, Goal::Position(40), Some(100.0)).next_speed(), 9800.0f64.sqrt());
+        assert_speed(status(0, 14.0, Goal::Position(1), Some(100.0)).next_speed(), 396.0f64.sqrt());
+        assert_speed(status(0, 14.0, Goal::Speed(0.0), Some(100.0)).next_speed(), 0.0);
+        assert_speed(status(0, 100.0, Goal::Speed(50.0), Some(100.0)).next_speed(), 9800.0f64.sqrt());
+        assert_speed(status(0, 51.0, Goal::Speed(50.0), Some(100.0)).next_speed(), 50.0);
+    }
+
+    #[test]
+    fn reversal() {
+        assert_speed(status(0, 50.0, Goal::Position(-100), Some(100.0)).next_speed(), 2300.0f64.sqrt());
+        assert_speed(status(0, 50.0, Goal::Speed(-100.0), Some(100.0)).next_speed(), 2300.0f64.sqrt());
+        assert_speed(status(0, 10.0, Goal::Speed(-100.0), Some(100.0)).next_speed(), 0.0);
+        assert_speed(status(0, 50.0, Goal::Position(-100), None).next_speed(), 0.0);
+        assert_speed(status(0, 0.0, Goal::Position(-100), None).next_speed(), -100.0);
+    }
+}
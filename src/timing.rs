@@ -0,0 +1,114 @@
+//! Helpers for the wiringPi clock.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use bindings;
+
+///The number of microseconds since wiringPi was set up. It wraps after about
+///71 minutes, so only differences calculated with `wrapping_sub` are
+///meaningful.
+pub fn micros() -> u32 {
+    unsafe {
+        bindings::micros()
+    }
+}
+
+///Converts `duration` to microseconds, saturating at the longest duration
+///that `micros` can measure.
+pub fn duration_to_micros(duration: Duration) -> u32 {
+    let micros = duration.as_secs()
+        .saturating_mul(1_000_000)
+        .saturating_add(duration.subsec_micros() as u64);
+
+    if micros > u32::MAX as u64 {
+        u32::MAX
+    } else {
+        micros as u32
+    }
+}
+
+///Converts `duration` to nanoseconds, saturating at `u64::MAX`.
+pub fn duration_to_nanos(duration: Duration) -> u64 {
+    duration.as_secs().saturating_mul(1_000_000_000).saturating_add(duration.subsec_nanos() as u64)
+}
+
+///The time before a deadline that is spent spinning instead of sleeping.
+const SPIN: Duration = Duration::from_micros(100);
+
+///The longest sleep between checks of a stop flag.
+const SLEEP_CHUNK: Duration = Duration::from_millis(10);
+
+///Waits until `deadline`, sleeping for the bulk of the time and spinning for
+///the last 100μs for precise timing.
+pub fn delay_until(deadline: Instant) {
+    delay_until_or_stop(deadline, &AtomicBool::new(false));
+}
+
+///Waits like `delay_until`, but wakes up at least every 10 ms to check
+///`stop`, and returns `false` as soon as it's set.
+pub fn delay_until_or_stop(deadline: Instant, stop: &AtomicBool) -> bool {
+    loop {
+        if stop.load(Ordering::SeqCst) {
+            return false;
+        }
+
+        let remaining = deadline.saturating_duration_since(Instant::now());
+
+        if remaining <= SPIN {
+            break;
+        }
+
+        thread::sleep((remaining - SPIN).min(SLEEP_CHUNK));
+    }
+
+    while Instant::now() < deadline {}
+    true
+}
+
+///Extends `micros` to 64 bits by counting the times it wraps around.
+///
+///The wraparound can only be detected if `now` is called at least once every
+///71 minutes.
+#[derive(Default)]
+pub struct ExtendedMicros {
+    last: Option<u32>,
+    high: u64,
+}
+
+impl ExtendedMicros {
+    pub fn now(&mut self) -> u64 {
+        self.extend(micros())
+    }
+
+    ///Extends the raw `micros` timestamp `now`, which has to be at least as
+    ///late as the previous one.
+    pub fn extend(&mut self, now: u32) -> u64 {
+        if let Some(last) = self.last {
+            if now < last {
+                self.high += 1 << 32;
+            }
+        }
+
+        self.last = Some(now);
+        self.high | now as u64
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ExtendedMicros;
+
+    #[test]
+    fn extend_across_wraparound() {
+        let mut clock = ExtendedMicros::default();
+
+        assert_eq!(clock.extend(u32::MAX - 10), u32::MAX as u64 - 10);
+        assert_eq!(clock.extend(u32::MAX), u32::MAX as u64);
+        assert_eq!(clock.extend(5), (1 << 32) + 5);
+        assert_eq!(clock.extend(5), (1 << 32) + 5);
+        assert_eq!(clock.extend(u32::MAX), (1 << 32) + u32::MAX as u64);
+        assert_eq!(clock.extend(0), 2 << 32);
+    }
+}
@@ -0,0 +1,122 @@
+//! Tones and melodies.
+
+use std::thread;
+use std::time::Duration;
+
+use pin::{Pin, Pwm, RequiresRoot, SoftTonePin, TonePin};
+
+///An output that can play square wave tones.
+pub trait Tone {
+    ///Starts playing a tone at `frequency` Hz, or stops the current tone if
+    ///`frequency` is 0.
+    fn tone(&self, frequency: u32);
+
+    ///Stops the current tone.
+    fn no_tone(&self) {
+        self.tone(0);
+    }
+}
+
+impl<'a, P: Pin + Pwm> Tone for TonePin<'a, P> {
+    fn tone(&self, frequency: u32) {
+        self.play(frequency);
+    }
+}
+
+impl<P: Pin + RequiresRoot> Tone for SoftTonePin<P> {
+    fn tone(&self, frequency: u32) {
+        self.write(frequency);
+    }
+}
+
+///A note with a frequency and a duration. A frequency of 0 is a rest.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Note {
+    pub frequency: u32,
+    pub duration: Duration,
+}
+
+impl Note {
+    pub fn new(frequency: u32, duration: Duration) -> Note {
+        Note { frequency, duration }
+    }
+
+    ///A silent note.
+    pub fn rest(duration: Duration) -> Note {
+        Note::new(0, duration)
+    }
+
+    ///A note from its MIDI key number, where 69 is A4 at 440 Hz, rounded to
+    ///the nearest Hz.
+    pub fn from_midi(key: u8, duration: Duration) -> Note {
+        let frequency = 440.0 * 2f64.powf((key as f64 - 69.0) / 12.0);
+        Note::new(frequency.round() as u32, duration)
+    }
+}
+
+///A sequence of notes that can be played on any `Tone` output.
+///
+///```no_run
+///use std::time::Duration;
+///use wiringpi::tone::{Melody, Note};
+///
+///let pi = wiringpi::setup_gpio();
+///let pwm = pi.pwm_controller().unwrap();
+///let buzzer = pwm.pwm_pin(18).unwrap().into_tone().unwrap();
+///
+///let beat = Duration::from_millis(200);
+///let mut alarm = Melody::new(vec![
+///    Note::from_midi(76, beat),
+///    Note::from_midi(72, beat),
+///    Note::rest(beat),
+///]);
+///alarm.set_gap(Duration::from_millis(20));
+///alarm.play(&buzzer);
+///```
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Melody {
+    notes: Vec<Note>,
+    gap: Duration,
+}
+
+impl Melody {
+    pub fn new(notes: Vec<Note>) -> Melody {
+        Melody {
+            notes,
+            gap: Duration::from_millis(0),
+        }
+    }
+
+    ///Adds a note at the end of the melody.
+    pub fn push(&mut self, note: Note) {
+        self.notes.push(note);
+    }
+
+    pub fn notes(&self) -> &[Note] {
+        &self.notes
+    }
+
+    ///Sets a silent gap that is taken from the end of each note, to make
+    ///repeated notes distinguishable. The default is no gap.
+    pub fn set_gap(&mut self, gap: Duration) {
+        self.gap = gap;
+    }
+
+    ///The total duration of the melody.
+    pub fn duration(&self) -> Duration {
+        self.notes.iter().fold(Duration::from_millis(0), |total, note| total + note.duration)
+    }
+
+    ///Plays the melody on `output`, blocking the current thread until it's
+    ///done.
+    pub fn play<T: Tone + ?Sized>(&self, output: &T) {
+        for note in &self.notes {
+            let gap = if note.duration > self.gap { self.gap } else { note.duration };
+
+            output.tone(note.frequency);
+            thread::sleep(note.duration - gap);
+            output.no_tone();
+            thread::sleep(gap);
+        }
+    }
+}